@@ -1,6 +1,9 @@
-use std::{env, sync::Arc};
+use std::{env, path::PathBuf, sync::Arc, time::Duration};
 
-use turntable_collab::Collab;
+use turntable_collab::{
+    ClipConfig, Collab, CollabConfig, ExtractorFilter, InputConfig, LibraryConfig, RecordingConfig,
+    UploadConfig,
+};
 use turntable_core::Config;
 use turntable_server::run_server;
 
@@ -12,8 +15,164 @@ async fn main() {
 
     println!("Setting up Collab...");
 
-    let collab = Arc::new(Collab::new(Config::default(), &database_url).await);
+    let config = CollabConfig {
+        pipeline: pipeline_config(),
+        recording: recording_config(),
+        uploads: upload_config(),
+        library: library_config(),
+        inputs: input_config(),
+        clips: clip_config(),
+    };
+
+    let collab = Arc::new(Collab::new(config, &database_url).await);
 
     println!("Server running.");
     run_server(&collab).await
 }
+
+/// Reads how the audio of rooms is processed from the environment.
+fn pipeline_config() -> Config {
+    let default = Config::default();
+
+    Config {
+        stream_timeshift_window_in_seconds: env::var("TURNTABLE_TIMESHIFT_WINDOW_SECONDS")
+            .map(|x| {
+                x.parse()
+                    .expect("TURNTABLE_TIMESHIFT_WINDOW_SECONDS must be a number")
+            })
+            .unwrap_or(default.stream_timeshift_window_in_seconds),
+        ..default
+    }
+}
+
+/// Reads where and for how long room recordings are kept from the environment.
+fn recording_config() -> RecordingConfig {
+    let number = |name: &str| {
+        env::var(name).ok().map(|x| {
+            x.parse::<u64>()
+                .unwrap_or_else(|_| panic!("{name} must be a number"))
+        })
+    };
+
+    let default = RecordingConfig::default();
+
+    RecordingConfig {
+        directory: env::var("TURNTABLE_RECORDINGS_DIRECTORY")
+            .map(PathBuf::from)
+            .unwrap_or(default.directory),
+        file_duration: number("TURNTABLE_RECORDINGS_FILE_SECONDS")
+            .map(Duration::from_secs)
+            .unwrap_or(default.file_duration),
+        quota_in_bytes: number("TURNTABLE_RECORDINGS_QUOTA_BYTES"),
+        retention: number("TURNTABLE_RECORDINGS_RETENTION_SECONDS").map(Duration::from_secs),
+    }
+}
+
+/// Reads where uploaded files are stored, which are accepted and how many a request can have from the environment.
+fn upload_config() -> UploadConfig {
+    let default = UploadConfig::default();
+
+    UploadConfig {
+        directory: env::var("TURNTABLE_MEDIA_DIRECTORY")
+            .map(PathBuf::from)
+            .unwrap_or(default.directory),
+        max_size_in_bytes: env::var("TURNTABLE_UPLOAD_MAX_BYTES")
+            .map(|x| {
+                x.parse()
+                    .expect("TURNTABLE_UPLOAD_MAX_BYTES must be a number")
+            })
+            .unwrap_or(default.max_size_in_bytes),
+        max_fields: env::var("TURNTABLE_UPLOAD_MAX_FIELDS")
+            .map(|x| {
+                x.parse()
+                    .expect("TURNTABLE_UPLOAD_MAX_FIELDS must be a number")
+            })
+            .unwrap_or(default.max_fields),
+        content_types: env::var("TURNTABLE_UPLOAD_CONTENT_TYPES")
+            .map(|x| x.split(',').map(|t| t.trim().to_string()).collect())
+            .unwrap_or(default.content_types),
+    }
+}
+
+/// Reads which directories make up the music library from the environment.
+fn library_config() -> LibraryConfig {
+    let default = LibraryConfig::default();
+
+    LibraryConfig {
+        directories: env::var_os("TURNTABLE_LIBRARY_DIRECTORIES")
+            .map(|x| env::split_paths(&x).collect())
+            .unwrap_or(default.directories),
+        watch: env::var("TURNTABLE_LIBRARY_WATCH")
+            .map(|x| {
+                x.parse()
+                    .expect("TURNTABLE_LIBRARY_WATCH must be true or false")
+            })
+            .unwrap_or(default.watch),
+    }
+}
+
+/// Reads which directories files can be played from with `file://`, which yt-dlp extractors can be used, where yt-dlp is and how long it can take from the environment.
+fn input_config() -> InputConfig {
+    let default = InputConfig::default();
+    let names = |x: String| {
+        x.split(',')
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .collect()
+    };
+
+    let extractors = match (
+        env::var("TURNTABLE_YTDLP_ALLOW"),
+        env::var("TURNTABLE_YTDLP_DENY"),
+    ) {
+        (Ok(_), Ok(_)) => {
+            panic!("Only one of TURNTABLE_YTDLP_ALLOW and TURNTABLE_YTDLP_DENY can be set")
+        }
+        (Ok(allow), _) => ExtractorFilter::Allow(names(allow)),
+        (_, Ok(deny)) => ExtractorFilter::Deny(names(deny)),
+        _ => default.extractors,
+    };
+
+    InputConfig {
+        media_roots: env::var_os("TURNTABLE_MEDIA_ROOTS")
+            .map(|x| env::split_paths(&x).collect())
+            .unwrap_or(default.media_roots),
+        extractors,
+        yt_dlp_path: env::var_os("TURNTABLE_YTDLP_PATH")
+            .map(PathBuf::from)
+            .or(default.yt_dlp_path),
+        yt_dlp_timeout: env::var("TURNTABLE_YTDLP_TIMEOUT_SECONDS")
+            .map(|x| {
+                Duration::from_secs(
+                    x.parse()
+                        .expect("TURNTABLE_YTDLP_TIMEOUT_SECONDS must be a number"),
+                )
+            })
+            .unwrap_or(default.yt_dlp_timeout),
+    }
+}
+
+/// Reads where captured clips are stored, and for how long, from the environment.
+fn clip_config() -> ClipConfig {
+    let default = ClipConfig::default();
+
+    ClipConfig {
+        directory: env::var("TURNTABLE_CLIPS_DIRECTORY")
+            .map(PathBuf::from)
+            .unwrap_or(default.directory),
+        lifetime: env::var("TURNTABLE_CLIP_LIFETIME_SECONDS")
+            .map(|x| {
+                Duration::from_secs(
+                    x.parse()
+                        .expect("TURNTABLE_CLIP_LIFETIME_SECONDS must be a number"),
+                )
+            })
+            .unwrap_or(default.lifetime),
+        quota_in_bytes: env::var("TURNTABLE_CLIPS_QUOTA_BYTES")
+            .map(|x| {
+                x.parse()
+                    .expect("TURNTABLE_CLIPS_QUOTA_BYTES must be a number")
+            })
+            .unwrap_or(default.quota_in_bytes),
+    }
+}
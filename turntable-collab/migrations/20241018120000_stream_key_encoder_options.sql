@@ -0,0 +1,5 @@
+-- Add migration script here
+
+ALTER TABLE stream_keys
+  ADD COLUMN bitrate INT,
+  ADD COLUMN complexity INT;
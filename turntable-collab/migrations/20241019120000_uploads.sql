@@ -0,0 +1,17 @@
+-- Add migration script here
+
+CREATE TABLE uploads (
+  id SERIAL PRIMARY KEY,
+  hash TEXT NOT NULL,
+  file_name TEXT NOT NULL,
+  content_type TEXT NOT NULL,
+  size BIGINT NOT NULL,
+  title TEXT,
+  artist TEXT,
+  duration REAL,
+  room_id INT NOT NULL REFERENCES rooms (id) ON DELETE CASCADE,
+  user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX upload_unique ON uploads (hash, room_id);
@@ -0,0 +1,16 @@
+-- Add migration script here
+
+CREATE TABLE library_tracks (
+  id SERIAL PRIMARY KEY,
+  path TEXT NOT NULL UNIQUE,
+  title TEXT NOT NULL,
+  artist TEXT,
+  album TEXT,
+  duration REAL,
+  artwork_type TEXT,
+  size BIGINT NOT NULL,
+  modified_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX library_track_artist ON library_tracks (artist);
+CREATE INDEX library_track_album ON library_tracks (album);
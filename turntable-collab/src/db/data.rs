@@ -69,4 +69,69 @@ pub struct StreamKeyData {
     pub room_id: PrimaryKey,
     /// The user this stream key belongs to
     pub user_id: PrimaryKey,
+    /// The default format name for streams using this key
+    pub format: Option<String>,
+    /// The default encoder bitrate for streams using this key, in bits per second
+    pub bitrate: Option<i32>,
+    /// The default encoder complexity for streams using this key
+    pub complexity: Option<i32>,
+}
+
+/// An audio file that was uploaded to a room
+/// Note: `hash` and `room_id` are unique together, but the same file can be uploaded to several rooms.
+#[derive(Debug, Clone)]
+pub struct UploadData {
+    pub id: PrimaryKey,
+    /// The SHA-256 hash of the contents, which is also the name of the stored file
+    pub hash: String,
+    /// The name of the file as it was uploaded
+    pub file_name: String,
+    pub content_type: String,
+    /// The size of the file in bytes
+    pub size: i64,
+    /// The title from the tags of the file
+    pub title: Option<String>,
+    /// The artist from the tags of the file
+    pub artist: Option<String>,
+    /// The length of the file in seconds, if known
+    pub duration: Option<f32>,
+    pub room_id: PrimaryKey,
+    /// The user that uploaded the file, who is the only one that can delete it
+    pub user_id: PrimaryKey,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An audio file in one of the library directories
+#[derive(Debug, Clone)]
+pub struct LibraryTrackData {
+    pub id: PrimaryKey,
+    /// The absolute path of the file
+    pub path: String,
+    /// The title from the tags of the file, or its name without the extension
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// The length of the file in seconds, if known
+    pub duration: Option<f32>,
+    /// The media type of the embedded cover art, if the file has any
+    pub artwork_type: Option<String>,
+    /// The size of the file when it was scanned, used to detect changes
+    pub size: i64,
+    /// When the file was last modified when it was scanned, used to detect changes
+    pub modified_at: DateTime<Utc>,
+}
+
+/// An artist in the library, with how many tracks they have
+#[derive(Debug, Clone)]
+pub struct LibraryArtistData {
+    pub name: String,
+    pub track_count: i64,
+}
+
+/// An album in the library, with how many tracks it has
+#[derive(Debug, Clone)]
+pub struct LibraryAlbumData {
+    pub title: String,
+    pub artist: Option<String>,
+    pub track_count: i64,
 }
@@ -37,6 +37,8 @@ pub enum DatabaseError {
 /// Helper trait to reduce boilerplate
 pub trait IntoDatabaseError {
     fn not_found_or(self, resource: &'static str, identifier: &'static str) -> DatabaseError;
+    fn conflict_or(self, resource: &'static str, field: &'static str, value: &str)
+        -> DatabaseError;
     fn any(self) -> DatabaseError;
 }
 
@@ -106,6 +108,24 @@ pub trait Database {
         user_id: PrimaryKey,
     ) -> Result<Vec<StreamKeyData>>;
     async fn delete_stream_key(&self, key_id: PrimaryKey) -> Result<()>;
+
+    async fn upload_by_id(&self, upload_id: PrimaryKey) -> Result<UploadData>;
+    async fn upload_by_hash(&self, room_id: PrimaryKey, hash: &str) -> Result<UploadData>;
+    async fn create_upload(&self, new_upload: NewUpload) -> Result<UploadData>;
+    async fn list_uploads(&self, room_id: PrimaryKey) -> Result<Vec<UploadData>>;
+    /// Returns how many rooms the file with the given hash is uploaded to
+    async fn count_uploads_by_hash(&self, hash: &str) -> Result<i64>;
+    async fn delete_upload(&self, upload_id: PrimaryKey) -> Result<()>;
+
+    async fn library_track_by_id(&self, track_id: PrimaryKey) -> Result<LibraryTrackData>;
+    /// Returns every track in the library, to compare against the files on disk
+    async fn all_library_tracks(&self) -> Result<Vec<LibraryTrackData>>;
+    async fn list_library_tracks(&self, filter: LibraryFilter) -> Result<Vec<LibraryTrackData>>;
+    async fn list_library_artists(&self) -> Result<Vec<LibraryArtistData>>;
+    async fn list_library_albums(&self, artist: Option<&str>) -> Result<Vec<LibraryAlbumData>>;
+    /// Creates a library track, or updates the one with the same path
+    async fn upsert_library_track(&self, track: NewLibraryTrack) -> Result<LibraryTrackData>;
+    async fn delete_library_tracks(&self, paths: &[String]) -> Result<()>;
 }
 
 #[derive(Debug)]
@@ -166,4 +186,43 @@ pub struct NewStreamKey {
     pub room_id: PrimaryKey,
     pub user_id: PrimaryKey,
     pub source: String,
+    pub format: Option<String>,
+    pub bitrate: Option<i32>,
+    pub complexity: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct NewUpload {
+    pub hash: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: i64,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration: Option<f32>,
+    pub room_id: PrimaryKey,
+    pub user_id: PrimaryKey,
+}
+
+#[derive(Debug)]
+pub struct NewLibraryTrack {
+    pub path: String,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<f32>,
+    pub artwork_type: Option<String>,
+    pub size: i64,
+    pub modified_at: DateTime<Utc>,
+}
+
+/// Narrows down the tracks of the library. Every field that is set has to match.
+#[derive(Debug, Default)]
+pub struct LibraryFilter {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Text that is searched for in the title, artist and album, ignoring case
+    pub text: Option<String>,
+    pub limit: i64,
+    pub offset: i64,
 }
@@ -2,9 +2,11 @@ use async_trait::async_trait;
 use sqlx::{postgres::PgPoolOptions, query, query_as, Error as SqlxError, PgPool};
 
 use crate::{
-    Database, DatabaseError, DatabaseResult, IntoDatabaseError, NewRoom, NewRoomInvite,
-    NewRoomMember, NewSession, NewStreamKey, NewUser, PrimaryKey, Result, RoomData, RoomInviteData,
-    RoomMemberData, SessionData, StreamKeyData, UpdatedRoom, UpdatedUser, UserData,
+    Database, DatabaseError, DatabaseResult, IntoDatabaseError, LibraryAlbumData,
+    LibraryArtistData, LibraryFilter, LibraryTrackData, NewLibraryTrack, NewRoom, NewRoomInvite,
+    NewRoomMember, NewSession, NewStreamKey, NewUpload, NewUser, PrimaryKey, Result, RoomData,
+    RoomInviteData, RoomMemberData, SessionData, StreamKeyData, UpdatedRoom, UpdatedUser,
+    UploadData, UserData,
 };
 
 /// A postgres database implementation for turntable
@@ -494,11 +496,14 @@ impl Database for PgDatabase {
             .as_str(),
         )?;
 
-        query_as!(StreamKeyData, "INSERT INTO stream_keys (token, source, room_id, user_id) VALUES ($1, $2, $3, $4) RETURNING *",
+        query_as!(StreamKeyData, "INSERT INTO stream_keys (token, source, room_id, user_id, bitrate, complexity, format) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
             new_key.token,
             new_key.source,
             new_key.room_id,
-            new_key.user_id
+            new_key.user_id,
+            new_key.bitrate,
+            new_key.complexity,
+            new_key.format
         ).fetch_one(&self.pool).await.map_err(|e| e.any())
     }
 
@@ -531,6 +536,178 @@ impl Database for PgDatabase {
             .map_err(|e| e.any())
             .map(|_| ())
     }
+
+    async fn upload_by_id(&self, upload_id: PrimaryKey) -> Result<UploadData> {
+        query_as!(UploadData, "SELECT * FROM uploads WHERE id = $1", upload_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.not_found_or("upload", "id"))
+    }
+
+    async fn upload_by_hash(&self, room_id: PrimaryKey, hash: &str) -> Result<UploadData> {
+        query_as!(
+            UploadData,
+            "SELECT * FROM uploads WHERE room_id = $1 AND hash = $2",
+            room_id,
+            hash
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.not_found_or("upload", "hash"))
+    }
+
+    async fn create_upload(&self, new_upload: NewUpload) -> Result<UploadData> {
+        self.upload_by_hash(new_upload.room_id, &new_upload.hash)
+            .await
+            .conflict_or_ok("upload", "hash", &new_upload.hash)?;
+
+        query_as!(UploadData, "INSERT INTO uploads (hash, file_name, content_type, size, title, artist, duration, room_id, user_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *",
+            new_upload.hash,
+            new_upload.file_name,
+            new_upload.content_type,
+            new_upload.size,
+            new_upload.title,
+            new_upload.artist,
+            new_upload.duration,
+            new_upload.room_id,
+            new_upload.user_id
+        ).fetch_one(&self.pool).await.map_err(|e| e.conflict_or("upload", "hash", &new_upload.hash))
+    }
+
+    async fn list_uploads(&self, room_id: PrimaryKey) -> Result<Vec<UploadData>> {
+        query_as!(
+            UploadData,
+            "SELECT * FROM uploads WHERE room_id = $1 ORDER BY created_at",
+            room_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.any())
+    }
+
+    async fn count_uploads_by_hash(&self, hash: &str) -> Result<i64> {
+        query!(
+            r#"SELECT COUNT(*) AS "count!" FROM uploads WHERE hash = $1"#,
+            hash
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map(|r| r.count)
+        .map_err(|e| e.any())
+    }
+
+    async fn delete_upload(&self, upload_id: PrimaryKey) -> Result<()> {
+        // Ensure upload exists
+        query!("SELECT id FROM uploads WHERE id = $1", upload_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.not_found_or("upload", "id"))?;
+
+        query!("DELETE FROM uploads WHERE id = $1", upload_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.any())
+            .map(|_| ())
+    }
+
+    async fn library_track_by_id(&self, track_id: PrimaryKey) -> Result<LibraryTrackData> {
+        query_as!(
+            LibraryTrackData,
+            "SELECT * FROM library_tracks WHERE id = $1",
+            track_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.not_found_or("library track", "id"))
+    }
+
+    async fn all_library_tracks(&self) -> Result<Vec<LibraryTrackData>> {
+        query_as!(LibraryTrackData, "SELECT * FROM library_tracks")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.any())
+    }
+
+    async fn list_library_tracks(&self, filter: LibraryFilter) -> Result<Vec<LibraryTrackData>> {
+        // Wildcards in the searched text are matched literally
+        let pattern = filter.text.map(|t| {
+            let escaped = t
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+
+            format!("%{}%", escaped)
+        });
+
+        query_as!(
+            LibraryTrackData,
+            "
+            SELECT * FROM library_tracks
+            WHERE ($1::TEXT IS NULL OR artist = $1)
+                AND ($2::TEXT IS NULL OR album = $2)
+                AND ($3::TEXT IS NULL OR title ILIKE $3 OR artist ILIKE $3 OR album ILIKE $3)
+            ORDER BY artist, album, title
+            LIMIT $4 OFFSET $5",
+            filter.artist,
+            filter.album,
+            pattern,
+            filter.limit,
+            filter.offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.any())
+    }
+
+    async fn list_library_artists(&self) -> Result<Vec<LibraryArtistData>> {
+        query_as!(
+            LibraryArtistData,
+            r#"
+            SELECT artist AS "name!", COUNT(*) AS "track_count!" FROM library_tracks
+            WHERE artist IS NOT NULL
+            GROUP BY artist
+            ORDER BY artist"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.any())
+    }
+
+    async fn list_library_albums(&self, artist: Option<&str>) -> Result<Vec<LibraryAlbumData>> {
+        query_as!(
+            LibraryAlbumData,
+            r#"
+            SELECT album AS "title!", artist, COUNT(*) AS "track_count!" FROM library_tracks
+            WHERE album IS NOT NULL AND ($1::TEXT IS NULL OR artist = $1)
+            GROUP BY album, artist
+            ORDER BY album"#,
+            artist
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.any())
+    }
+
+    async fn upsert_library_track(&self, track: NewLibraryTrack) -> Result<LibraryTrackData> {
+        query_as!(LibraryTrackData, "INSERT INTO library_tracks (path, title, artist, album, duration, artwork_type, size, modified_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (path) DO UPDATE SET title = $2, artist = $3, album = $4, duration = $5, artwork_type = $6, size = $7, modified_at = $8 RETURNING *",
+            track.path,
+            track.title,
+            track.artist,
+            track.album,
+            track.duration,
+            track.artwork_type,
+            track.size,
+            track.modified_at
+        ).fetch_one(&self.pool).await.map_err(|e| e.any())
+    }
+
+    async fn delete_library_tracks(&self, paths: &[String]) -> Result<()> {
+        query!("DELETE FROM library_tracks WHERE path = ANY($1)", paths)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.any())
+            .map(|_| ())
+    }
 }
 
 impl IntoDatabaseError for SqlxError {
@@ -547,4 +724,20 @@ impl IntoDatabaseError for SqlxError {
             e => Self::any(e),
         }
     }
+
+    fn conflict_or(
+        self,
+        resource: &'static str,
+        field: &'static str,
+        value: &str,
+    ) -> DatabaseError {
+        match self {
+            SqlxError::Database(e) if e.is_unique_violation() => DatabaseError::Conflict {
+                resource,
+                field,
+                value: value.to_string(),
+            },
+            e => Self::any(e),
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use crossbeam::channel::{Receiver, Sender};
-use turntable_core::{PipelineEvent, PlayerState};
+use turntable_core::{HeardPosition, PipelineEvent, PlayerState};
 
 use crate::{CollabContext, LinearQueueItem, PrimaryKey, RoomMemberData, TrackId};
 
@@ -21,6 +21,8 @@ pub enum CollabEvent {
         position: f32,
         /// The total position of the player, in seconds.
         total_position: f32,
+        /// The estimated positions listeners hear, for every stream format.
+        heard_positions: Vec<HeardPosition>,
     },
     /// A track as a queue item has been ingested
     TrackActivated {
@@ -38,10 +40,18 @@ pub enum CollabEvent {
         /// The error that happened while activating the queue item.
         error: String,
     },
+    /// The title of what a live track is playing changed
+    TrackStreamTitleUpdate {
+        room_id: PrimaryKey,
+        track_id: TrackId,
+        title: String,
+    },
     /// The currently playing track of a room updated
     RoomQueueItemUpdate {
         room_id: PrimaryKey,
         new_item: Option<LinearQueueItem>,
+        /// The total position the new item starts at, in seconds of stream time.
+        total_position: f32,
     },
     /// A queue was modified and updated
     RoomQueueUpdate {
@@ -93,18 +103,24 @@ impl CollabEvent {
                 player_id,
                 position,
                 total_position,
+                heard_positions,
             } => context
                 .room_by_player_id(player_id)
                 .map(|room| Self::PlayerTimeUpdate {
                     room_id: room.id(),
                     position,
                     total_position,
+                    heard_positions,
                 }),
-            PipelineEvent::PlayerAdvanced { player_id } => context
+            PipelineEvent::PlayerAdvanced {
+                player_id,
+                total_position,
+            } => context
                 .room_by_player_id(player_id)
                 .map(|room| Self::RoomQueueItemUpdate {
                     room_id: room.id(),
                     new_item: room.current_item(),
+                    total_position,
                 }),
             _ => None,
         }
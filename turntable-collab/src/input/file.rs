@@ -2,12 +2,15 @@ use async_trait::async_trait;
 use lazy_static::lazy_static;
 use parking_lot::Mutex;
 use regex::Regex;
-use std::fmt::Debug;
-use tokio::fs::File;
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+use tokio::fs::{self, File};
 use turntable_core::{BoxedLoadable, Loadable};
-use turntable_impls::LoadableFile;
+use turntable_impls::{read_tags, LoadableFile, MediaArtwork, MediaTags};
 
-use crate::{InputError, Inputable, Metadata};
+use crate::{InputConfig, InputError, Inputable, Metadata};
 
 lazy_static! {
     static ref REGEX: Regex = Regex::new(r"^file://([a-zA-Z0-9_/\\:-]+\.[a-zA-Z0-9_]+)$").unwrap();
@@ -17,6 +20,15 @@ lazy_static! {
 pub struct FileInput {
     file: Mutex<Option<File>>,
     path: String,
+    /// The tags that were read when the file was probed
+    tags: MediaTags,
+}
+
+impl FileInput {
+    /// Returns the cover art that is embedded in the file, if any.
+    pub fn artwork(&self) -> Option<&MediaArtwork> {
+        self.tags.artwork.as_ref()
+    }
 }
 
 #[async_trait]
@@ -25,51 +37,273 @@ impl Inputable for FileInput {
         REGEX.is_match(query)
     }
 
-    async fn fetch(query: &str) -> Result<Vec<Self>, InputError>
+    async fn fetch(query: &str, config: &InputConfig) -> Result<Vec<Self>, InputError>
     where
         Self: Sized,
     {
+        // File inputs are disabled without media roots, rather than every path being denied
+        if config.media_roots.is_empty() {
+            return Err(InputError::NoMatch);
+        }
+
         let path = REGEX
             .captures(query)
             .and_then(|c| c.get(1))
             .map(|m| m.as_str())
             .ok_or(InputError::Invalid)?;
 
-        let file = File::open(path)
+        let path = resolve_path(Path::new(path), &config.media_roots).await?;
+
+        let file = File::open(&path)
+            .await
+            .map_err(|e| InputError::Other(e.to_string()))?;
+
+        // The file is probed through a handle of its own, so the one that is kept still starts at the beginning
+        let probed = File::open(&path)
             .await
             .map_err(|e| InputError::Other(e.to_string()))?;
 
+        let tags = read_tags(LoadableFile::new(probed))
+            .await
+            .map_err(|e| InputError::ParseError(e.to_string()))?;
+
         Ok(vec![Self {
-            path: path.to_string(),
+            path: path.to_string_lossy().to_string(),
             file: Mutex::new(Some(file)),
+            tags,
         }])
     }
 
     fn length(&self) -> Option<f32> {
-        None
+        self.tags.duration
     }
 
     async fn loadable(&self) -> Result<BoxedLoadable, InputError> {
-        let file = self.file.lock().take().expect("file is taken");
+        let file = self.file.lock().take();
+
+        // The file is opened again when it's loaded more than once, such as when it's recovered
+        let file = match file {
+            Some(file) => file,
+            None => File::open(&self.path)
+                .await
+                .map_err(|e| InputError::Other(e.to_string()))?,
+        };
+
         let boxed = LoadableFile::new(file).boxed();
 
         Ok(boxed)
     }
 
     fn metadata(&self) -> Metadata {
+        let title = self.tags.title.clone().unwrap_or_else(|| {
+            Path::new(&self.path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| self.path.clone())
+        });
+
         Metadata {
-            title: self.path.clone(),
-            artist: None,
+            title,
+            artist: self.tags.artist.clone(),
+            album: self.tags.album.clone(),
             canonical: self.path.clone(),
             source: "file".to_string(),
-            duration: 0.,
+            duration: self.tags.duration.unwrap_or_default(),
             artwork: None,
         }
     }
 }
 
+/// Resolves a path to the file it points to, which has to be inside one of the media roots.
+///
+/// Relative paths are relative to the media roots. Paths are canonicalised first, so symlinks can't lead outside of them.
+async fn resolve_path(path: &Path, roots: &[PathBuf]) -> Result<PathBuf, InputError> {
+    let denied = || InputError::PathDenied(path.to_string_lossy().to_string());
+    let mut inside_root = false;
+
+    for root in roots {
+        let Ok(root) = fs::canonicalize(root).await else {
+            continue;
+        };
+
+        let candidate = root.join(path);
+
+        match fs::canonicalize(&candidate).await {
+            Ok(resolved) if resolved.starts_with(&root) => return Ok(resolved),
+            // The path leads outside of this root, such as through a symlink
+            Ok(_) => {}
+            Err(_) => inside_root |= candidate.starts_with(&root),
+        }
+    }
+
+    // Whether a file outside of the roots exists is not revealed
+    if inside_root {
+        Err(InputError::NotFound)
+    } else {
+        Err(denied())
+    }
+}
+
 impl Debug for FileInput {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "File: {}", &self.path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend((data.len() as u32).to_le_bytes());
+        chunk.extend(data);
+
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+
+        chunk
+    }
+
+    /// Two seconds of silent 8 kHz mono 16-bit audio with a LIST INFO chunk.
+    fn tagged_wave() -> Vec<u8> {
+        let sample_rate = 8000u32;
+        let mut format = vec![];
+        format.extend(1u16.to_le_bytes());
+        format.extend(1u16.to_le_bytes());
+        format.extend(sample_rate.to_le_bytes());
+        format.extend((sample_rate * 2).to_le_bytes());
+        format.extend(2u16.to_le_bytes());
+        format.extend(16u16.to_le_bytes());
+
+        let mut info = b"INFO".to_vec();
+        info.extend(chunk(b"INAM", b"Pipes\0"));
+        info.extend(chunk(b"IART", b"Turntable\0"));
+        info.extend(chunk(b"IPRD", b"Fixtures\0"));
+
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", &format));
+        body.extend(chunk(b"LIST", &info));
+        body.extend(chunk(b"data", &vec![0; sample_rate as usize * 4]));
+
+        chunk(b"RIFF", &body)
+    }
+
+    #[tokio::test]
+    async fn test_resolve_path_without_roots_is_denied() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("track.wav");
+        std::fs::write(&path, [0; 16]).unwrap();
+
+        let result = resolve_path(&path, &[]).await;
+        assert!(matches!(result, Err(InputError::PathDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_without_roots_does_not_match() {
+        let query = "file://track.wav";
+        let config = InputConfig::default();
+
+        let result = FileInput::fetch(query, &config).await;
+        assert!(matches!(result, Err(InputError::NoMatch)));
+
+        let result = crate::Input::query(query, &config).await;
+        assert!(matches!(result, Err(InputError::NoMatch)));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_path_outside_of_roots_is_denied() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let path = outside.path().join("track.wav");
+        std::fs::write(&path, [0; 16]).unwrap();
+
+        let roots = [root.path().to_path_buf()];
+
+        let result = resolve_path(&path, &roots).await;
+        assert!(matches!(result, Err(InputError::PathDenied(_))));
+
+        // Leaving the root through the parent directory is no different
+        let escaping = Path::new("..")
+            .join(outside.path().file_name().unwrap())
+            .join("track.wav");
+
+        let result = resolve_path(&escaping, &roots).await;
+        assert!(matches!(result, Err(InputError::PathDenied(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_resolve_path_through_symlink_out_of_root_is_denied() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let target = outside.path().join("track.wav");
+        std::fs::write(&target, [0; 16]).unwrap();
+
+        let link = root.path().join("link.wav");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let roots = [root.path().to_path_buf()];
+
+        assert!(matches!(
+            resolve_path(Path::new("link.wav"), &roots).await,
+            Err(InputError::PathDenied(_))
+        ));
+        assert!(matches!(
+            resolve_path(&link, &roots).await,
+            Err(InputError::PathDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_path_of_missing_file_in_root_is_not_found() {
+        let root = tempfile::tempdir().unwrap();
+        let roots = [root.path().to_path_buf()];
+
+        let result = resolve_path(Path::new("missing.wav"), &roots).await;
+        assert!(matches!(result, Err(InputError::NotFound)));
+
+        let result = resolve_path(&root.path().join("missing.wav"), &roots).await;
+        assert!(matches!(result, Err(InputError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_path_inside_root() {
+        let other = tempfile::tempdir().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("album")).unwrap();
+        std::fs::write(root.path().join("album").join("track.wav"), [0; 16]).unwrap();
+
+        let roots = [other.path().to_path_buf(), root.path().to_path_buf()];
+        let expected = std::fs::canonicalize(root.path().join("album").join("track.wav")).unwrap();
+
+        let relative = resolve_path(Path::new("album/track.wav"), &roots).await;
+        assert_eq!(relative.unwrap(), expected);
+
+        let absolute = resolve_path(&root.path().join("album").join("track.wav"), &roots).await;
+        assert_eq!(absolute.unwrap(), expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fetch_reads_tags() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("tagged.wav"), tagged_wave()).unwrap();
+
+        let config = InputConfig {
+            media_roots: vec![directory.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let mut inputs = FileInput::fetch("file://tagged.wav", &config)
+            .await
+            .unwrap();
+        let metadata = inputs.remove(0).metadata();
+
+        assert_eq!(metadata.title, "Pipes");
+        assert_eq!(metadata.artist.as_deref(), Some("Turntable"));
+        assert_eq!(metadata.album.as_deref(), Some("Fixtures"));
+        assert_eq!(metadata.duration, 2.);
+        assert_eq!(metadata.source, "file");
+    }
+}
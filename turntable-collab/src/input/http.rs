@@ -0,0 +1,178 @@
+use async_trait::async_trait;
+use std::fmt::Debug;
+use turntable_core::{BoxedLoadable, Loadable};
+use turntable_impls::{percent_decode, read_tags, LoadableNetworkStream, MediaArtwork, MediaTags};
+
+use crate::{InputConfig, InputError, Inputable, Metadata};
+
+use super::{AUDIO_EXTENSIONS, HTTP_URL_REGEX};
+
+/// An audio file served over HTTP(S).
+pub struct HttpFileInput {
+    url: String,
+    /// The file name from the server's Content-Disposition header, if any
+    file_name: Option<String>,
+    tags: MediaTags,
+}
+
+impl HttpFileInput {
+    /// How much of the file is read for its tags, which is enough for the headers and a large embedded cover.
+    const TAGS_PREFIX_SIZE: usize = 8 * 1024 * 1024;
+
+    /// Returns the cover art that is embedded in the file, if any.
+    pub fn artwork(&self) -> Option<&MediaArtwork> {
+        self.tags.artwork.as_ref()
+    }
+
+    /// Returns true if the content type is audio, falling back to the url's extension
+    /// when the server doesn't know what it's serving.
+    fn is_audio(content_type: Option<&str>, url: &str) -> bool {
+        match content_type {
+            Some("application/ogg") => true,
+            Some(t) if t.starts_with("audio/") => true,
+            None | Some("application/octet-stream") | Some("binary/octet-stream") => {
+                Self::has_audio_extension(url)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if the url points to a file with the extension of an audio file.
+    pub(super) fn has_audio_extension(url: &str) -> bool {
+        Self::last_segment(url)
+            .and_then(|segment| segment.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_ascii_lowercase())
+            .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.as_str()))
+    }
+
+    /// Returns the last path segment of the url, without the query or fragment.
+    fn last_segment(url: &str) -> Option<&str> {
+        let path = url.split(['?', '#']).next()?;
+        let (_, path) = path.split_once("://")?;
+
+        path.split_once('/')
+            .and_then(|(_, path)| path.rsplit('/').next())
+            .filter(|segment| !segment.is_empty())
+    }
+}
+
+#[async_trait]
+impl Inputable for HttpFileInput {
+    fn test(query: &str) -> bool {
+        HTTP_URL_REGEX.is_match(query)
+    }
+
+    async fn fetch(query: &str, _config: &InputConfig) -> Result<Vec<Self>, InputError>
+    where
+        Self: Sized,
+    {
+        let stream = LoadableNetworkStream::new(query)
+            .await
+            .map_err(|_| InputError::NetworkFailed)?;
+
+        if !Self::is_audio(stream.content_type(), stream.url()) {
+            return Err(InputError::UnsupportedType);
+        }
+
+        let file_name = stream.file_name();
+        let tags = read_tags(stream.with_limit(Self::TAGS_PREFIX_SIZE))
+            .await
+            .map_err(|e| InputError::ParseError(e.to_string()))?;
+
+        Ok(vec![Self {
+            url: query.to_string(),
+            file_name,
+            tags,
+        }])
+    }
+
+    fn length(&self) -> Option<f32> {
+        self.tags.duration
+    }
+
+    async fn loadable(&self) -> Result<BoxedLoadable, InputError> {
+        let stream = LoadableNetworkStream::new(self.url.clone())
+            .await
+            .map_err(|_| InputError::NetworkFailed)?;
+
+        Ok(stream.boxed())
+    }
+
+    fn metadata(&self) -> Metadata {
+        let title = self
+            .tags
+            .title
+            .clone()
+            .or_else(|| self.file_name.clone())
+            .or_else(|| Self::last_segment(&self.url).map(percent_decode))
+            .unwrap_or_else(|| self.url.clone());
+
+        Metadata {
+            title,
+            artist: self.tags.artist.clone(),
+            album: self.tags.album.clone(),
+            canonical: self.url.clone(),
+            source: "http".to_string(),
+            duration: self.tags.duration.unwrap_or_default(),
+            artwork: None,
+        }
+    }
+}
+
+impl Debug for HttpFileInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Http: {}", &self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_segment() {
+        let last_segment = HttpFileInput::last_segment;
+
+        assert_eq!(
+            last_segment("https://example.com/music/song.mp3"),
+            Some("song.mp3")
+        );
+        assert_eq!(
+            last_segment("https://example.com/song.mp3?token=a/b#t=10"),
+            Some("song.mp3")
+        );
+        assert_eq!(last_segment("https://example.com/music/"), None);
+        assert_eq!(last_segment("https://example.com"), None);
+        assert_eq!(last_segment("example.com/song.mp3"), None);
+    }
+
+    #[test]
+    fn test_is_audio() {
+        let is_audio = HttpFileInput::is_audio;
+        let url = "https://example.com/stream";
+
+        assert!(is_audio(Some("audio/mpeg"), url));
+        assert!(is_audio(Some("application/ogg"), url));
+        assert!(!is_audio(Some("text/html"), "https://example.com/song.mp3"));
+
+        // Unknown types fall back to the extension
+        assert!(is_audio(None, "https://example.com/Song.FLAC"));
+        assert!(is_audio(
+            Some("application/octet-stream"),
+            "https://example.com/song.opus?download=1"
+        ));
+        assert!(!is_audio(Some("binary/octet-stream"), url));
+        assert!(!is_audio(None, "https://example.com/archive.zip"));
+    }
+
+    #[test]
+    fn test_has_audio_extension() {
+        let has_audio_extension = HttpFileInput::has_audio_extension;
+
+        assert!(has_audio_extension("https://example.com/music/song.mp3"));
+        assert!(has_audio_extension("https://example.com/Song.M4A?t=10"));
+        assert!(!has_audio_extension("https://youtube.com/watch?v=song.mp3"));
+        assert!(!has_audio_extension("https://example.com/song.mp3/"));
+        assert!(!has_audio_extension("https://example.com"));
+    }
+}
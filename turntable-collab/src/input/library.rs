@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt::Debug;
+use tokio::fs::File;
+use turntable_core::{BoxedLoadable, Loadable};
+use turntable_impls::LoadableFile;
+
+use crate::{InputConfig, InputError, Inputable, LibraryTrackData, Metadata};
+
+lazy_static! {
+    static ref REGEX: Regex = Regex::new(r"^library://(\d+)$").unwrap();
+}
+
+/// A file in the library, referred to as `library://{id}`.
+///
+/// Library tracks are looked up in the database, so they are created by the room they're enqueued in instead of being fetched.
+pub struct LibraryInput {
+    track: LibraryTrackData,
+}
+
+impl LibraryInput {
+    pub fn new(track: LibraryTrackData) -> Self {
+        Self { track }
+    }
+}
+
+#[async_trait]
+impl Inputable for LibraryInput {
+    fn test(query: &str) -> bool {
+        REGEX.is_match(query)
+    }
+
+    async fn fetch(_query: &str, _config: &InputConfig) -> Result<Vec<Self>, InputError>
+    where
+        Self: Sized,
+    {
+        Err(InputError::NoMatch)
+    }
+
+    fn length(&self) -> Option<f32> {
+        self.track.duration
+    }
+
+    async fn loadable(&self) -> Result<BoxedLoadable, InputError> {
+        let file = File::open(&self.track.path)
+            .await
+            .map_err(|_| InputError::NotFound)?;
+
+        Ok(LoadableFile::new(file).boxed())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            title: self.track.title.clone(),
+            artist: self.track.artist.clone(),
+            album: self.track.album.clone(),
+            canonical: format!("library://{}", self.track.id),
+            source: "library".to_string(),
+            duration: self.track.duration.unwrap_or_default(),
+            // The library serves the artwork that is embedded in its files
+            artwork: self
+                .track
+                .artwork_type
+                .as_ref()
+                .map(|_| format!("/v1/library/tracks/{}/artwork", self.track.id)),
+        }
+    }
+}
+
+impl Debug for LibraryInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Library: {}", &self.track.path)
+    }
+}
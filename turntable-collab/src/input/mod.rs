@@ -1,10 +1,56 @@
+use std::{path::PathBuf, time::Duration};
+
 use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::Regex;
 use thiserror::Error;
 use turntable_core::BoxedLoadable;
-use youtube::YouTubeVideoInput;
+use turntable_impls::MediaArtwork;
 
 mod file;
-mod youtube;
+mod http;
+mod library;
+mod radio;
+mod upload;
+mod ytdlp;
+
+pub use library::LibraryInput;
+pub use radio::StreamTitleListener;
+pub use upload::UploadInput;
+pub use ytdlp::ExtractorFilter;
+
+lazy_static! {
+    /// Matches the http(s) urls that files and radio stations are played from
+    static ref HTTP_URL_REGEX: Regex = Regex::new(r"^https?://[^\s/]+(/\S*)?$").unwrap();
+}
+
+/// The extensions of audio files that can be played, used when the type of a file isn't known otherwise.
+pub(crate) const AUDIO_EXTENSIONS: &[&str] =
+    &["mp3", "ogg", "oga", "opus", "flac", "wav", "m4a", "aac"];
+
+/// Which resources inputs are allowed to access.
+#[derive(Debug, Clone)]
+pub struct InputConfig {
+    /// The directories files can be played from with `file://`. File inputs are disabled if there are none.
+    pub media_roots: Vec<PathBuf>,
+    /// Which yt-dlp extractors urls can be played with
+    pub extractors: ExtractorFilter,
+    /// The yt-dlp executable, which is looked up on the `PATH` if not set
+    pub yt_dlp_path: Option<PathBuf>,
+    /// How long yt-dlp can take before it's stopped
+    pub yt_dlp_timeout: Duration,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            media_roots: vec![],
+            extractors: Default::default(),
+            yt_dlp_path: None,
+            yt_dlp_timeout: Duration::from_secs(30),
+        }
+    }
+}
 
 #[derive(Debug, Error)]
 pub enum InputError {
@@ -26,6 +72,15 @@ pub enum InputError {
     #[error("Resource is invalid")]
     Invalid,
 
+    #[error("Access to {0} is not allowed")]
+    PathDenied(String),
+
+    #[error("Playing from {0} is not allowed")]
+    ExtractorDenied(String),
+
+    #[error("Failed to extract resource: {0}")]
+    ExtractionFailed(String),
+
     #[error("{0}")]
     Other(String),
 
@@ -38,54 +93,135 @@ pub enum InputError {
 pub struct Metadata {
     pub title: String,
     pub artist: Option<String>,
+    pub album: Option<String>,
 
     pub canonical: String,
     pub source: String,
 
     pub duration: f32,
+    /// A URL of the cover art. Artwork that is embedded in a file isn't a URL, see [Input::artwork].
     pub artwork: Option<String>,
 }
 
+impl Metadata {
+    /// Returns the title as players display it, such as `Artist - Title`.
+    pub fn display_title(&self) -> String {
+        match &self.artist {
+            Some(artist) => format!("{} - {}", artist, self.title),
+            None => self.title.clone(),
+        }
+    }
+}
+
 /// Represents any resource that can be used as an input for turntable
 #[derive(Debug)]
 pub enum Input {
-    YouTube(youtube::YouTubeVideoInput),
+    YtDlp(ytdlp::YtDlpInput),
     File(file::FileInput),
+    Radio(radio::RadioInput),
+    Http(http::HttpFileInput),
+    Upload(upload::UploadInput),
+    Library(library::LibraryInput),
 }
 
 impl Input {
-    pub async fn query(input: &str) -> Result<Vec<Self>, InputError> {
-        if YouTubeVideoInput::test(input) {
-            let results = YouTubeVideoInput::fetch(input).await?;
-            return Ok(results.into_iter().map(Input::YouTube).collect());
-        }
-
+    pub async fn query(input: &str, config: &InputConfig) -> Result<Vec<Self>, InputError> {
         if file::FileInput::test(input) {
-            let results = file::FileInput::fetch(input).await?;
+            let results = file::FileInput::fetch(input, config).await?;
             return Ok(results.into_iter().map(Input::File).collect());
         }
 
+        // Audio files are only claimed by the generic extractor, so yt-dlp isn't run for them
+        let audio_file = http::HttpFileInput::has_audio_extension(input);
+
+        // Otherwise yt-dlp goes first, so pages it can extract aren't connected to by the radio probe beforehand
+        if !audio_file && ytdlp::YtDlpInput::test(input) {
+            match ytdlp::YtDlpInput::fetch(input, config).await {
+                Ok(results) => return Ok(results.into_iter().map(Input::YtDlp).collect()),
+                Err(InputError::NoMatch) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if radio::RadioInput::test(input) {
+            match radio::RadioInput::fetch(input, config).await {
+                Ok(results) => return Ok(results.into_iter().map(Input::Radio).collect()),
+                Err(InputError::NoMatch) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if http::HttpFileInput::test(input) {
+            let results = http::HttpFileInput::fetch(input, config).await?;
+            return Ok(results.into_iter().map(Input::Http).collect());
+        }
+
         Err(InputError::NoMatch)
     }
 
     pub async fn loadable(&self) -> Result<BoxedLoadable, InputError> {
         match self {
-            Input::YouTube(input) => input.loadable().await,
+            Input::YtDlp(input) => input.loadable().await,
             Input::File(input) => input.loadable().await,
+            Input::Radio(input) => input.loadable().await,
+            Input::Http(input) => input.loadable().await,
+            Input::Upload(input) => input.loadable().await,
+            Input::Library(input) => input.loadable().await,
         }
     }
 
     pub fn length(&self) -> Option<f32> {
         match self {
-            Input::YouTube(input) => input.length(),
+            Input::YtDlp(input) => input.length(),
             Input::File(input) => input.length(),
+            Input::Radio(input) => input.length(),
+            Input::Http(input) => input.length(),
+            Input::Upload(input) => input.length(),
+            Input::Library(input) => input.length(),
         }
     }
 
     pub fn metadata(&self) -> Metadata {
         match self {
-            Input::YouTube(input) => input.metadata(),
+            Input::YtDlp(input) => input.metadata(),
             Input::File(input) => input.metadata(),
+            Input::Radio(input) => input.metadata(),
+            Input::Http(input) => input.metadata(),
+            Input::Upload(input) => input.metadata(),
+            Input::Library(input) => input.metadata(),
+        }
+    }
+
+    /// Returns the cover art that is embedded in the file of the input, if it has any.
+    pub fn artwork(&self) -> Option<&MediaArtwork> {
+        match self {
+            Input::File(input) => input.artwork(),
+            Input::Http(input) => input.artwork(),
+            _ => None,
+        }
+    }
+
+    /// Returns the hash of the uploaded file, if the input is an upload.
+    pub fn upload_hash(&self) -> Option<&str> {
+        match self {
+            Input::Upload(input) => Some(input.hash()),
+            _ => None,
+        }
+    }
+
+    /// Returns the title of what a live stream is currently playing, if the input is one and it's known.
+    pub fn stream_title(&self) -> Option<String> {
+        match self {
+            Input::Radio(input) => input.stream_title(),
+            _ => None,
+        }
+    }
+
+    /// Sets the listener that is called whenever the title of a live stream changes.
+    /// Inputs that aren't live streams never call it.
+    pub fn on_stream_title(&self, listener: StreamTitleListener) {
+        if let Input::Radio(input) = self {
+            input.on_stream_title(listener)
         }
     }
 }
@@ -98,7 +234,7 @@ pub trait Inputable {
 
     /// Attempts to fetch the resource from the given query.
     /// This can return multiple results if the query is a playlist.
-    async fn fetch(query: &str) -> Result<Vec<Self>, InputError>
+    async fn fetch(query: &str, config: &InputConfig) -> Result<Vec<Self>, InputError>
     where
         Self: Sized;
 
@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::{fmt::Debug, sync::Arc};
+use turntable_core::{BoxedLoadable, Loadable};
+use turntable_impls::{IcyTitleListener, LoadableIcyStream};
+
+use crate::{InputConfig, InputError, Inputable, Metadata};
+
+use super::HTTP_URL_REGEX;
+
+/// Called with the new title whenever the title of a live stream changes.
+pub type StreamTitleListener = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// A live internet radio stream from an Icecast or SHOUTcast server.
+///
+/// It never ends by itself, so it plays until it's skipped.
+pub struct RadioInput {
+    url: String,
+    /// The name of the station, if the server sent one
+    name: Option<String>,
+    /// The title of what the station is currently playing
+    stream_title: Arc<Mutex<Option<String>>>,
+    listener: Arc<Mutex<Option<StreamTitleListener>>>,
+}
+
+impl RadioInput {
+    /// Returns the title of what the station is currently playing, once it's known.
+    pub fn stream_title(&self) -> Option<String> {
+        self.stream_title.lock().clone()
+    }
+
+    /// Sets the listener that is called whenever the title of the stream changes.
+    pub fn on_stream_title(&self, listener: StreamTitleListener) {
+        *self.listener.lock() = Some(listener);
+    }
+
+    fn title_listener(&self) -> IcyTitleListener {
+        let stream_title = self.stream_title.clone();
+        let listener = self.listener.clone();
+
+        Box::new(move |title| {
+            *stream_title.lock() = Some(title.clone());
+
+            if let Some(listener) = &*listener.lock() {
+                listener(&title);
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Inputable for RadioInput {
+    fn test(query: &str) -> bool {
+        HTTP_URL_REGEX.is_match(query)
+    }
+
+    async fn fetch(query: &str, _config: &InputConfig) -> Result<Vec<Self>, InputError>
+    where
+        Self: Sized,
+    {
+        // Urls that can't be streamed from might still be handled by other inputs
+        let stream = LoadableIcyStream::connect(query, Default::default(), Box::new(|_| {}))
+            .await
+            .map_err(|_| InputError::NoMatch)?;
+
+        // Regular files can be served from the same kind of url, which are left to other inputs
+        if !stream.is_icy() {
+            return Err(InputError::NoMatch);
+        }
+
+        Ok(vec![Self {
+            url: query.to_string(),
+            name: stream.name.clone(),
+            stream_title: Default::default(),
+            listener: Default::default(),
+        }])
+    }
+
+    fn length(&self) -> Option<f32> {
+        None
+    }
+
+    async fn loadable(&self) -> Result<BoxedLoadable, InputError> {
+        let stream =
+            LoadableIcyStream::connect(self.url.clone(), Default::default(), self.title_listener())
+                .await
+                .map_err(|_| InputError::NetworkFailed)?;
+
+        Ok(stream.boxed())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            title: self.name.clone().unwrap_or_else(|| self.url.clone()),
+            artist: None,
+            album: None,
+            canonical: self.url.clone(),
+            source: "radio".to_string(),
+            duration: 0.,
+            artwork: None,
+        }
+    }
+}
+
+impl Debug for RadioInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Radio: {}", &self.url)
+    }
+}
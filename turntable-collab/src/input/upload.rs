@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{fmt::Debug, path::PathBuf};
+use tokio::fs::File;
+use turntable_core::{BoxedLoadable, Loadable};
+use turntable_impls::LoadableFile;
+
+use crate::{InputConfig, InputError, Inputable, Metadata, UploadData};
+
+lazy_static! {
+    static ref REGEX: Regex = Regex::new(r"^upload://(\d+)$").unwrap();
+}
+
+/// An audio file that was uploaded to a room, referred to as `upload://{id}`.
+///
+/// Uploads are looked up in the database, so they are created by the room they belong to instead of being fetched.
+pub struct UploadInput {
+    upload: UploadData,
+    /// Where the uploaded file is stored
+    path: PathBuf,
+}
+
+impl UploadInput {
+    pub fn new(upload: UploadData, path: PathBuf) -> Self {
+        Self { upload, path }
+    }
+
+    /// Returns the hash of the uploaded file, which its stored file is named by.
+    pub fn hash(&self) -> &str {
+        &self.upload.hash
+    }
+}
+
+#[async_trait]
+impl Inputable for UploadInput {
+    fn test(query: &str) -> bool {
+        REGEX.is_match(query)
+    }
+
+    async fn fetch(_query: &str, _config: &InputConfig) -> Result<Vec<Self>, InputError>
+    where
+        Self: Sized,
+    {
+        Err(InputError::NoMatch)
+    }
+
+    fn length(&self) -> Option<f32> {
+        self.upload.duration
+    }
+
+    async fn loadable(&self) -> Result<BoxedLoadable, InputError> {
+        let file = File::open(&self.path)
+            .await
+            .map_err(|_| InputError::NotFound)?;
+
+        Ok(LoadableFile::new(file).boxed())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            title: self.upload.display_title(),
+            artist: self.upload.artist.clone(),
+            album: None,
+            canonical: format!("upload://{}", self.upload.id),
+            source: "upload".to_string(),
+            duration: self.upload.duration.unwrap_or_default(),
+            artwork: None,
+        }
+    }
+}
+
+impl Debug for UploadInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Upload: {}", &self.upload.id)
+    }
+}
@@ -0,0 +1,657 @@
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use turntable_core::{BoxedLoadable, Loadable};
+use turntable_impls::{LoadableResolvableStream, StreamResolver};
+
+use crate::Metadata;
+
+use super::{InputConfig, InputError, Inputable};
+
+lazy_static! {
+    /// Matches urls, which can leave out the scheme if the host has a domain like `youtube.com`
+    static ref REGEX: Regex =
+        Regex::new(r"^(https?://[^\s/]+|[a-zA-Z0-9-]+(\.[a-zA-Z0-9-]+)*\.[a-zA-Z]{2,})(/\S*)?$")
+            .unwrap();
+}
+
+/// The yt-dlp executable that is run if no other one is configured.
+const DEFAULT_PROGRAM: &str = "yt-dlp";
+
+/// What yt-dlp prints when none of its extractors can handle a url.
+const UNSUPPORTED_URL: &str = "Unsupported URL";
+
+/// The extractor yt-dlp falls back to for urls no other extractor claims, such as plain audio files.
+const GENERIC_EXTRACTOR: &str = "generic";
+
+/// The titles yt-dlp gives to playlist entries that can't be played anymore.
+const UNAVAILABLE_TITLES: &[&str] = &["[Deleted video]", "[Private video]"];
+
+/// Which yt-dlp extractors can be used, by the names `yt-dlp --list-extractors` prints.
+///
+/// A name also covers the extractors that are named after it, so `youtube` covers `youtube:tab` too.
+#[derive(Debug, Clone)]
+pub enum ExtractorFilter {
+    /// Only these extractors can be used
+    Allow(Vec<String>),
+    /// Any extractor can be used except these
+    Deny(Vec<String>),
+}
+
+/// Anything yt-dlp can play, such as a YouTube video, a SoundCloud track or a Bandcamp album.
+#[derive(Clone)]
+pub struct YtDlpInput {
+    /// The yt-dlp executable, which resolves the stream later on
+    program: PathBuf,
+    /// How long yt-dlp can take to resolve the stream
+    timeout: Duration,
+    url: String,
+    extractor: String,
+    title: String,
+    artist: Option<String>,
+    album: Option<String>,
+    duration: Option<f32>,
+    thumbnail: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Format {
+    url: String,
+    format_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Thumbnail {
+    url: String,
+}
+
+/// A single video or track, or an entry of a playlist which only has some of the fields.
+#[derive(Debug, Deserialize)]
+struct YtDlpEntry {
+    url: Option<String>,
+    webpage_url: Option<String>,
+    title: Option<String>,
+    track: Option<String>,
+    artist: Option<String>,
+    creator: Option<String>,
+    uploader: Option<String>,
+    channel: Option<String>,
+    album: Option<String>,
+    duration: Option<f32>,
+    thumbnail: Option<String>,
+    #[serde(default)]
+    thumbnails: Vec<Thumbnail>,
+    extractor_key: Option<String>,
+    /// The extractor of a playlist entry
+    ie_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct YtDlpResource {
+    #[serde(flatten)]
+    info: YtDlpEntry,
+    extractor: Option<String>,
+    /// Only set for playlists, where unavailable entries can be null
+    entries: Option<Vec<Option<YtDlpEntry>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayableEntry {
+    url: Option<String>,
+    format_id: Option<String>,
+    #[serde(default)]
+    formats: Vec<Format>,
+}
+
+#[async_trait]
+impl Inputable for YtDlpInput {
+    fn test(query: &str) -> bool {
+        REGEX.is_match(query)
+    }
+
+    async fn fetch(query: &str, config: &InputConfig) -> Result<Vec<Self>, InputError>
+    where
+        Self: Sized,
+    {
+        let program = config
+            .yt_dlp_path
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PROGRAM.into());
+
+        let url = with_scheme(query);
+        let resource = YtDlpResource::fetch(&program, config.yt_dlp_timeout, &url).await?;
+
+        let extractor = resource
+            .extractor
+            .clone()
+            .or_else(|| resource.info.extractor_key.clone())
+            .unwrap_or_default();
+
+        if extractor.eq_ignore_ascii_case(GENERIC_EXTRACTOR) {
+            return Err(InputError::NoMatch);
+        }
+
+        if !config.extractors.allows(&extractor) {
+            return Err(InputError::ExtractorDenied(extractor));
+        }
+
+        let extractor_key = resource.info.extractor_key.clone().unwrap_or(extractor);
+
+        let Some(entries) = resource.entries else {
+            let mut info = resource.info;
+            // The url of a single video is that of its stream, so the page is the query if yt-dlp doesn't know it
+            info.url = Some(url);
+
+            let input =
+                YtDlpInput::from_entry(info, &program, config.yt_dlp_timeout, &extractor_key)
+                    .ok_or(InputError::Invalid)?;
+
+            return Ok(vec![input]);
+        };
+
+        // Playlists can contain entries of other extractors, which have to be allowed too
+        let mut denied = None;
+
+        let inputs: Vec<_> = entries
+            .into_iter()
+            .flatten()
+            .filter(|e| {
+                !e.title
+                    .as_deref()
+                    .is_some_and(|t| UNAVAILABLE_TITLES.contains(&t))
+            })
+            .filter(|e| match &e.ie_key {
+                Some(ie_key) if !config.extractors.allows(ie_key) => {
+                    denied = Some(ie_key.clone());
+                    false
+                }
+                _ => true,
+            })
+            .filter_map(|e| {
+                YtDlpInput::from_entry(e, &program, config.yt_dlp_timeout, &extractor_key)
+            })
+            .collect();
+
+        // A playlist that only has denied entries is denied as a whole, instead of being empty
+        match denied {
+            Some(ie_key) if inputs.is_empty() => Err(InputError::ExtractorDenied(ie_key)),
+            _ => Ok(inputs),
+        }
+    }
+
+    fn length(&self) -> Option<f32> {
+        self.duration
+    }
+
+    async fn loadable(&self) -> Result<BoxedLoadable, InputError> {
+        let stream_url = self.stream_url().await?;
+
+        // Stream urls expire after a while, so a fresh one is resolved if the stream is refused later on
+        let boxed = LoadableResolvableStream::new(stream_url, self.clone())
+            .await
+            .map_err(|_| InputError::NetworkFailed)?
+            .boxed();
+
+        Ok(boxed)
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            album: self.album.clone(),
+            duration: self.duration.unwrap_or_default(),
+            artwork: self.thumbnail.clone(),
+            canonical: self.url.clone(),
+            source: self.extractor.to_lowercase(),
+        }
+    }
+}
+
+impl YtDlpInput {
+    /// Creates an input from what yt-dlp extracted, which needs at least the url of the page it is on.
+    fn from_entry(
+        entry: YtDlpEntry,
+        program: &Path,
+        timeout: Duration,
+        extractor_key: &str,
+    ) -> Option<Self> {
+        let url = entry.webpage_url.or(entry.url)?;
+        let thumbnail = entry
+            .thumbnail
+            .or_else(|| entry.thumbnails.into_iter().last().map(|t| t.url));
+
+        Some(Self {
+            title: entry.track.or(entry.title).unwrap_or_else(|| url.clone()),
+            artist: entry
+                .artist
+                .or(entry.creator)
+                .or(entry.uploader)
+                .or(entry.channel),
+            album: entry.album,
+            duration: entry.duration,
+            thumbnail,
+            extractor: entry.ie_key.unwrap_or_else(|| extractor_key.to_string()),
+            program: program.to_path_buf(),
+            timeout,
+            url,
+        })
+    }
+
+    /// Resolves the url of the audio stream, which is only valid for a limited time.
+    async fn stream_url(&self) -> Result<String, InputError> {
+        let output = run_yt_dlp(
+            &self.program,
+            self.timeout,
+            &["-f", "bestaudio[ext=mp3]/best", "-j", "--", &self.url],
+        )
+        .await?;
+
+        let entry: PlayableEntry =
+            serde_json::from_str(&output).map_err(|e| InputError::ParseError(e.to_string()))?;
+
+        entry
+            .formats
+            .iter()
+            .find(|f| Some(&f.format_id) == entry.format_id.as_ref())
+            .map(|f| f.url.to_owned())
+            .or(entry.url)
+            .ok_or(InputError::Invalid)
+    }
+}
+
+#[async_trait]
+impl StreamResolver for YtDlpInput {
+    async fn resolve(&self) -> Result<String, Box<dyn Error + Sync + Send>> {
+        Ok(self.stream_url().await?)
+    }
+}
+
+impl YtDlpResource {
+    /// Attempts to fetch a video or several videos from the given URL using yt-dlp.
+    pub async fn fetch(program: &Path, timeout: Duration, url: &str) -> Result<Self, InputError> {
+        let output = run_yt_dlp(
+            program,
+            timeout,
+            &[
+                // Don't try to get a stream url for playlists.
+                "--flat-playlist",
+                // Or videos.
+                "--skip-download",
+                // Get a JSON output, in a single line.
+                "-J",
+                "--",
+                url,
+            ],
+        )
+        .await?;
+
+        let entry: YtDlpResource =
+            serde_json::from_str(&output).map_err(|e| InputError::ParseError(e.to_string()))?;
+
+        Ok(entry)
+    }
+}
+
+impl Default for ExtractorFilter {
+    fn default() -> Self {
+        Self::Deny(vec![])
+    }
+}
+
+impl ExtractorFilter {
+    /// Returns true if the extractor with the given name can be used.
+    pub fn allows(&self, extractor: &str) -> bool {
+        let matches = |names: &Vec<String>| {
+            names.iter().any(|name| {
+                extractor.eq_ignore_ascii_case(name)
+                    || extractor
+                        .split_once(':')
+                        .is_some_and(|(base, _)| base.eq_ignore_ascii_case(name))
+            })
+        };
+
+        match self {
+            Self::Allow(names) => matches(names),
+            Self::Deny(names) => !matches(names),
+        }
+    }
+}
+
+/// Adds a scheme to urls that were entered without one, such as `youtube.com/watch?v=...`.
+fn with_scheme(query: &str) -> String {
+    if query.starts_with("http://") || query.starts_with("https://") {
+        query.to_string()
+    } else {
+        format!("https://{}", query)
+    }
+}
+
+/// Runs yt-dlp with the given arguments, and returns what it printed if it succeeded.
+///
+/// Urls none of the extractors can handle don't match, while any other failure is reported with what yt-dlp printed.
+/// yt-dlp is killed if it takes longer than the timeout, or if the query is dropped before it's done.
+async fn run_yt_dlp(
+    program: &Path,
+    timeout: Duration,
+    args: &[&str],
+) -> Result<String, InputError> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output();
+
+    let output = tokio::time::timeout(timeout, output)
+        .await
+        .map_err(|_| {
+            InputError::ExtractionFailed(format!(
+                "yt-dlp took longer than {} seconds",
+                timeout.as_secs_f32()
+            ))
+        })?
+        .map_err(|e| {
+            InputError::ExtractionFailed(format!("{} could not be run: {}", program.display(), e))
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        if stderr.contains(UNSUPPORTED_URL) {
+            return Err(InputError::NoMatch);
+        }
+
+        // The error comes last, after any warnings
+        let message = stderr
+            .lines()
+            .rev()
+            .map(|l| l.trim())
+            .find(|l| l.starts_with("ERROR:"))
+            .or_else(|| {
+                stderr
+                    .lines()
+                    .rev()
+                    .map(|l| l.trim())
+                    .find(|l| !l.is_empty())
+            })
+            .map(|l| l.trim_start_matches("ERROR:").trim().to_string())
+            .unwrap_or_else(|| format!("yt-dlp exited with {}", output.status));
+
+        return Err(InputError::ExtractionFailed(message));
+    }
+
+    String::from_utf8(output.stdout).map_err(|e| InputError::ParseError(e.to_string()))
+}
+
+impl Debug for YtDlpInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", &self.extractor, &self.url)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use tempfile::TempDir;
+    use tokio::sync::{Mutex, MutexGuard};
+
+    use super::*;
+
+    /// Answers like yt-dlp would for a few made up urls.
+    const STUB: &str = r#"#!/bin/sh
+for url; do :; done
+
+case "$url" in
+  https://bandcamp.test/track/song)
+    echo '{"title": "Artist - Song", "track": "Song", "artist": "Artist", "album": "Album", "duration": 201.5, "thumbnail": "https://bandcamp.test/art.jpg", "webpage_url": "https://bandcamp.test/track/song", "url": "https://cdn.bandcamp.test/song.mp3", "format_id": "mp3-128", "formats": [{"format_id": "mp3-128", "url": "https://cdn.bandcamp.test/song.mp3"}], "extractor": "Bandcamp", "extractor_key": "Bandcamp"}'
+    ;;
+  https://soundcloud.test/user/sets/set)
+    echo '{"_type": "playlist", "title": "Set", "extractor": "soundcloud:set", "extractor_key": "SoundcloudSet", "entries": [{"_type": "url", "ie_key": "Soundcloud", "url": "https://soundcloud.test/user/one", "title": "One", "uploader": "User", "duration": 60, "thumbnails": [{"url": "https://soundcloud.test/small.jpg"}, {"url": "https://soundcloud.test/large.jpg"}]}, {"_type": "url", "ie_key": "Soundcloud", "url": "https://soundcloud.test/user/gone", "title": "[Deleted video]"}, null, {"_type": "url", "ie_key": "Soundcloud", "url": "https://soundcloud.test/user/two"}]}'
+    ;;
+  https://mixes.test/mix)
+    echo '{"_type": "playlist", "title": "Mix", "extractor": "mixes", "extractor_key": "Mixes", "entries": [{"_type": "url", "ie_key": "Soundcloud", "url": "https://soundcloud.test/user/one", "title": "One"}, {"_type": "url", "ie_key": "Bandcamp", "url": "https://bandcamp.test/track/song", "title": "Song"}]}'
+    ;;
+  https://example.test/song.mp3)
+    echo '{"title": "song", "webpage_url": "https://example.test/song.mp3", "extractor": "generic", "extractor_key": "Generic"}'
+    ;;
+  https://slow.test/video)
+    sleep 5
+    ;;
+  https://video.test/gone)
+    echo "WARNING: [video] Falling back to the web client" >&2
+    echo "ERROR: [video] gone: Video unavailable" >&2
+    exit 1
+    ;;
+  *)
+    echo "ERROR: Unsupported URL: $url" >&2
+    exit 1
+    ;;
+esac
+"#;
+
+    /// Tests take turns running the stub, since one can't be run while another test is writing its own (ETXTBSY).
+    static STUB_LOCK: Mutex<()> = Mutex::const_new(());
+
+    /// The stub in a directory of its own, which is removed once it's dropped.
+    struct Stub {
+        directory: TempDir,
+        _guard: MutexGuard<'static, ()>,
+    }
+
+    impl Stub {
+        async fn install() -> Self {
+            let guard = STUB_LOCK.lock().await;
+
+            let directory = tempfile::tempdir().unwrap();
+            let path = directory.path().join("yt-dlp");
+
+            fs::write(&path, STUB).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+            Self {
+                directory,
+                _guard: guard,
+            }
+        }
+
+        fn config(&self, extractors: ExtractorFilter) -> InputConfig {
+            InputConfig {
+                extractors,
+                yt_dlp_path: Some(self.directory.path().join("yt-dlp")),
+                ..Default::default()
+            }
+        }
+
+        async fn fetch(
+            &self,
+            query: &str,
+            extractors: ExtractorFilter,
+        ) -> Result<Vec<YtDlpInput>, InputError> {
+            YtDlpInput::fetch(query, &self.config(extractors)).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_track_metadata() {
+        let stub = Stub::install().await;
+        let inputs = stub
+            .fetch("https://bandcamp.test/track/song", Default::default())
+            .await
+            .unwrap();
+
+        assert_eq!(inputs.len(), 1);
+
+        let metadata = inputs[0].metadata();
+        assert_eq!(metadata.title, "Song");
+        assert_eq!(metadata.artist.as_deref(), Some("Artist"));
+        assert_eq!(metadata.album.as_deref(), Some("Album"));
+        assert_eq!(metadata.duration, 201.5);
+        assert_eq!(
+            metadata.artwork.as_deref(),
+            Some("https://bandcamp.test/art.jpg")
+        );
+        assert_eq!(metadata.canonical, "https://bandcamp.test/track/song");
+        assert_eq!(metadata.source, "bandcamp");
+
+        let stream_url = inputs[0].stream_url().await.unwrap();
+        assert_eq!(stream_url, "https://cdn.bandcamp.test/song.mp3");
+    }
+
+    #[tokio::test]
+    async fn test_urls_without_scheme() {
+        assert!(YtDlpInput::test("youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(YtDlpInput::test("www.youtube.com"));
+        assert!(!YtDlpInput::test("never gonna give you up"));
+        assert!(!YtDlpInput::test("song.mp3"));
+
+        let stub = Stub::install().await;
+        let inputs = stub
+            .fetch("bandcamp.test/track/song", Default::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            inputs[0].metadata().canonical,
+            "https://bandcamp.test/track/song"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_playlist_entries() {
+        let stub = Stub::install().await;
+        let inputs = stub
+            .fetch("https://soundcloud.test/user/sets/set", Default::default())
+            .await
+            .unwrap();
+
+        // Deleted and missing entries are left out
+        assert_eq!(inputs.len(), 2);
+
+        let first = inputs[0].metadata();
+        assert_eq!(first.title, "One");
+        assert_eq!(first.artist.as_deref(), Some("User"));
+        assert_eq!(
+            first.artwork.as_deref(),
+            Some("https://soundcloud.test/large.jpg")
+        );
+        assert_eq!(first.source, "soundcloud");
+        assert_eq!(inputs[0].length(), Some(60.));
+
+        let second = inputs[1].metadata();
+        assert_eq!(second.title, "https://soundcloud.test/user/two");
+        assert_eq!(inputs[1].length(), None);
+    }
+
+    #[tokio::test]
+    async fn test_unclaimed_urls() {
+        let stub = Stub::install().await;
+
+        let generic = stub
+            .fetch("https://example.test/song.mp3", Default::default())
+            .await;
+        assert!(matches!(generic, Err(InputError::NoMatch)));
+
+        let unsupported = stub
+            .fetch("https://example.test/page", Default::default())
+            .await;
+        assert!(matches!(unsupported, Err(InputError::NoMatch)));
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_reported() {
+        let stub = Stub::install().await;
+
+        let unavailable = stub
+            .fetch("https://video.test/gone", Default::default())
+            .await;
+        assert!(
+            matches!(unavailable, Err(InputError::ExtractionFailed(e)) if e == "[video] gone: Video unavailable")
+        );
+
+        let config = InputConfig {
+            yt_dlp_path: Some(stub.directory.path().join("missing")),
+            ..Default::default()
+        };
+
+        let not_installed = YtDlpInput::fetch("https://bandcamp.test/track/song", &config).await;
+        assert!(matches!(
+            not_installed,
+            Err(InputError::ExtractionFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_slow_extraction_times_out() {
+        let stub = Stub::install().await;
+        let config = InputConfig {
+            yt_dlp_timeout: Duration::from_millis(200),
+            ..stub.config(Default::default())
+        };
+
+        let started = std::time::Instant::now();
+        let result = YtDlpInput::fetch("https://slow.test/video", &config).await;
+
+        assert!(matches!(result, Err(InputError::ExtractionFailed(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_extractor_filter() {
+        let stub = Stub::install().await;
+        let url = "https://soundcloud.test/user/sets/set";
+
+        let denied = stub
+            .fetch(url, ExtractorFilter::Deny(vec!["SoundCloud".to_string()]))
+            .await;
+        assert!(matches!(denied, Err(InputError::ExtractorDenied(e)) if e == "soundcloud:set"));
+
+        let allowed = stub
+            .fetch(url, ExtractorFilter::Allow(vec!["soundcloud".to_string()]))
+            .await;
+        assert!(allowed.is_ok());
+
+        let not_allowed = stub
+            .fetch(url, ExtractorFilter::Allow(vec!["bandcamp".to_string()]))
+            .await;
+        assert!(matches!(not_allowed, Err(InputError::ExtractorDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn test_extractor_filter_applies_to_playlist_entries() {
+        let stub = Stub::install().await;
+        let url = "https://mixes.test/mix";
+
+        let inputs = stub
+            .fetch(url, ExtractorFilter::Deny(vec!["bandcamp".to_string()]))
+            .await
+            .unwrap();
+
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].metadata().source, "soundcloud");
+
+        let allowed = vec!["mixes".to_string(), "soundcloud".to_string()];
+        let inputs = stub
+            .fetch(url, ExtractorFilter::Allow(allowed))
+            .await
+            .unwrap();
+
+        assert_eq!(inputs.len(), 1);
+
+        let only_denied = stub
+            .fetch(
+                url,
+                ExtractorFilter::Deny(vec!["soundcloud".to_string(), "bandcamp".to_string()]),
+            )
+            .await;
+        assert!(matches!(only_denied, Err(InputError::ExtractorDenied(_))));
+    }
+}
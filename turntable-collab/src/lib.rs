@@ -2,6 +2,7 @@ mod auth;
 mod db;
 mod events;
 mod input;
+mod library;
 mod queues;
 mod rooms;
 mod track;
@@ -17,12 +18,25 @@ pub use auth::{AuthError, Credentials, NewPlainUser};
 pub use db::*;
 pub use events::CollabEvent;
 pub use input::*;
+pub use library::*;
 pub use queues::*;
-pub use rooms::{Room, RoomConnection, RoomConnectionHandle, RoomError, RoomState};
+pub use rooms::{
+    Clip, ClipConfig, ClipId, CueEntry, CueSheet, NewRelay, PendingUpload, PlaylistEntry,
+    PlaylistFormat, PreviewControl, PreviewHandle, PreviewId, Recorder, RecorderState, Recording,
+    RecordingConfig, Relay, RelayId, RelayState, Room, RoomConnection, RoomConnectionHandle,
+    RoomError, RoomState, StreamPacket, StreamPlaylist, StreamPreferences, UploadConfig,
+};
 pub use track::*;
+pub use turntable_impls::IcecastMethod;
 
 use turntable_core::{ArcedStore, Config, Pipeline, PlayerId};
-use turntable_impls::SymphoniaIngestion;
+use turntable_impls::{PcmEncoder, SymphoniaIngestion, WaveEncoder};
+
+#[cfg(feature = "opus")]
+use turntable_impls::OggOpusEncoder;
+
+#[cfg(feature = "mp3")]
+use turntable_impls::Mp3Encoder;
 
 pub type CollabPipeline = Pipeline<SymphoniaIngestion>;
 pub type CollabDatabase = PgDatabase;
@@ -33,6 +47,19 @@ pub struct Collab {
 
     pub auth: Auth<CollabDatabase>,
     pub rooms: RoomManager,
+    pub library: Arc<Library>,
+}
+
+/// How the collab system is set up, apart from the database it connects to.
+#[derive(Debug, Clone, Default)]
+pub struct CollabConfig {
+    /// How the audio of rooms is processed
+    pub pipeline: Config,
+    pub recording: RecordingConfig,
+    pub uploads: UploadConfig,
+    pub library: LibraryConfig,
+    pub inputs: InputConfig,
+    pub clips: ClipConfig,
 }
 
 /// A type passed to various components of the collab system, to access state, emit events, and dispatch actions.
@@ -43,17 +70,24 @@ pub struct CollabContext {
     pub pipeline: Arc<CollabPipeline>,
     pub database: Arc<CollabDatabase>,
     pub rooms: ArcedStore<RoomId, Room>,
+    pub recording: RecordingConfig,
+    pub uploads: UploadConfig,
+    pub library: LibraryConfig,
+    pub inputs: InputConfig,
+    pub clips: ClipConfig,
 }
 
 impl Collab {
-    pub async fn new(config: Config, database_url: &str) -> Self {
+    pub async fn new(config: CollabConfig, database_url: &str) -> Self {
         let database = Arc::new(
             CollabDatabase::new(database_url)
                 .await
                 .expect("database is created"),
         );
 
-        let pipeline = Arc::new(CollabPipeline::new(config));
+        let pipeline = Arc::new(CollabPipeline::new(config.pipeline));
+        register_encoders(&pipeline);
+
         let (event_sender, event_receiver) = unbounded();
 
         let context = CollabContext {
@@ -61,15 +95,22 @@ impl Collab {
             pipeline: pipeline.clone(),
             event_sender: event_sender.clone(),
             rooms: Default::default(),
+            recording: config.recording,
+            uploads: config.uploads,
+            library: config.library,
+            inputs: config.inputs,
+            clips: config.clips,
         };
 
         let room_manager = RoomManager::new(&context);
         let auth = Auth::new(&database);
+        let library = Arc::new(Library::new(&context));
 
         let new = Self {
             auth,
             event_receiver,
             rooms: room_manager,
+            library,
         };
 
         spawn_pipeline_event_conversion_thread(&context, &event_sender);
@@ -81,6 +122,7 @@ impl Collab {
     /// Must be called after creation
     async fn init(&self) {
         self.rooms.restore().await.expect("rooms are restored");
+        self.library.start();
     }
 
     /// Receive events from the collab.
@@ -105,6 +147,20 @@ impl CollabContext {
     }
 }
 
+/// Registers the encoders that streams can be requested in. The first one is the default.
+fn register_encoders(pipeline: &CollabPipeline) {
+    let encoders = pipeline.encoders();
+
+    encoders.register::<WaveEncoder>("wav", "audio/wav");
+    encoders.register::<PcmEncoder>("pcm", "audio/pcm");
+
+    #[cfg(feature = "opus")]
+    encoders.register::<OggOpusEncoder>("opus", "audio/ogg");
+
+    #[cfg(feature = "mp3")]
+    encoders.register::<Mp3Encoder>("mp3", "audio/mpeg");
+}
+
 fn spawn_pipeline_event_conversion_thread(context: &CollabContext, sender: &EventSender) {
     let context = context.to_owned();
     let sender = sender.to_owned();
@@ -113,9 +169,25 @@ fn spawn_pipeline_event_conversion_thread(context: &CollabContext, sender: &Even
         let event = context.pipeline.wait_for_event();
 
         if let Some(converted_event) = CollabEvent::from_pipeline_event(&context, event) {
+            update_rooms(&context, &converted_event);
             sender.send(converted_event).expect("event is sent")
         }
     };
 
     thread::spawn(run);
 }
+
+/// Keeps the state rooms derive from events up to date.
+fn update_rooms(context: &CollabContext, event: &CollabEvent) {
+    if let CollabEvent::RoomQueueItemUpdate {
+        room_id,
+        new_item,
+        total_position,
+    } = event
+    {
+        if let Some(room) = context.rooms.get(room_id) {
+            room.update_stream_title(new_item.as_ref());
+            room.update_recording_cues(new_item.as_ref(), *total_position);
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, SubsecRound, Utc};
+use crossbeam::channel::unbounded;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use thiserror::Error;
+use tokio::{fs::File, sync::Mutex};
+use turntable_core::get_or_create_handle;
+use turntable_impls::{read_tags, LoadableFile, MediaArtwork};
+
+use crate::{
+    input::AUDIO_EXTENSIONS, CollabContext, Database, DatabaseError, LibraryAlbumData,
+    LibraryArtistData, LibraryFilter, LibraryTrackData, NewLibraryTrack, PrimaryKey,
+};
+
+/// Which directories make up the library.
+#[derive(Debug, Clone)]
+pub struct LibraryConfig {
+    /// The directories that are scanned for audio files, including their subdirectories
+    pub directories: Vec<PathBuf>,
+    /// Whether the directories are watched, so changes are picked up without restarting
+    pub watch: bool,
+}
+
+/// An index of the audio files in the library directories, with their tags.
+pub struct Library {
+    context: CollabContext,
+    /// Held while scanning, so scans never overlap
+    scanning: Mutex<()>,
+}
+
+/// What changed in the library during a scan.
+#[derive(Debug, Clone, Default)]
+pub struct LibraryScan {
+    /// How many files were added or changed
+    pub updated: usize,
+    /// How many files are gone
+    pub removed: usize,
+}
+
+/// An audio file on disk, with its size and when it was last modified.
+type AudioFile = (PathBuf, i64, DateTime<Utc>);
+
+/// The size and modification time of every indexed track, by path.
+type KnownFiles = HashMap<String, (i64, DateTime<Utc>)>;
+
+/// How the files on disk differ from the indexed tracks.
+#[derive(Debug, Default)]
+struct DiskChanges {
+    /// Files that are new or have changed since they were indexed
+    changed: Vec<AudioFile>,
+    /// Paths of the files that are the same as when they were indexed
+    unchanged: HashSet<String>,
+    /// The directories that could be read, so tracks missing from them are gone
+    readable_directories: Vec<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+pub enum LibraryError {
+    #[error("Library track {0} does not exist")]
+    TrackNotFound(PrimaryKey),
+    #[error("Library track {0} has no artwork")]
+    ArtworkNotFound(PrimaryKey),
+    #[error(transparent)]
+    Database(DatabaseError),
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        Self {
+            directories: vec![],
+            watch: true,
+        }
+    }
+}
+
+impl Library {
+    /// How long the directories have to be quiet before changes are scanned, since copying an album causes many events
+    const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+    pub fn new(context: &CollabContext) -> Self {
+        Self {
+            context: context.clone(),
+            scanning: Default::default(),
+        }
+    }
+
+    /// Scans the library in the background, and keeps watching it for changes if enabled.
+    pub fn start(self: &Arc<Self>) {
+        if self.context.library.directories.is_empty() {
+            return;
+        }
+
+        let library = self.clone();
+        get_or_create_handle().spawn(async move {
+            let _ = library.scan().await;
+        });
+
+        if self.context.library.watch {
+            spawn_watch_thread(self);
+        }
+    }
+
+    /// Brings the library up to date with the files on disk.
+    ///
+    /// Only files that are new or have changed since the last scan are read.
+    pub async fn scan(&self) -> Result<LibraryScan, LibraryError> {
+        let _scanning = self.scanning.lock().await;
+        let database = &self.context.database;
+
+        let known: KnownFiles = database
+            .all_library_tracks()
+            .await
+            .map_err(LibraryError::Database)?
+            .into_iter()
+            .map(|t| (t.path, (t.size, t.modified_at)))
+            .collect();
+
+        let directories = &self.context.library.directories;
+        let changes = compare_with_disk(directories, &known);
+
+        let mut scan = LibraryScan::default();
+        let mut seen = changes.unchanged;
+
+        for (path, size, modified_at) in changes.changed {
+            // Files that can't be indexed count as gone, so a track that became unplayable is removed
+            if self.index(&path, size, modified_at).await.is_ok() {
+                seen.insert(path.to_string_lossy().to_string());
+                scan.updated += 1;
+            }
+        }
+
+        let removed = removed_paths(known, &seen, directories, &changes.readable_directories);
+        scan.removed = removed.len();
+
+        if !removed.is_empty() {
+            database
+                .delete_library_tracks(&removed)
+                .await
+                .map_err(LibraryError::Database)?;
+        }
+
+        Ok(scan)
+    }
+
+    /// Returns the tracks of the library that match the filter, ordered by artist, album and title.
+    pub async fn tracks(
+        &self,
+        filter: LibraryFilter,
+    ) -> Result<Vec<LibraryTrackData>, LibraryError> {
+        self.context
+            .database
+            .list_library_tracks(filter)
+            .await
+            .map_err(LibraryError::Database)
+    }
+
+    /// Returns the artists of the library by name.
+    pub async fn artists(&self) -> Result<Vec<LibraryArtistData>, LibraryError> {
+        self.context
+            .database
+            .list_library_artists()
+            .await
+            .map_err(LibraryError::Database)
+    }
+
+    /// Returns the albums of the library by title, optionally only those of an artist.
+    pub async fn albums(
+        &self,
+        artist: Option<&str>,
+    ) -> Result<Vec<LibraryAlbumData>, LibraryError> {
+        self.context
+            .database
+            .list_library_albums(artist)
+            .await
+            .map_err(LibraryError::Database)
+    }
+
+    pub async fn track_by_id(
+        &self,
+        track_id: PrimaryKey,
+    ) -> Result<LibraryTrackData, LibraryError> {
+        self.context
+            .database
+            .library_track_by_id(track_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound {
+                    resource: _,
+                    identifier: _,
+                } => LibraryError::TrackNotFound(track_id),
+                e => LibraryError::Database(e),
+            })
+    }
+
+    /// Reads the cover art that is embedded in the file of a track.
+    pub async fn artwork(&self, track_id: PrimaryKey) -> Result<MediaArtwork, LibraryError> {
+        let track = self.track_by_id(track_id).await?;
+
+        let file = File::open(&track.path)
+            .await
+            .map_err(|_| LibraryError::ArtworkNotFound(track_id))?;
+
+        read_tags(LoadableFile::new(file))
+            .await
+            .ok()
+            .and_then(|tags| tags.artwork)
+            .ok_or(LibraryError::ArtworkNotFound(track_id))
+    }
+
+    /// Reads the tags of a file and stores them, which fails if it isn't audio that can be played.
+    async fn index(
+        &self,
+        path: &Path,
+        size: i64,
+        modified_at: DateTime<Utc>,
+    ) -> Result<LibraryTrackData, Box<dyn std::error::Error + Send + Sync>> {
+        let file = File::open(path).await?;
+        let tags = read_tags(LoadableFile::new(file)).await?;
+
+        let title = tags.title.unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+        let track = self
+            .context
+            .database
+            .upsert_library_track(NewLibraryTrack {
+                path: path.to_string_lossy().to_string(),
+                title,
+                artist: tags.artist,
+                album: tags.album,
+                duration: tags.duration,
+                artwork_type: tags.artwork.map(|a| a.media_type),
+                size,
+                modified_at,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(track)
+    }
+}
+
+/// Returns the audio files in a directory and its subdirectories, with their size and when they were last modified.
+fn audio_files(directory: &Path) -> io::Result<Vec<AudioFile>> {
+    let mut files = vec![];
+
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            // Unreadable subdirectories are skipped instead of failing the whole directory
+            files.extend(audio_files(&path).unwrap_or_default());
+            continue;
+        }
+
+        let is_audio = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.as_str()));
+
+        if is_audio {
+            let modified_at: DateTime<Utc> = metadata.modified()?.into();
+            // The database keeps microseconds, so anything finer would look like a change on every scan
+            files.push((path, metadata.len() as i64, modified_at.trunc_subsecs(6)));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Compares the audio files in the directories against the indexed tracks.
+fn compare_with_disk(directories: &[PathBuf], known: &KnownFiles) -> DiskChanges {
+    let mut changes = DiskChanges::default();
+
+    for directory in directories {
+        // Tracks in directories that can't be read are kept, in case they're only unavailable for now
+        let Ok(files) = audio_files(directory) else {
+            continue;
+        };
+
+        changes.readable_directories.push(directory.clone());
+
+        for (path, size, modified_at) in files {
+            let path_string = path.to_string_lossy().to_string();
+
+            if known.get(&path_string) == Some(&(size, modified_at)) {
+                changes.unchanged.insert(path_string);
+            } else {
+                changes.changed.push((path, size, modified_at));
+            }
+        }
+    }
+
+    changes
+}
+
+/// Returns the paths of the indexed tracks that weren't seen in a scan and are gone.
+///
+/// Tracks outside of the library directories are always gone, while those in a directory that couldn't be read are kept.
+fn removed_paths(
+    known: KnownFiles,
+    seen: &HashSet<String>,
+    directories: &[PathBuf],
+    readable_directories: &[PathBuf],
+) -> Vec<String> {
+    known
+        .into_keys()
+        .filter(|p| !seen.contains(p))
+        .filter(|p| {
+            let in_library = directories.iter().any(|d| Path::new(p).starts_with(d));
+            let was_scanned = readable_directories
+                .iter()
+                .any(|d| Path::new(p).starts_with(d));
+
+            !in_library || was_scanned
+        })
+        .collect()
+}
+
+fn spawn_watch_thread(library: &Arc<Library>) {
+    let library = library.clone();
+    let handle = get_or_create_handle();
+    let (sender, receiver) = unbounded();
+
+    let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        // Reading files while scanning must not cause another scan
+        if event.is_ok_and(|e| !matches!(e.kind, EventKind::Access(_))) {
+            let _ = sender.send(());
+        }
+    });
+
+    let Ok(mut watcher) = watcher else {
+        return;
+    };
+
+    for directory in &library.context.library.directories {
+        let _ = watcher.watch(directory, RecursiveMode::Recursive);
+    }
+
+    let run = move || {
+        // The watcher stops when it's dropped
+        let _watcher = watcher;
+
+        while receiver.recv().is_ok() {
+            while receiver.recv_timeout(Library::SETTLE_TIME).is_ok() {}
+
+            let _ = handle.block_on(library.scan());
+        }
+    };
+
+    thread::spawn(run);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the path as it's stored in the database.
+    fn key(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_audio_files_are_found_in_subdirectories() {
+        let directory = tempfile::tempdir().unwrap();
+        let album = directory.path().join("artist").join("album");
+        fs::create_dir_all(&album).unwrap();
+
+        fs::write(directory.path().join("single.mp3"), [0; 16]).unwrap();
+        fs::write(album.join("track.FLAC"), [0; 32]).unwrap();
+        fs::write(album.join("cover.jpg"), [0; 8]).unwrap();
+        fs::write(album.join("notes"), [0; 8]).unwrap();
+
+        let mut files = audio_files(directory.path()).unwrap();
+        files.sort();
+
+        let found: Vec<_> = files
+            .iter()
+            .map(|(p, size, _)| (p.clone(), *size))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (album.join("track.FLAC"), 32),
+                (directory.path().join("single.mp3"), 16),
+            ]
+        );
+
+        // Kept at the precision of the database
+        assert!(files
+            .iter()
+            .all(|(_, _, m)| m.timestamp_subsec_nanos() % 1000 == 0));
+    }
+
+    #[test]
+    fn test_audio_files_fail_for_a_missing_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        assert!(audio_files(&directory.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_compare_with_disk_finds_new_and_changed_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let directories = vec![
+            directory.path().to_path_buf(),
+            directory.path().join("unavailable"),
+        ];
+
+        let unchanged = directory.path().join("unchanged.mp3");
+        let changed = directory.path().join("changed.mp3");
+        let added = directory.path().join("added.mp3");
+
+        fs::write(&unchanged, [0; 16]).unwrap();
+        fs::write(&changed, [0; 16]).unwrap();
+        fs::write(&added, [0; 16]).unwrap();
+
+        let files = audio_files(directory.path()).unwrap();
+        let modified_at = |path: &Path| files.iter().find(|f| f.0 == path).unwrap().2;
+
+        let known = KnownFiles::from([
+            (key(&unchanged), (16, modified_at(&unchanged))),
+            (key(&changed), (8, modified_at(&changed))),
+        ]);
+
+        let changes = compare_with_disk(&directories, &known);
+
+        let mut changed_paths: Vec<_> = changes.changed.into_iter().map(|f| f.0).collect();
+        changed_paths.sort();
+
+        assert_eq!(changed_paths, vec![added, changed]);
+        assert_eq!(changes.unchanged, HashSet::from([key(&unchanged)]));
+        assert_eq!(
+            changes.readable_directories,
+            vec![directory.path().to_path_buf()]
+        );
+    }
+
+    #[test]
+    fn test_removed_paths_keep_tracks_of_unreadable_directories() {
+        let directory = tempfile::tempdir().unwrap();
+        let readable = directory.path().join("readable");
+        let unreadable = directory.path().join("unreadable");
+        let directories = vec![readable.clone(), unreadable.clone()];
+
+        let entry = (0, Utc::now());
+        let known = KnownFiles::from([
+            (key(&readable.join("kept.mp3")), entry),
+            (key(&readable.join("deleted.mp3")), entry),
+            (key(&unreadable.join("unavailable.mp3")), entry),
+            (key(&directory.path().join("outside.mp3")), entry),
+        ]);
+        let seen = HashSet::from([key(&readable.join("kept.mp3"))]);
+
+        let mut removed =
+            removed_paths(known, &seen, &directories, std::slice::from_ref(&readable));
+        removed.sort();
+
+        assert_eq!(
+            removed,
+            vec![
+                key(&directory.path().join("outside.mp3")),
+                key(&readable.join("deleted.mp3")),
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::VecDeque;
 
+use std::sync::Arc;
+
 use parking_lot::Mutex;
 use turntable_core::{BoxedQueueItem, Queue, QueueItem, QueueNotifier, SinkId};
 
@@ -35,7 +37,10 @@ impl LinearQueue {
         }
     }
 
-    pub fn push(&self, item: Track, user_id: PrimaryKey) {
+    pub fn push(&self, mut item: Track, user_id: PrimaryKey) {
+        item.link_artwork(self.notifier.room_id);
+        self.notifier.watch_stream_title(&item);
+
         let item = LinearQueueItem {
             user_id,
             track: item,
@@ -125,6 +130,24 @@ impl Queue for LinearQueue {
 }
 
 impl WrappedQueueNotifier {
+    /// Emits a collab event whenever the title of a live track changes.
+    fn watch_stream_title(&self, track: &Track) {
+        // The room owns the queue and with it the track, so holding on to the rooms would keep them alive
+        let rooms = Arc::downgrade(&self.context.rooms);
+        let room_id = self.room_id;
+        let track_id = track.id;
+
+        track.on_stream_title(Arc::new(move |title| {
+            let room = rooms
+                .upgrade()
+                .and_then(|rooms| rooms.get(&room_id).map(|r| r.clone()));
+
+            if let Some(room) = room {
+                room.stream_title_changed(track_id, title);
+            }
+        }));
+    }
+
     fn notify(&self, items: Vec<LinearQueueItem>, history: Vec<LinearQueueItem>) {
         self.context.emit(CollabEvent::RoomQueueUpdate {
             room_id: self.room_id,
@@ -0,0 +1,217 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use turntable_core::{Config, Id, RegisteredEncoder, Sample};
+use turntable_impls::WaveEncoder;
+
+use crate::{PrimaryKey, Track};
+
+pub type ClipId = Id<Clip>;
+
+/// Where captured clips are stored, and how much of them is kept.
+#[derive(Debug, Clone)]
+pub struct ClipConfig {
+    /// The directory clips are stored in until they expire, which is emptied on start
+    pub directory: PathBuf,
+    /// How long clips can be downloaded
+    pub lifetime: Duration,
+    /// How many bytes the clips of a room can take up together, after which the oldest are deleted
+    pub quota_in_bytes: u64,
+}
+
+/// The last seconds of what a room played, encoded into a file that can be downloaded until it expires.
+///
+/// The file is deleted once the clip is dropped.
+#[derive(Debug)]
+pub struct Clip {
+    pub id: ClipId,
+    /// The user that captured the clip
+    pub user_id: PrimaryKey,
+    /// The name of the format the clip is encoded in
+    pub format: String,
+    pub content_type: String,
+    /// The length of the clip, in seconds
+    pub duration: f32,
+    /// What was playing when the clip was captured
+    pub track: Option<Track>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Where the encoded clip is stored
+    pub path: PathBuf,
+    pub size_in_bytes: u64,
+}
+
+impl Default for ClipConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("clips"),
+            lifetime: Duration::from_secs(24 * 60 * 60),
+            quota_in_bytes: 512 * 1024 * 1024,
+        }
+    }
+}
+
+impl ClipConfig {
+    /// Deletes the clips that were left behind by a previous run, since clips don't outlive the server.
+    pub fn clear(&self) {
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return;
+        };
+
+        // Only files named like clips are deleted, in case the directory is shared
+        let clips = entries.flatten().map(|e| e.path()).filter(|path| {
+            path.is_file()
+                && path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|s| s.parse::<u64>().is_ok())
+        });
+
+        for path in clips {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Clip {
+    /// The format whose header is written with the size once the clip is encoded, as it's otherwise unknown.
+    const WAV_FORMAT: &'static str = "wav";
+
+    /// Encodes captured samples into a file that expires after the configured lifetime.
+    ///
+    /// Note: This is a blocking operation.
+    pub fn encode(
+        user_id: PrimaryKey,
+        track: Option<Track>,
+        samples: &[Sample],
+        encoder: &RegisteredEncoder,
+        config: &Config,
+        clips: &ClipConfig,
+    ) -> io::Result<Self> {
+        let id = ClipId::new();
+
+        fs::create_dir_all(&clips.directory)?;
+        let path = clips
+            .directory
+            .join(format!("{}.{}", id.value(), encoder.name));
+
+        let size_in_bytes = match write_encoded(&path, samples, encoder, config) {
+            Ok(size) => size,
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+
+        let created_at = Utc::now();
+        let lifetime = chrono::Duration::from_std(clips.lifetime).unwrap_or(chrono::Duration::MAX);
+
+        Ok(Self {
+            id,
+            user_id,
+            format: encoder.name.clone(),
+            content_type: encoder.content_type.clone(),
+            duration: config.samples_to_seconds(samples.len()),
+            track,
+            created_at,
+            expires_at: created_at
+                .checked_add_signed(lifetime)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            path,
+            size_in_bytes,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+}
+
+impl Drop for Clip {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Encodes samples into a file, returning its size in bytes.
+fn write_encoded(
+    path: &Path,
+    samples: &[Sample],
+    encoder: &RegisteredEncoder,
+    config: &Config,
+) -> io::Result<u64> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let mut instance = encoder.create(config.clone(), Default::default());
+
+    // Encoded a second at a time, so the encoded data is never held in memory all at once
+    for chunk in samples.chunks(config.samples_per_sec()) {
+        instance.encode(chunk);
+        io::copy(&mut instance, &mut file)?;
+    }
+
+    // Encoders can hold back the end of the audio to fill a frame, which silence pushes out
+    let padding = config.seconds_to_samples(encoder.latency(config)) / config.channel_count
+        * config.channel_count;
+
+    instance.encode(&vec![0.; padding + config.channel_count]);
+    io::copy(&mut instance, &mut file)?;
+
+    let mut file = file.into_inner().map_err(|e| e.into_error())?;
+    let size_in_bytes = file.stream_position()?;
+
+    if encoder.name == Clip::WAV_FORMAT {
+        let data_size = size_in_bytes.saturating_sub(WaveEncoder::HEADER_SIZE as u64);
+        let header = WaveEncoder::header_for_size(config, &Default::default(), data_size as u32);
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)?;
+    }
+
+    file.flush()?;
+    Ok(size_in_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use turntable_core::EncoderRegistry;
+
+    use super::*;
+
+    #[test]
+    fn test_wav_clip_is_stored_with_its_size() {
+        let directory = tempfile::tempdir().unwrap();
+        let clips = ClipConfig {
+            directory: directory.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let encoders = EncoderRegistry::default();
+        encoders.register::<WaveEncoder>("wav", "audio/wav");
+        let encoder = encoders.get("wav").unwrap();
+
+        let config = Config::default();
+        let samples = vec![0.; config.samples_per_sec() * 3];
+
+        let clip = Clip::encode(1, None, &samples, &encoder, &config, &clips).unwrap();
+        let bytes = fs::read(&clip.path).unwrap();
+
+        let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let data_size = u32::from_le_bytes(bytes[40..44].try_into().unwrap());
+
+        assert_eq!(clip.duration, 3.);
+        assert_eq!(clip.size_in_bytes, bytes.len() as u64);
+        assert_eq!(riff_size as usize, bytes.len() - 8);
+        assert_eq!(data_size as usize, bytes.len() - WaveEncoder::HEADER_SIZE);
+
+        // The file goes away with the clip
+        let path = clip.path.clone();
+        drop(clip);
+
+        assert!(!path.exists());
+    }
+}
@@ -3,12 +3,13 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures_util::{FutureExt, Stream};
 use parking_lot::Mutex;
 use tokio::task::{spawn_blocking, JoinHandle};
-use turntable_core::{Consumer, Id};
+use turntable_core::{Consumer, IcyInterleaver, Id};
 
 use crate::{CollabContext, PrimaryKey};
 
@@ -34,6 +35,16 @@ pub struct RoomConnectionHandle {
     stream: Arc<Consumer>,
     /// The future being polled currently
     fut: Mutex<Option<JoinHandle<Vec<u8>>>>,
+    /// Interleaves the stream title, if the listener asked for ICY metadata
+    icy: Mutex<Option<IcyInterleaver>>,
+}
+
+/// A chunk of encoded audio read from a [RoomConnectionHandle] as soon as it's available.
+#[derive(Debug, Clone)]
+pub struct StreamPacket {
+    /// The total position of the player at the end of the packet, in seconds.
+    pub total_position: f32,
+    pub bytes: Vec<u8>,
 }
 
 impl RoomConnection {
@@ -61,13 +72,63 @@ impl RoomConnectionHandle {
             context: context.clone(),
             fut: Default::default(),
             stream: stream.into(),
+            icy: Default::default(),
         }
     }
 
+    /// Interleaves the stream title of the room into the stream every `interval` bytes, as ICY metadata.
+    pub fn with_icy_metadata(self, interval: usize) -> Self {
+        *self.icy.lock() = Some(IcyInterleaver::new(interval));
+        self
+    }
+
+    /// Returns the amount of audio bytes between ICY metadata blocks, if they are interleaved.
+    pub fn icy_metaint(&self) -> Option<usize> {
+        self.icy.lock().as_ref().map(|i| i.interval())
+    }
+
     /// Get the content type of the stream
     pub fn content_type(&self) -> String {
         self.stream.content_type()
     }
+
+    /// Returns the consumer of the audio stream
+    pub fn consumer(&self) -> Arc<Consumer> {
+        self.stream.clone()
+    }
+
+    /// Waits for new audio, and returns everything that was encoded so far as a packet.
+    ///
+    /// Returns `None` if nothing was encoded before the timeout elapsed.
+    ///
+    /// Note: This is a blocking operation.
+    pub fn read_packet(&self, timeout: Duration) -> Option<StreamPacket> {
+        let mut bytes = Vec::new();
+        let mut buf = vec![0; Self::BUFFER_SIZE];
+
+        loop {
+            // Loaded before reading, so it can't be ahead of the data once nothing is left to read
+            let total_position = self.stream.total_position();
+
+            let amount = self.stream.read_available(&mut buf).unwrap_or_default();
+            bytes.extend_from_slice(&buf[..amount]);
+
+            if amount > 0 {
+                continue;
+            }
+
+            if !bytes.is_empty() {
+                return Some(StreamPacket {
+                    total_position,
+                    bytes,
+                });
+            }
+
+            if !self.stream.wait(timeout) {
+                return None;
+            }
+        }
+    }
 }
 
 impl Drop for RoomConnectionHandle {
@@ -88,8 +149,10 @@ impl Stream for RoomConnectionHandle {
         let fut = fut_guard.get_or_insert_with(|| {
             spawn_blocking(move || {
                 let mut buf = vec![0; Self::BUFFER_SIZE];
-                let _ = cloned_stream.read(&mut buf).ok();
+                let amount = cloned_stream.read(&mut buf).unwrap_or_default();
 
+                // Only send what was actually read, since padding would corrupt the stream
+                buf.truncate(amount);
                 buf
             })
         });
@@ -97,7 +160,18 @@ impl Stream for RoomConnectionHandle {
         match fut.poll_unpin(cx) {
             Poll::Ready(result) => {
                 fut_guard.take();
-                Poll::Ready(Some(Ok(result.expect("infallible"))))
+
+                let bytes = result.expect("infallible");
+                let bytes = match self.icy.lock().as_mut() {
+                    Some(icy) => {
+                        let room = self.context.rooms.get(&self.room_id);
+                        icy.set_title(room.and_then(|r| r.stream_title()).as_deref());
+                        icy.interleave(&bytes)
+                    }
+                    None => bytes,
+                };
+
+                Poll::Ready(Some(Ok(bytes)))
             }
             Poll::Pending => Poll::Pending,
         }
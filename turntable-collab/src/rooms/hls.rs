@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use turntable_core::HlsPackager;
+
+use super::RoomConnectionHandle;
+
+/// An HLS stream of a room, shared by all requests made with the same stream key and format.
+///
+/// HLS players only poll for the playlist and segments,
+/// so the session stays connected to the room until it hasn't been requested for a while.
+pub struct HlsSession {
+    pub packager: HlsPackager,
+    /// Keeps the listener connected to the room
+    _handle: RoomConnectionHandle,
+    last_requested: Mutex<Instant>,
+    /// How long the session lives without being requested
+    timeout: Duration,
+}
+
+impl HlsSession {
+    pub fn new(handle: RoomConnectionHandle, extension: &str) -> Self {
+        let config = handle.consumer().config().clone();
+
+        // Players fetch the playlist at least once per segment, so a session that outlives its whole playlist is abandoned
+        let playlist_duration =
+            config.hls_segment_duration_in_seconds * config.hls_playlist_window as f32;
+
+        Self {
+            packager: HlsPackager::new(handle.consumer(), extension),
+            _handle: handle,
+            last_requested: Mutex::new(Instant::now()),
+            timeout: Duration::from_secs_f32(playlist_duration.max(10.)),
+        }
+    }
+
+    /// Marks the session as requested, keeping it alive.
+    pub fn touch(&self) {
+        *self.last_requested.lock() = Instant::now();
+    }
+
+    /// Returns true if no player has requested anything for longer than the playlist spans.
+    pub fn is_stale(&self) -> bool {
+        self.last_requested.lock().elapsed() > self.timeout
+    }
+}
@@ -0,0 +1,252 @@
+use turntable_core::RegisteredEncoder;
+
+/// A playlist file format that player apps such as VLC and mpv can open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+/// The streams of a room in every available format, to be written as a playlist file.
+#[derive(Debug, Clone)]
+pub struct StreamPlaylist {
+    /// The title of the room
+    pub title: String,
+    /// The title of what is currently playing
+    pub now_playing: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// A stream of a room in one format.
+#[derive(Debug, Clone)]
+pub struct PlaylistEntry {
+    /// The name of the format, such as `mp3`
+    pub format: String,
+    pub url: String,
+}
+
+impl PlaylistFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::M3u => "audio/x-mpegurl",
+            Self::Pls => "audio/x-scpls",
+            Self::Xspf => "application/xspf+xml",
+        }
+    }
+}
+
+impl PlaylistEntry {
+    /// The format of the entry pointing at the HLS playlist of the stream.
+    pub const HLS_FORMAT: &'static str = "hls";
+
+    /// Content types of encoders that write bare samples, which players can't open without knowing the format.
+    const RAW_CONTENT_TYPES: [&'static str; 1] = ["audio/pcm"];
+
+    /// Returns true if the encoder writes bare samples instead of a container format.
+    pub fn is_raw(encoder: &RegisteredEncoder) -> bool {
+        Self::RAW_CONTENT_TYPES
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&encoder.content_type))
+    }
+}
+
+impl StreamPlaylist {
+    /// Writes the playlist in the given format.
+    pub fn render(&self, format: PlaylistFormat) -> String {
+        match format {
+            PlaylistFormat::M3u => self.render_m3u(),
+            PlaylistFormat::Pls => self.render_pls(),
+            PlaylistFormat::Xspf => self.render_xspf(),
+        }
+    }
+
+    /// The title of an entry, including what is playing since most players show it instead of the stream title.
+    fn entry_title(&self, entry: &PlaylistEntry) -> String {
+        let title = match &self.now_playing {
+            Some(now_playing) => format!("{}: {} ({})", self.title, now_playing, entry.format),
+            None => format!("{} ({})", self.title, entry.format),
+        };
+
+        single_line(&title)
+    }
+
+    fn render_m3u(&self) -> String {
+        let mut lines = vec![
+            "#EXTM3U".to_string(),
+            format!("#PLAYLIST:{}", single_line(&self.title)),
+        ];
+
+        for entry in &self.entries {
+            // A length of -1 marks the entry as a live stream
+            lines.push(format!("#EXTINF:-1,{}", self.entry_title(entry)));
+            lines.push(entry.url.clone());
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    fn render_pls(&self) -> String {
+        let mut lines = vec!["[playlist]".to_string()];
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let number = index + 1;
+
+            lines.push(format!("File{}={}", number, entry.url));
+            lines.push(format!("Title{}={}", number, self.entry_title(entry)));
+            lines.push(format!("Length{}=-1", number));
+        }
+
+        lines.push(format!("NumberOfEntries={}", self.entries.len()));
+        lines.push("Version=2".to_string());
+
+        lines.join("\n") + "\n"
+    }
+
+    fn render_xspf(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+        xml += "<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n";
+        xml += &format!("  <title>{}</title>\n", escape_xml(&self.title));
+
+        if let Some(now_playing) = &self.now_playing {
+            xml += &format!("  <annotation>{}</annotation>\n", escape_xml(now_playing));
+        }
+
+        xml += "  <trackList>\n";
+
+        for entry in &self.entries {
+            xml += "    <track>\n";
+            xml += &format!("      <location>{}</location>\n", escape_xml(&entry.url));
+            xml += &format!(
+                "      <title>{}</title>\n",
+                escape_xml(&self.entry_title(entry))
+            );
+            xml += &format!("      <creator>{}</creator>\n", escape_xml(&self.title));
+            xml += "    </track>\n";
+        }
+
+        xml += "  </trackList>\n";
+        xml += "</playlist>\n";
+
+        xml
+    }
+}
+
+/// Replaces line breaks, which would end a line-based entry early.
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use turntable_core::EncoderRegistry;
+    use turntable_impls::{PcmEncoder, WaveEncoder};
+
+    use super::*;
+
+    fn playlist(now_playing: Option<&str>) -> StreamPlaylist {
+        StreamPlaylist {
+            title: "Late\nNight".to_string(),
+            now_playing: now_playing.map(String::from),
+            entries: vec![
+                PlaylistEntry {
+                    format: "mp3".to_string(),
+                    url: "https://example.com/v1/streams/abc?format=mp3".to_string(),
+                },
+                PlaylistEntry {
+                    format: "hls".to_string(),
+                    url: "https://example.com/v1/streams/abc/hls/index.m3u8".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_single_line() {
+        assert_eq!(single_line("a\r\nb\nc"), "a  b c");
+        assert_eq!(single_line("plain"), "plain");
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+        assert_eq!(escape_xml("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn test_render_m3u() {
+        let m3u = playlist(Some("Song\nTitle")).render(PlaylistFormat::M3u);
+
+        assert_eq!(
+            m3u,
+            "#EXTM3U\n\
+             #PLAYLIST:Late Night\n\
+             #EXTINF:-1,Late Night: Song Title (mp3)\n\
+             https://example.com/v1/streams/abc?format=mp3\n\
+             #EXTINF:-1,Late Night: Song Title (hls)\n\
+             https://example.com/v1/streams/abc/hls/index.m3u8\n"
+        );
+    }
+
+    #[test]
+    fn test_render_pls() {
+        let pls = playlist(None).render(PlaylistFormat::Pls);
+
+        assert_eq!(
+            pls,
+            "[playlist]\n\
+             File1=https://example.com/v1/streams/abc?format=mp3\n\
+             Title1=Late Night (mp3)\n\
+             Length1=-1\n\
+             File2=https://example.com/v1/streams/abc/hls/index.m3u8\n\
+             Title2=Late Night (hls)\n\
+             Length2=-1\n\
+             NumberOfEntries=2\n\
+             Version=2\n"
+        );
+    }
+
+    #[test]
+    fn test_render_xspf() {
+        let mut playlist = playlist(Some("Rock & Roll"));
+        playlist.entries.truncate(1);
+
+        assert_eq!(
+            playlist.render(PlaylistFormat::Xspf),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  \
+               <title>Late\nNight</title>\n  \
+               <annotation>Rock &amp; Roll</annotation>\n  \
+               <trackList>\n    \
+                 <track>\n      \
+                   <location>https://example.com/v1/streams/abc?format=mp3</location>\n      \
+                   <title>Late Night: Rock &amp; Roll (mp3)</title>\n      \
+                   <creator>Late\nNight</creator>\n    \
+                 </track>\n  \
+               </trackList>\n\
+             </playlist>\n"
+        );
+    }
+
+    #[test]
+    fn test_raw_formats_are_detected() {
+        let encoders = EncoderRegistry::default();
+        encoders.register::<WaveEncoder>("wav", "audio/wav");
+        encoders.register::<PcmEncoder>("pcm", "audio/pcm");
+
+        assert!(!PlaylistEntry::is_raw(&encoders.get("wav").unwrap()));
+        assert!(PlaylistEntry::is_raw(&encoders.get("pcm").unwrap()));
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll},
+};
+
+use futures_util::{FutureExt, Stream};
+use parking_lot::Mutex;
+use tokio::task::{spawn_blocking, JoinHandle};
+use turntable_core::{Id, Preview};
+
+use crate::PrimaryKey;
+
+use super::RoomId;
+
+pub type PreviewId = Id<PreviewHandle>;
+
+/// A chunk of the stream, and whether it's the last one.
+type PreviewRead = (Vec<u8>, bool);
+
+/// A handle to a private preview of a track, which tears down its player when dropped.
+///
+/// Previews play independently of the room, so listening to one doesn't show up as a connection.
+/// The stream ends once the track has played to its end.
+pub struct PreviewHandle {
+    pub id: PreviewId,
+    room_id: RoomId,
+    user_id: PrimaryKey,
+    preview: Arc<Preview>,
+    /// The future being polled currently
+    fut: Mutex<Option<JoinHandle<PreviewRead>>>,
+    /// Whether the end of the track was read, so the stream is over
+    is_finished: AtomicBool,
+}
+
+/// Controls a preview while it's being streamed, without keeping it alive.
+#[derive(Clone)]
+pub struct PreviewControl {
+    pub id: PreviewId,
+    /// The room the previewed track is in
+    pub room_id: RoomId,
+    /// The user that started the preview, who is the only one that can control it
+    pub user_id: PrimaryKey,
+    preview: Weak<Preview>,
+}
+
+impl PreviewHandle {
+    const BUFFER_SIZE: usize = 1024 * 4;
+
+    pub fn new(preview: Preview, room_id: RoomId, user_id: PrimaryKey) -> Self {
+        Self {
+            id: PreviewId::new(),
+            room_id,
+            user_id,
+            preview: preview.into(),
+            fut: Default::default(),
+            is_finished: Default::default(),
+        }
+    }
+
+    /// Get the content type of the stream
+    pub fn content_type(&self) -> String {
+        self.preview.consumer().content_type()
+    }
+
+    /// Returns a control for the preview, which stops working once the preview is dropped.
+    pub fn control(&self) -> PreviewControl {
+        PreviewControl {
+            id: self.id,
+            room_id: self.room_id,
+            user_id: self.user_id,
+            preview: Arc::downgrade(&self.preview),
+        }
+    }
+}
+
+impl PreviewControl {
+    /// Whether the preview is still being streamed.
+    pub fn is_alive(&self) -> bool {
+        self.preview.strong_count() > 0
+    }
+
+    /// Seeks the preview to the given position in seconds, without affecting the room.
+    ///
+    /// Returns false if the preview isn't being streamed anymore.
+    pub fn seek(&self, position: f32) -> bool {
+        let Some(preview) = self.preview.upgrade() else {
+            return false;
+        };
+
+        preview.player.seek(position);
+        true
+    }
+}
+
+impl Stream for PreviewHandle {
+    type Item = Result<Vec<u8>, Infallible>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.is_finished.load(Ordering::Relaxed) {
+            return Poll::Ready(None);
+        }
+
+        let mut fut_guard = self.fut.lock();
+        let preview = self.preview.clone();
+
+        let fut = fut_guard.get_or_insert_with(|| {
+            spawn_blocking(move || {
+                // Checked before reading, so what is read next includes the end of the track
+                let is_finished = preview.is_finished();
+
+                let mut buf = vec![0; Self::BUFFER_SIZE];
+                let amount = preview.consumer().read(&mut buf).unwrap_or_default();
+
+                buf.truncate(amount);
+                (buf, is_finished)
+            })
+        });
+
+        match fut.poll_unpin(cx) {
+            Poll::Ready(result) => {
+                fut_guard.take();
+
+                let (bytes, is_finished) = result.expect("infallible");
+                self.is_finished.store(is_finished, Ordering::Relaxed);
+
+                Poll::Ready(Some(Ok(bytes)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read, path::Path, time::Duration};
+
+    use futures_util::StreamExt;
+    use turntable_core::{Config, Encoder, Pipeline};
+    use turntable_impls::{LoadableFile, WaveEncoder};
+
+    use super::*;
+    use crate::CollabPipeline;
+
+    /// Writes a WAV file of a constant tone with the given length.
+    fn write_wave(path: &Path, config: &Config, seconds: f32) {
+        let mut encoder = WaveEncoder::new(config.clone(), Default::default());
+        encoder.encode(&vec![0.25; config.seconds_to_samples(seconds)]);
+
+        let mut bytes = vec![];
+        encoder.read_to_end(&mut bytes).unwrap();
+
+        let data_size = (bytes.len() - WaveEncoder::HEADER_SIZE) as u32;
+        let header = WaveEncoder::header_for_size(config, &Default::default(), data_size);
+        bytes[..WaveEncoder::HEADER_SIZE].copy_from_slice(&header);
+
+        fs::write(path, bytes).unwrap();
+    }
+
+    async fn start_preview(pipeline: &CollabPipeline, path: &Path) -> PreviewHandle {
+        let loadable = LoadableFile::new(fs::File::open(path).unwrap().into());
+        let sink = pipeline.ingest(loadable).await.unwrap();
+
+        let encoder = pipeline.encoders().get("wav").unwrap();
+        let preview = pipeline
+            .create_preview(sink, 0., &encoder, Default::default())
+            .unwrap();
+
+        PreviewHandle::new(preview, 1, 1)
+    }
+
+    /// Reads the stream until it ends, failing if it takes longer than the timeout.
+    async fn read_to_end(handle: PreviewHandle, timeout: Duration) -> usize {
+        let read = handle.fold(
+            0,
+            |total, bytes| async move { total + bytes.unwrap().len() },
+        );
+
+        tokio::time::timeout(timeout, read)
+            .await
+            .expect("the preview should end with the track")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_preview_ends_with_the_track() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("track.wav");
+
+        let pipeline: CollabPipeline = Pipeline::new(Config::default());
+        pipeline
+            .encoders()
+            .register::<WaveEncoder>("wav", "audio/wav");
+        write_wave(&path, pipeline.config(), 0.5);
+
+        let handle = start_preview(&pipeline, &path).await;
+        let read = read_to_end(handle, Duration::from_secs(10)).await;
+
+        let track_size = pipeline.config().seconds_to_samples(0.5) * 2;
+        assert!(read >= track_size, "the whole track is streamed");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_preview_control_seeks_while_streaming() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("track.wav");
+
+        let pipeline: CollabPipeline = Pipeline::new(Config::default());
+        pipeline
+            .encoders()
+            .register::<WaveEncoder>("wav", "audio/wav");
+        write_wave(&path, pipeline.config(), 30.);
+
+        let handle = start_preview(&pipeline, &path).await;
+        let control = handle.control();
+
+        // Seeking close to the end makes the stream end long before the track would
+        assert!(control.seek(29.5));
+        read_to_end(handle, Duration::from_secs(10)).await;
+
+        assert!(!control.is_alive());
+        assert!(!control.seek(0.));
+    }
+}
@@ -0,0 +1,664 @@
+use std::{
+    fs::{self, File},
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use turntable_core::{Config, Consumer, EncoderOptions};
+use turntable_impls::WaveEncoder;
+
+use crate::{LinearQueueItem, PrimaryKey};
+
+use super::RoomId;
+
+const AUDIO_EXTENSION: &str = "wav";
+const CUE_SHEET_EXTENSION: &str = "json";
+
+/// Where room recordings are written, and how long they are kept.
+#[derive(Debug, Clone)]
+pub struct RecordingConfig {
+    /// The directory recordings are written to, in a subdirectory per room
+    pub directory: PathBuf,
+    /// How much audio a file holds before the next one is started
+    pub file_duration: Duration,
+    /// How many bytes all recordings can take up together, after which the oldest are deleted
+    pub quota_in_bytes: Option<u64>,
+    /// How long recordings are kept before they are deleted
+    pub retention: Option<Duration>,
+}
+
+/// Records the stream of a room into rotating WAV files, each with a JSON cue sheet of the tracks that played.
+pub struct Recorder {
+    pub started_at: DateTime<Utc>,
+    state: Arc<Mutex<RecorderState>>,
+    cue_sender: Sender<Cue>,
+    is_stopped: Arc<AtomicBool>,
+    current_file: Arc<Mutex<Option<PathBuf>>>,
+}
+
+/// What a [Recorder] is currently doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecorderState {
+    Recording,
+    /// Writing a file failed, which stopped the recording
+    Failed(String),
+    Stopped,
+}
+
+/// The tracks that played in a recorded file, stored next to it with the same name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CueSheet {
+    pub room_id: RoomId,
+    pub started_at: DateTime<Utc>,
+    /// The amount of audio in the file, in seconds
+    pub duration: f32,
+    pub entries: Vec<CueEntry>,
+}
+
+/// A track in a [CueSheet].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CueEntry {
+    /// Where the track starts in the file, in seconds
+    pub offset: f32,
+    pub title: String,
+    pub artist: Option<String>,
+    pub canonical: String,
+    pub source: String,
+    /// The user that added the track to the queue
+    pub user_id: PrimaryKey,
+}
+
+/// A recorded file of a room.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub file_name: String,
+    pub size_in_bytes: u64,
+    pub cue_sheet: CueSheet,
+}
+
+/// A change of the current item, at the total position of the player it happened at.
+struct Cue {
+    item: Option<LinearQueueItem>,
+    total_position: f32,
+}
+
+/// Everything the recorder thread needs to write files.
+struct RecorderTask {
+    room_id: RoomId,
+    consumer: Consumer,
+    config: RecordingConfig,
+    pipeline_config: Config,
+    options: EncoderOptions,
+    cues: Receiver<Cue>,
+    state: Arc<Mutex<RecorderState>>,
+    is_stopped: Arc<AtomicBool>,
+    current_file: Arc<Mutex<Option<PathBuf>>>,
+}
+
+/// The file a recorder is currently writing.
+struct RecordingFile {
+    path: PathBuf,
+    file: File,
+    /// The amount of audio bytes written after the header
+    data_size: u64,
+    bytes_per_second: f32,
+    cue_sheet: CueSheet,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("recordings"),
+            file_duration: Duration::from_secs(60 * 60),
+            quota_in_bytes: None,
+            retention: None,
+        }
+    }
+}
+
+impl RecordingConfig {
+    /// Returns the recordings of a room, oldest first.
+    ///
+    /// Files without a readable cue sheet are left out.
+    pub fn list(&self, room_id: RoomId) -> io::Result<Vec<Recording>> {
+        let directory = self.room_directory(room_id);
+
+        if !directory.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut recordings: Vec<_> = audio_files(&directory)?
+            .into_iter()
+            .filter_map(|(path, metadata)| {
+                let cue_sheet = fs::read(path.with_extension(CUE_SHEET_EXTENSION)).ok()?;
+
+                Some(Recording {
+                    file_name: path.file_name()?.to_string_lossy().to_string(),
+                    size_in_bytes: metadata.len(),
+                    cue_sheet: serde_json::from_slice(&cue_sheet).ok()?,
+                })
+            })
+            .collect();
+
+        recordings.sort_by_key(|r| r.cue_sheet.started_at);
+        Ok(recordings)
+    }
+
+    /// Deletes the recordings that are past the retention, and then the oldest ones until all of them fit the quota.
+    ///
+    /// The files in `current` are being written, so they're never deleted.
+    pub fn enforce_limits(&self, current: &[PathBuf]) {
+        let Ok(rooms) = fs::read_dir(&self.directory) else {
+            return;
+        };
+
+        let mut files: Vec<_> = rooms
+            .flatten()
+            .filter_map(|room| audio_files(&room.path()).ok())
+            .flatten()
+            .collect();
+
+        files.sort_by_key(|(_, metadata)| metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+
+        let size_of = |path: &Path| {
+            let cue_sheet = path.with_extension(CUE_SHEET_EXTENSION);
+
+            [path, cue_sheet.as_path()]
+                .into_iter()
+                .filter_map(|p| fs::metadata(p).ok())
+                .map(|m| m.len())
+                .sum::<u64>()
+        };
+
+        let mut total_size: u64 = files.iter().map(|(path, _)| size_of(path)).sum();
+
+        for (path, metadata) in files {
+            if current.contains(&path) {
+                continue;
+            }
+
+            let is_expired = self.retention.is_some_and(|retention| {
+                metadata
+                    .modified()
+                    .ok()
+                    .and_then(|m| m.elapsed().ok())
+                    .is_some_and(|age| age > retention)
+            });
+
+            let is_over_quota = self.quota_in_bytes.is_some_and(|q| total_size > q);
+
+            if !is_expired && !is_over_quota {
+                continue;
+            }
+
+            total_size = total_size.saturating_sub(size_of(&path));
+
+            let _ = fs::remove_file(path.with_extension(CUE_SHEET_EXTENSION));
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn room_directory(&self, room_id: RoomId) -> PathBuf {
+        self.directory.join(room_id.to_string())
+    }
+}
+
+/// Returns the audio files in a directory, along with their metadata.
+fn audio_files(directory: &Path) -> io::Result<Vec<(PathBuf, fs::Metadata)>> {
+    let files = fs::read_dir(directory)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == AUDIO_EXTENSION))
+        .filter_map(|path| fs::metadata(&path).ok().map(|m| (path, m)))
+        .collect();
+
+    Ok(files)
+}
+
+impl Recorder {
+    /// The name of the encoder the stream is recorded with, which is wrapped in a WAV file when it's complete.
+    pub const FORMAT: &'static str = "pcm";
+
+    /// How long to wait for audio before checking if the recorder was stopped.
+    const WAIT_TIMEOUT: Duration = Duration::from_millis(500);
+    /// How often the cue sheet of the current file is updated.
+    const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+    const BUFFER_SIZE: usize = 1024 * 16;
+
+    /// Starts recording from a consumer in the background.
+    ///
+    /// * `current_item` - What is playing when the recording starts, which the first file begins with.
+    pub fn start(
+        room_id: RoomId,
+        consumer: Consumer,
+        config: RecordingConfig,
+        current_item: Option<LinearQueueItem>,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(RecorderState::Recording));
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let current_file = Arc::new(Mutex::new(None));
+        let (cue_sender, cues) = unbounded();
+
+        let recorder = Self {
+            started_at: Utc::now(),
+            state: state.clone(),
+            cue_sender,
+            is_stopped: is_stopped.clone(),
+            current_file: current_file.clone(),
+        };
+
+        recorder.queue_item_update(current_item, 0.);
+
+        let task = RecorderTask {
+            room_id,
+            config,
+            pipeline_config: consumer.config().clone(),
+            consumer,
+            options: EncoderOptions::default(),
+            cues,
+            state,
+            is_stopped,
+            current_file,
+        };
+
+        thread::spawn(move || task.run());
+        recorder
+    }
+
+    pub fn state(&self) -> RecorderState {
+        self.state.lock().clone()
+    }
+
+    /// Returns the path of the file that is being written, if any.
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.current_file.lock().clone()
+    }
+
+    /// Adds a change of the current item to the cue sheet, once the audio at the given total position is written.
+    pub fn queue_item_update(&self, item: Option<LinearQueueItem>, total_position: f32) {
+        let _ = self.cue_sender.send(Cue {
+            item,
+            total_position,
+        });
+    }
+
+    /// Stops the recorder, finishing the current file.
+    pub fn stop(&self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop()
+    }
+}
+
+impl RecorderTask {
+    fn run(self) {
+        let new_state = match self.record() {
+            Ok(()) => RecorderState::Stopped,
+            Err(error) => RecorderState::Failed(error.to_string()),
+        };
+
+        *self.current_file.lock() = None;
+        *self.state.lock() = new_state;
+    }
+
+    /// Writes the stream to files until the recorder is stopped, or writing fails.
+    fn record(&self) -> io::Result<()> {
+        let directory = self.config.room_directory(self.room_id);
+        fs::create_dir_all(&directory)?;
+
+        let mut buf = vec![0; Recorder::BUFFER_SIZE];
+        let mut file: Option<RecordingFile> = None;
+        let mut current_item = None;
+        let mut pending_cues = Vec::new();
+        let mut last_sync = Instant::now();
+
+        while !self.is_stopped() {
+            // Loaded before reading, so it can't be ahead of the data once nothing is left to read
+            let total_position = self.consumer.total_position();
+            let amount = self.consumer.read_available(&mut buf)?;
+
+            if amount == 0 {
+                self.consumer.wait(Recorder::WAIT_TIMEOUT);
+                continue;
+            }
+
+            if file.as_ref().is_none_or(|f| self.is_full(f)) {
+                if let Some(previous) = file.take() {
+                    previous.finish(&self.pipeline_config, &self.options)?;
+                }
+
+                let mut next =
+                    RecordingFile::create(&directory, self.room_id, self.bytes_per_second())?;
+
+                if let Some(item) = &current_item {
+                    next.add_entry(0., item)?;
+                }
+
+                *self.current_file.lock() = Some(next.path.clone());
+                file = Some(next);
+            }
+
+            let file = file.as_mut().expect("file is created before writing");
+            file.write(&buf[..amount])?;
+
+            pending_cues.extend(self.cues.try_iter());
+
+            let (written, not_written): (Vec<_>, Vec<_>) = pending_cues
+                .into_iter()
+                .partition(|c| c.total_position <= total_position);
+
+            for cue in written {
+                // The item started somewhere in the audio that was just written
+                let offset = (file.duration() - (total_position - cue.total_position)).max(0.);
+
+                if let Some(item) = &cue.item {
+                    file.add_entry(offset, item)?;
+                }
+
+                current_item = cue.item;
+            }
+
+            pending_cues = not_written;
+
+            if last_sync.elapsed() >= Recorder::SYNC_INTERVAL {
+                file.write_cue_sheet()?;
+                last_sync = Instant::now();
+            }
+        }
+
+        if let Some(file) = file {
+            file.finish(&self.pipeline_config, &self.options)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a file holds enough audio to start the next one.
+    fn is_full(&self, file: &RecordingFile) -> bool {
+        // WAV files can't hold more than 4GB
+        let max_data_size =
+            (u32::MAX as usize - WaveEncoder::HEADER_SIZE - Recorder::BUFFER_SIZE) as u64;
+
+        file.duration() >= self.config.file_duration.as_secs_f32()
+            || file.data_size >= max_data_size
+    }
+
+    fn bytes_per_second(&self) -> f32 {
+        let bytes_per_sample = self.options.sample_format.unwrap_or_default().bit_depth() / 8;
+        (self.pipeline_config.samples_per_sec() * bytes_per_sample as usize) as f32
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.is_stopped.load(Ordering::Relaxed)
+    }
+}
+
+impl RecordingFile {
+    /// Creates a file named after the current time, leaving room for the header.
+    fn create(directory: &Path, room_id: RoomId, bytes_per_second: f32) -> io::Result<Self> {
+        let started_at = Utc::now();
+
+        // Milliseconds are included, so files that are started within the same second don't overwrite each other
+        let path = directory
+            .join(started_at.format("%Y-%m-%dT%H-%M-%S-%3fZ").to_string())
+            .with_extension(AUDIO_EXTENSION);
+
+        let mut file = File::create(&path)?;
+        file.write_all(&[0; WaveEncoder::HEADER_SIZE])?;
+
+        let new = Self {
+            path,
+            file,
+            data_size: 0,
+            bytes_per_second,
+            cue_sheet: CueSheet {
+                room_id,
+                started_at,
+                duration: 0.,
+                entries: vec![],
+            },
+        };
+
+        new.write_cue_sheet()?;
+        Ok(new)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.data_size += bytes.len() as u64;
+
+        Ok(())
+    }
+
+    /// The amount of audio written, in seconds.
+    fn duration(&self) -> f32 {
+        self.data_size as f32 / self.bytes_per_second
+    }
+
+    fn add_entry(&mut self, offset: f32, item: &LinearQueueItem) -> io::Result<()> {
+        let metadata = &item.track.metadata;
+
+        self.cue_sheet.entries.push(CueEntry {
+            offset,
+            title: metadata.title.clone(),
+            artist: metadata.artist.clone(),
+            canonical: metadata.canonical.clone(),
+            source: metadata.source.clone(),
+            user_id: item.user_id,
+        });
+
+        self.write_cue_sheet()
+    }
+
+    fn write_cue_sheet(&self) -> io::Result<()> {
+        let cue_sheet = CueSheet {
+            duration: self.duration(),
+            ..self.cue_sheet.clone()
+        };
+
+        fs::write(
+            self.path.with_extension(CUE_SHEET_EXTENSION),
+            serde_json::to_vec_pretty(&cue_sheet)?,
+        )
+    }
+
+    /// Writes the header now that the size is known, and the final duration to the cue sheet.
+    fn finish(mut self, config: &Config, options: &EncoderOptions) -> io::Result<()> {
+        let header = WaveEncoder::header_for_size(config, options, self.data_size as u32);
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.file.flush()?;
+
+        self.write_cue_sheet()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Weak;
+
+    use turntable_core::Encoder;
+    use turntable_impls::PcmEncoder;
+
+    use super::*;
+    use crate::{Input, UploadData, UploadInput};
+
+    const ROOM_ID: RoomId = 1;
+
+    fn item(title: &str) -> LinearQueueItem {
+        let upload = UploadData {
+            id: 1,
+            hash: String::new(),
+            file_name: format!("{}.wav", title),
+            content_type: "audio/wav".to_string(),
+            size: 0,
+            title: Some(title.to_string()),
+            artist: None,
+            duration: None,
+            room_id: ROOM_ID,
+            user_id: 1,
+            created_at: Utc::now(),
+        };
+
+        LinearQueueItem {
+            user_id: 1,
+            track: Input::Upload(UploadInput::new(upload, PathBuf::new())).into(),
+        }
+    }
+
+    /// Creates a recorded file with a cue sheet, last modified `age` ago.
+    fn create_recording(
+        config: &RecordingConfig,
+        name: &str,
+        size: usize,
+        age: Duration,
+    ) -> PathBuf {
+        let directory = config.room_directory(ROOM_ID);
+        fs::create_dir_all(&directory).unwrap();
+
+        let path = directory.join(name).with_extension(AUDIO_EXTENSION);
+        fs::write(&path, vec![0; size]).unwrap();
+        fs::write(path.with_extension(CUE_SHEET_EXTENSION), "{}").unwrap();
+
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+
+        path
+    }
+
+    fn titles(recording: &Recording) -> Vec<(f32, &str)> {
+        recording
+            .cue_sheet
+            .entries
+            .iter()
+            .map(|e| (e.offset, e.title.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_files_are_rotated_with_cue_offsets() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = RecordingConfig {
+            directory: directory.path().to_path_buf(),
+            file_duration: Duration::from_secs(1),
+            ..Default::default()
+        };
+
+        // A second of 16-bit audio fills exactly two reads of the recorder, so the files are
+        // rotated at the same place however the reads line up with what is pushed
+        let pipeline_config = Config {
+            sample_rate: Recorder::BUFFER_SIZE,
+            channel_count: 1,
+            ..Default::default()
+        };
+        let encoder = PcmEncoder::new(pipeline_config.clone(), EncoderOptions::default());
+        let (consumer, producer) =
+            Consumer::new(Box::new(encoder), pipeline_config.clone(), Weak::new());
+
+        let recorder = Recorder::start(ROOM_ID, consumer, config.clone(), Some(item("One")));
+        recorder.queue_item_update(Some(item("Two")), 1.5);
+        recorder.queue_item_update(Some(item("Three")), 2.25);
+
+        // 2.5 seconds of audio, pushed in chunks like a player would
+        let chunk = vec![0.; pipeline_config.samples_per_sec() / 8];
+
+        for i in 1..=20 {
+            producer.push(&chunk, i as f32 / 8.);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        recorder.stop();
+
+        let started = Instant::now();
+        while recorder.state() == RecorderState::Recording {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(recorder.state(), RecorderState::Stopped);
+        assert_eq!(recorder.current_file(), None);
+
+        let recordings = config.list(ROOM_ID).unwrap();
+        let durations: Vec<_> = recordings.iter().map(|r| r.cue_sheet.duration).collect();
+
+        assert_eq!(durations, vec![1., 1., 0.5]);
+        assert_eq!(titles(&recordings[0]), vec![(0., "One")]);
+        assert_eq!(titles(&recordings[1]), vec![(0., "One"), (0.5, "Two")]);
+
+        assert_eq!(titles(&recordings[2]), vec![(0., "Two"), (0.25, "Three")]);
+
+        // The header is written with the size once the file is finished
+        let file = fs::read(directory.path().join("1").join(&recordings[2].file_name)).unwrap();
+        let data_size = u32::from_le_bytes(file[40..44].try_into().unwrap());
+
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(data_size as usize, file.len() - WaveEncoder::HEADER_SIZE);
+    }
+
+    #[test]
+    fn test_expired_recordings_are_deleted() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = RecordingConfig {
+            directory: directory.path().to_path_buf(),
+            retention: Some(Duration::from_secs(60 * 60)),
+            ..Default::default()
+        };
+
+        let hour = Duration::from_secs(60 * 60);
+        let expired = create_recording(&config, "expired", 10, hour * 2);
+        let current = create_recording(&config, "current", 10, hour * 3);
+        let kept = create_recording(&config, "kept", 10, Duration::ZERO);
+
+        config.enforce_limits(std::slice::from_ref(&current));
+
+        assert!(!expired.exists());
+        assert!(!expired.with_extension(CUE_SHEET_EXTENSION).exists());
+        assert!(current.exists());
+        assert!(kept.exists());
+    }
+
+    #[test]
+    fn test_oldest_recordings_are_deleted_over_quota() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = RecordingConfig {
+            directory: directory.path().to_path_buf(),
+            // Each recording takes up 100 bytes, and 2 bytes for its cue sheet
+            quota_in_bytes: Some(250),
+            ..Default::default()
+        };
+
+        let minute = Duration::from_secs(60);
+        let oldest = create_recording(&config, "oldest", 100, minute * 3);
+        let older = create_recording(&config, "older", 100, minute * 2);
+        let old = create_recording(&config, "old", 100, minute);
+        let current = create_recording(&config, "current", 100, Duration::ZERO);
+
+        config.enforce_limits(std::slice::from_ref(&current));
+
+        assert!(!oldest.exists());
+        assert!(!older.exists());
+        assert!(old.exists());
+        assert!(current.exists());
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use turntable_core::{EncoderOptions, Id, RegisteredEncoder};
+use turntable_impls::{IcecastSource, IcecastTarget};
+
+use crate::{CollabContext, PrimaryKey};
+
+use super::RoomId;
+
+pub type RelayId = Id<Relay>;
+
+/// Pushes the stream of a room to a mount on an Icecast server, as a source client.
+///
+/// Failed connections are retried with an exponential backoff until the relay is stopped.
+pub struct Relay {
+    pub id: RelayId,
+    /// The user the relay connects to the room as
+    pub user_id: PrimaryKey,
+    pub target: IcecastTarget,
+    /// The name of the format the stream is pushed in
+    pub format: String,
+    state: Arc<Mutex<RelayState>>,
+    is_stopped: Arc<AtomicBool>,
+}
+
+/// What a [Relay] is currently doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayState {
+    Connecting,
+    Connected,
+    /// The last attempt failed, and the relay is waiting to try again
+    Retrying {
+        attempt: u32,
+        error: String,
+    },
+    Stopped,
+}
+
+/// Everything the relay thread needs to reconnect.
+struct RelayTask {
+    context: CollabContext,
+    room_id: RoomId,
+    user_id: PrimaryKey,
+    target: IcecastTarget,
+    encoder: RegisteredEncoder,
+    options: EncoderOptions,
+    state: Arc<Mutex<RelayState>>,
+    is_stopped: Arc<AtomicBool>,
+}
+
+impl Relay {
+    /// How long connecting and writing may take before the connection is considered failed.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    const MIN_BACKOFF: Duration = Duration::from_secs(1);
+    const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+    /// The source of the room connection, as shown to other members.
+    pub const SOURCE: &'static str = "icecast";
+
+    /// Starts relaying the stream of a room in the background.
+    pub fn start(
+        context: &CollabContext,
+        room_id: RoomId,
+        user_id: PrimaryKey,
+        target: IcecastTarget,
+        encoder: RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(RelayState::Connecting));
+        let is_stopped = Arc::new(AtomicBool::new(false));
+
+        let relay = Self {
+            id: RelayId::new(),
+            user_id,
+            target: target.clone(),
+            format: encoder.name.clone(),
+            state: state.clone(),
+            is_stopped: is_stopped.clone(),
+        };
+
+        let task = RelayTask {
+            context: context.clone(),
+            room_id,
+            user_id,
+            target,
+            encoder,
+            options,
+            state,
+            is_stopped,
+        };
+
+        thread::spawn(move || task.run());
+        relay
+    }
+
+    pub fn state(&self) -> RelayState {
+        self.state.lock().clone()
+    }
+
+    /// Stops the relay, disconnecting it from the server and the room.
+    pub fn stop(&self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.stop()
+    }
+}
+
+impl RelayTask {
+    fn run(self) {
+        let mut attempt = 0;
+
+        while !self.is_stopped() {
+            *self.state.lock() = RelayState::Connecting;
+
+            let Err(error) = self.push(&mut attempt) else {
+                break;
+            };
+
+            attempt += 1;
+
+            *self.state.lock() = RelayState::Retrying { attempt, error };
+            self.sleep(backoff(attempt));
+        }
+
+        *self.state.lock() = RelayState::Stopped;
+    }
+
+    /// Connects to the server and pushes the stream until the relay is stopped, or the connection fails.
+    fn push(&self, attempt: &mut u32) -> Result<(), String> {
+        let room = self
+            .context
+            .rooms
+            .get(&self.room_id)
+            .map(|r| r.clone())
+            .ok_or("Room no longer exists")?;
+
+        let mut source =
+            IcecastSource::connect(&self.target, &self.encoder.content_type, Relay::TIMEOUT)
+                .map_err(|e| e.to_string())?;
+
+        // Only join the room once the server accepted the stream, so failed attempts don't show up as listeners
+        let handle = room
+            .connect(
+                self.user_id,
+                Relay::SOURCE.to_string(),
+                &self.encoder,
+                self.options.clone(),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let consumer = handle.consumer();
+
+        *self.state.lock() = RelayState::Connected;
+        *attempt = 0;
+
+        let mut buf = vec![0; 1024 * 4];
+        let mut title = None;
+
+        while !self.is_stopped() {
+            let new_title = room.stream_title();
+
+            if new_title != title {
+                // The stream itself is fine without a title, so a failed update is not worth reconnecting over
+                if let Some(new_title) = &new_title {
+                    let _ = source.update_metadata(new_title);
+                }
+
+                title = new_title;
+            }
+
+            let amount = consumer.read(&mut buf).map_err(|e| e.to_string())?;
+
+            source
+                .write_all(&buf[..amount])
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Sleeps for the given duration, waking up early if the relay is stopped.
+    fn sleep(&self, duration: Duration) {
+        let start = Instant::now();
+
+        while !self.is_stopped() && start.elapsed() < duration {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.is_stopped.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns how long to wait before the given attempt, doubling every time.
+fn backoff(attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+
+    Relay::MIN_BACKOFF
+        .saturating_mul(1 << exponent)
+        .min(Relay::MAX_BACKOFF)
+}
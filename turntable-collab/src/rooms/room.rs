@@ -1,15 +1,19 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use parking_lot::Mutex;
-use turntable_core::PlayerContext as Player;
-use turntable_impls::WaveEncoder;
+use tokio::task::spawn_blocking;
+use turntable_core::{EncoderOptions, PlayerContext as Player, QueueItem, RegisteredEncoder};
+use turntable_impls::IcecastTarget;
 
 use crate::{
     events::CollabEvent, CollabContext, LinearQueue, LinearQueueItem, PrimaryKey, RoomData,
-    RoomMemberData, WrappedQueueNotifier,
+    RoomMemberData, Track, TrackId, WrappedQueueNotifier,
 };
 
-use super::{RoomConnection, RoomConnectionHandle, RoomConnectionId, RoomError};
+use super::{
+    Clip, PreviewHandle, Recorder, RecorderState, Relay, RoomConnection, RoomConnectionHandle,
+    RoomConnectionId, RoomError,
+};
 
 pub type RoomId = PrimaryKey;
 
@@ -20,6 +24,14 @@ pub struct Room {
     data: Mutex<RoomData>,
     /// The users currently connected and listening in this room
     connections: Mutex<Vec<RoomConnection>>,
+    /// The title of what is currently playing, sent to listeners that support in-band metadata
+    stream_title: Mutex<Option<String>>,
+    /// The Icecast servers the stream of this room is pushed to
+    relays: Mutex<Vec<Arc<Relay>>>,
+    /// Records the stream of this room to disk, if recording was started
+    recorder: Mutex<Option<Recorder>>,
+    /// The clips captured from the stream of this room, until they expire
+    clips: Mutex<Vec<Arc<Clip>>>,
 }
 
 #[derive(Default)]
@@ -38,6 +50,10 @@ impl Room {
             context: context.clone(),
             state: Default::default(),
             connections: Default::default(),
+            stream_title: Default::default(),
+            relays: Default::default(),
+            recorder: Default::default(),
+            clips: Default::default(),
             data: data.into(),
         }
     }
@@ -87,6 +103,29 @@ impl Room {
         }
     }
 
+    /// Returns the title of what is currently playing
+    pub fn stream_title(&self) -> Option<String> {
+        self.stream_title.lock().clone()
+    }
+
+    /// Updates the stream title from the new current item, called when the current item changes
+    pub fn update_stream_title(&self, new_item: Option<&LinearQueueItem>) {
+        *self.stream_title.lock() = new_item.map(|i| i.track.display_title());
+    }
+
+    /// Updates the stream title if the given live track is currently playing, called when its title changes
+    pub fn stream_title_changed(&self, track_id: TrackId, title: &str) {
+        if let Some(item) = self.current_item().filter(|i| i.track.id == track_id) {
+            self.update_stream_title(Some(&item));
+        }
+
+        self.context.emit(CollabEvent::TrackStreamTitleUpdate {
+            room_id: self.id(),
+            track_id,
+            title: title.to_string(),
+        });
+    }
+
     /// Gets the associated queue if the room is active
     pub fn queue(&self) -> Result<Arc<LinearQueue>, RoomError> {
         let state = self.state.lock();
@@ -128,11 +167,13 @@ impl Room {
             .ok_or(RoomError::UserNotInRoom)
     }
 
-    /// Creates a stream connection to the room.
+    /// Creates a stream connection to the room, encoded with the given encoder.
     pub fn connect(
         &self,
         user_id: PrimaryKey,
         source: String,
+        encoder: &RegisteredEncoder,
+        options: EncoderOptions,
     ) -> Result<RoomConnectionHandle, RoomError> {
         // Ensure the user is actually in the room before doing anything else
         let _ = self.member_by_user_id(user_id)?;
@@ -149,7 +190,7 @@ impl Room {
         let stream = self
             .context
             .pipeline
-            .consume_player::<WaveEncoder>(player.id);
+            .consume_player_as(player.id, encoder, options);
 
         self.context.emit(CollabEvent::UserConnected {
             room_id: self.id(),
@@ -165,6 +206,234 @@ impl Room {
         ))
     }
 
+    /// Starts pushing the stream of the room to an Icecast server, connected as the given user.
+    pub fn start_relay(
+        &self,
+        user_id: PrimaryKey,
+        target: IcecastTarget,
+        encoder: RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Result<Arc<Relay>, RoomError> {
+        let _ = self.member_by_user_id(user_id)?;
+
+        let relay = Arc::new(Relay::start(
+            &self.context,
+            self.id(),
+            user_id,
+            target,
+            encoder,
+            options,
+        ));
+
+        self.relays.lock().push(relay.clone());
+        Ok(relay)
+    }
+
+    /// Stops and removes a relay by the value of its id, which only its creator or an owner of the room can do
+    pub fn stop_relay(&self, user_id: PrimaryKey, relay_id: u64) -> Result<(), RoomError> {
+        let member = self.member_by_user_id(user_id)?;
+        let mut relays = self.relays.lock();
+
+        let index = relays
+            .iter()
+            .position(|r| r.id.value() == relay_id)
+            .ok_or(RoomError::RelayNotFound(relay_id))?;
+
+        if relays[index].user_id != user_id && !member.owner {
+            return Err(RoomError::RelayNotOwn);
+        }
+
+        relays.remove(index).stop();
+        Ok(())
+    }
+
+    /// Returns the relays of the room
+    pub fn relays(&self) -> Vec<Arc<Relay>> {
+        self.relays.lock().clone()
+    }
+
+    /// Starts recording the stream of the room to disk, unless it's already being recorded.
+    pub fn start_recording(&self) -> Result<(), RoomError> {
+        let mut recorder = self.recorder.lock();
+
+        if recorder
+            .as_ref()
+            .is_some_and(|r| r.state() == RecorderState::Recording)
+        {
+            return Ok(());
+        }
+
+        self.ensure_activation();
+
+        let encoder = self
+            .context
+            .pipeline
+            .encoders()
+            .get(Recorder::FORMAT)
+            .ok_or(RoomError::UnsupportedFormat(Recorder::FORMAT.to_string()))?;
+
+        // The recorder consumes the stream directly, so it doesn't show up as a listener
+        let player = self.player()?;
+        let consumer =
+            self.context
+                .pipeline
+                .consume_player_as(player.id, &encoder, Default::default());
+
+        *recorder = Some(Recorder::start(
+            self.id(),
+            consumer,
+            self.context.recording.clone(),
+            self.current_item(),
+        ));
+
+        Ok(())
+    }
+
+    /// Stops recording the stream of the room, finishing the current file.
+    pub fn stop_recording(&self) {
+        if let Some(recorder) = self.recorder.lock().take() {
+            recorder.stop();
+        }
+    }
+
+    /// Returns the state of the recorder, if recording was started.
+    pub fn recorder_state(&self) -> Option<RecorderState> {
+        self.recorder.lock().as_ref().map(|r| r.state())
+    }
+
+    /// Returns the path of the file the room is being recorded to, if it's being recorded.
+    pub fn recording_file(&self) -> Option<PathBuf> {
+        self.recorder.lock().as_ref().and_then(|r| r.current_file())
+    }
+
+    /// Adds the new current item to the cue sheet of the recording, called when the current item changes.
+    pub fn update_recording_cues(&self, new_item: Option<&LinearQueueItem>, total_position: f32) {
+        if let Some(recorder) = &*self.recorder.lock() {
+            recorder.queue_item_update(new_item.cloned(), total_position);
+        }
+    }
+
+    /// Returns a track in the queue or its history.
+    pub fn track_by_id(&self, track_id: u64) -> Result<Track, RoomError> {
+        let (items, history) = self.queue()?.tracks();
+
+        items
+            .into_iter()
+            .chain(history)
+            .map(|i| i.track)
+            .find(|t| t.id.value() == track_id)
+            .ok_or(RoomError::TrackNotFound(track_id))
+    }
+
+    /// Starts a private preview of a track in the queue or its history, which plays without affecting the room.
+    pub async fn preview(
+        &self,
+        user_id: PrimaryKey,
+        track_id: u64,
+        position: f32,
+        encoder: &RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Result<PreviewHandle, RoomError> {
+        let _ = self.member_by_user_id(user_id)?;
+        let track = self.track_by_id(track_id)?;
+
+        // The preview gets a sink of its own, since the sink of an activated track is cleared around the room's position
+        let loadable = track
+            .loadable()
+            .await
+            .map_err(|e| RoomError::PreviewFailed(e.to_string()))?;
+
+        let pipeline = &self.context.pipeline;
+
+        let sink = pipeline
+            .ingest(loadable)
+            .await
+            .map_err(|e| RoomError::PreviewFailed(e.to_string()))?;
+
+        let preview = pipeline
+            .create_preview(sink, position, encoder, options)
+            .expect("a newly ingested sink is not played yet");
+
+        Ok(PreviewHandle::new(preview, self.id(), user_id))
+    }
+
+    /// Captures the last `seconds` of the stream into a clip, along with what is currently playing.
+    pub async fn create_clip(
+        &self,
+        user_id: PrimaryKey,
+        seconds: f32,
+        encoder: &RegisteredEncoder,
+    ) -> Result<Arc<Clip>, RoomError> {
+        let _ = self.member_by_user_id(user_id)?;
+
+        let player_id = self.player()?.id;
+        let track = self.current_item().map(|i| i.track);
+        let context = self.context.clone();
+        let encoder = encoder.clone();
+
+        // Reading the time-shift buffer and encoding can take a while for long clips
+        let pipeline = self.context.pipeline.clone();
+        let samples = spawn_blocking(move || pipeline.capture_player(player_id, seconds))
+            .await
+            .expect("clip is captured without panicking")
+            .ok_or(RoomError::ClipsUnavailable)?;
+
+        if samples.is_empty() {
+            return Err(RoomError::ClipEmpty);
+        }
+
+        let clip = spawn_blocking(move || {
+            let config = context.pipeline.config();
+            Clip::encode(user_id, track, &samples, &encoder, config, &context.clips)
+        })
+        .await
+        .expect("clip is encoded without panicking")
+        .map_err(RoomError::ClipFailed)?;
+
+        let quota = self.context.clips.quota_in_bytes;
+
+        if clip.size_in_bytes > quota {
+            return Err(RoomError::ClipTooLarge(quota));
+        }
+
+        let clip = Arc::new(clip);
+        let mut clips = self.clips.lock();
+
+        clips.retain(|c| !c.is_expired());
+
+        // The oldest clips make room for the new one
+        let mut total_size: u64 = clips.iter().map(|c| c.size_in_bytes).sum();
+
+        while total_size + clip.size_in_bytes > quota {
+            total_size -= clips.remove(0).size_in_bytes;
+        }
+
+        clips.push(clip.clone());
+
+        Ok(clip)
+    }
+
+    /// Drops the clips that have expired, which deletes their files once they're no longer being downloaded.
+    pub fn remove_expired_clips(&self) {
+        self.clips.lock().retain(|c| !c.is_expired());
+    }
+
+    /// Returns the clips of the room that haven't expired yet
+    pub fn clips(&self) -> Vec<Arc<Clip>> {
+        let mut clips = self.clips.lock();
+
+        clips.retain(|c| !c.is_expired());
+        clips.clone()
+    }
+
+    /// Returns a clip by the value of its id, if it hasn't expired yet
+    pub fn clip_by_id(&self, clip_id: u64) -> Result<Arc<Clip>, RoomError> {
+        self.clips()
+            .into_iter()
+            .find(|c| c.id.value() == clip_id)
+            .ok_or(RoomError::ClipNotFound(clip_id))
+    }
+
     /// Called when a [RoomConnectionHandle] is dropped
     pub fn remove_connection(&self, connection_id: RoomConnectionId) {
         let mut connections = self.connections.lock();
@@ -0,0 +1,325 @@
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File},
+    io::AsyncWriteExt,
+};
+use turntable_impls::{read_tags, LoadableFile, MediaTags};
+
+use crate::{util::random_string, PrimaryKey, UploadData};
+
+use super::RoomError;
+
+/// Where uploaded files are stored, and which files are accepted.
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    /// The directory uploaded files are stored in, named by the hash of their contents
+    pub directory: PathBuf,
+    /// How large a single uploaded file can be
+    pub max_size_in_bytes: u64,
+    /// How many fields a single upload request can have, which bounds the amount of files stored for it
+    pub max_fields: usize,
+    /// The content types that can be uploaded, where `audio/*` matches any audio type
+    pub content_types: Vec<String>,
+}
+
+/// A file that is being uploaded, which is written to a temporary file until it's finished.
+pub struct PendingUpload {
+    pub room_id: PrimaryKey,
+    pub user_id: PrimaryKey,
+    pub file_name: String,
+    pub content_type: String,
+    path: PathBuf,
+    file: File,
+    hasher: Sha256,
+    size: u64,
+    max_size: u64,
+}
+
+/// An upload that was written completely, and has been checked to be playable.
+pub(crate) struct FinishedUpload {
+    pub hash: String,
+    pub size: u64,
+    pub tags: MediaTags,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("media"),
+            max_size_in_bytes: 100 * 1024 * 1024,
+            max_fields: 50,
+            content_types: vec!["audio/*".to_string(), "application/ogg".to_string()],
+        }
+    }
+}
+
+impl UploadConfig {
+    /// Returns true if files of the given content type can be uploaded.
+    pub fn accepts(&self, content_type: &str) -> bool {
+        let content_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        self.content_types
+            .iter()
+            .any(|accepted| match accepted.strip_suffix("/*") {
+                Some(kind) => content_type.split('/').next() == Some(kind),
+                None => accepted.eq_ignore_ascii_case(&content_type),
+            })
+    }
+
+    /// Returns the path a file with the given hash is stored at.
+    pub fn path(&self, hash: &str) -> PathBuf {
+        self.directory.join(hash)
+    }
+}
+
+impl PendingUpload {
+    pub(crate) async fn new(
+        config: &UploadConfig,
+        room_id: PrimaryKey,
+        user_id: PrimaryKey,
+        file_name: String,
+        content_type: String,
+    ) -> Result<Self, RoomError> {
+        if !config.accepts(&content_type) {
+            return Err(RoomError::UnsupportedUploadType(content_type));
+        }
+
+        fs::create_dir_all(&config.directory)
+            .await
+            .map_err(RoomError::UploadFailed)?;
+
+        let path = config
+            .directory
+            .join(format!(".upload-{}", random_string(16)));
+
+        let file = File::create(&path).await.map_err(RoomError::UploadFailed)?;
+
+        Ok(Self {
+            room_id,
+            user_id,
+            file_name,
+            content_type,
+            path,
+            file,
+            hasher: Sha256::new(),
+            size: 0,
+            max_size: config.max_size_in_bytes,
+        })
+    }
+
+    /// Appends a chunk of the file, failing once it grows larger than allowed.
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), RoomError> {
+        self.size += chunk.len() as u64;
+
+        if self.size > self.max_size {
+            return Err(RoomError::UploadTooLarge(self.max_size));
+        }
+
+        self.hasher.update(chunk);
+        self.file
+            .write_all(chunk)
+            .await
+            .map_err(RoomError::UploadFailed)
+    }
+
+    /// Checks that the file can be played, and moves it to its place in the media directory.
+    ///
+    /// If a file with the same contents is already stored, it is kept and the new one is discarded.
+    pub(crate) async fn finish(
+        mut self,
+        config: &UploadConfig,
+    ) -> Result<FinishedUpload, RoomError> {
+        self.file.flush().await.map_err(RoomError::UploadFailed)?;
+
+        let file = File::open(&self.path)
+            .await
+            .map_err(RoomError::UploadFailed)?;
+
+        let tags = read_tags(LoadableFile::new(file))
+            .await
+            .map_err(|_| RoomError::UnsupportedUploadType(self.content_type.clone()))?;
+
+        let hash = format!("{:x}", self.hasher.finalize_reset());
+        let path = config.path(&hash);
+
+        if !fs::try_exists(&path)
+            .await
+            .map_err(RoomError::UploadFailed)?
+        {
+            fs::rename(&self.path, &path)
+                .await
+                .map_err(RoomError::UploadFailed)?;
+        }
+
+        Ok(FinishedUpload {
+            hash,
+            size: self.size,
+            tags,
+        })
+    }
+}
+
+impl Drop for PendingUpload {
+    fn drop(&mut self) {
+        // The temporary file is already gone if it was moved into place
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl UploadData {
+    /// Returns the title of the upload, from its tags or its file name.
+    pub fn display_title(&self) -> String {
+        self.title.clone().unwrap_or_else(|| {
+            self.file_name
+                .rsplit_once('.')
+                .map(|(stem, _)| stem.to_string())
+                .filter(|stem| !stem.is_empty())
+                .unwrap_or_else(|| self.file_name.clone())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use chrono::Utc;
+    use turntable_core::{Config, Encoder};
+    use turntable_impls::WaveEncoder;
+
+    use super::*;
+
+    fn config(directory: &std::path::Path) -> UploadConfig {
+        UploadConfig {
+            directory: directory.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// A short WAV file, which is small enough to be uploaded in a single chunk.
+    fn wave() -> Vec<u8> {
+        let config = Config::default();
+        let mut encoder = WaveEncoder::new(config.clone(), Default::default());
+        encoder.encode(&vec![0.; config.samples_per_sec() / 10]);
+
+        let mut bytes = vec![];
+        encoder.read_to_end(&mut bytes).unwrap();
+
+        let data_size = (bytes.len() - WaveEncoder::HEADER_SIZE) as u32;
+        let header = WaveEncoder::header_for_size(&config, &Default::default(), data_size);
+        bytes[..WaveEncoder::HEADER_SIZE].copy_from_slice(&header);
+
+        bytes
+    }
+
+    fn upload(file_name: &str, title: Option<&str>) -> UploadData {
+        UploadData {
+            id: 1,
+            hash: "hash".to_string(),
+            file_name: file_name.to_string(),
+            content_type: "audio/mpeg".to_string(),
+            size: 0,
+            title: title.map(String::from),
+            artist: None,
+            duration: None,
+            room_id: 1,
+            user_id: 1,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_accepts() {
+        let config = UploadConfig::default();
+
+        assert!(config.accepts("audio/mpeg"));
+        assert!(config.accepts("Audio/FLAC; charset=binary"));
+        assert!(config.accepts("application/ogg"));
+        assert!(!config.accepts("application/octet-stream"));
+        assert!(!config.accepts("video/mp4"));
+        assert!(!config.accepts("audiobook/mp3"));
+
+        let config = UploadConfig {
+            content_types: vec!["audio/wav".to_string()],
+            ..Default::default()
+        };
+
+        assert!(config.accepts("audio/WAV"));
+        assert!(!config.accepts("audio/mpeg"));
+    }
+
+    #[test]
+    fn test_display_title() {
+        assert_eq!(upload("song.mp3", Some("Title")).display_title(), "Title");
+        assert_eq!(upload("my.song.mp3", None).display_title(), "my.song");
+        assert_eq!(upload("song", None).display_title(), "song");
+        assert_eq!(upload(".mp3", None).display_title(), ".mp3");
+    }
+
+    #[tokio::test]
+    async fn test_write_fails_over_the_size_limit() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = UploadConfig {
+            max_size_in_bytes: 10,
+            ..config(directory.path())
+        };
+
+        let mut pending = PendingUpload::new(&config, 1, 1, "song.wav".into(), "audio/wav".into())
+            .await
+            .unwrap();
+
+        pending.write(&[0; 6]).await.unwrap();
+        pending.write(&[0; 4]).await.unwrap();
+
+        assert!(matches!(
+            pending.write(&[0]).await,
+            Err(RoomError::UploadTooLarge(10))
+        ));
+
+        // The temporary file goes away with the upload
+        drop(pending);
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_finish_stores_identical_files_once() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = config(directory.path());
+        let bytes = wave();
+
+        let mut hashes = vec![];
+
+        for file_name in ["first.wav", "second.wav"] {
+            let mut pending =
+                PendingUpload::new(&config, 1, 1, file_name.into(), "audio/wav".into())
+                    .await
+                    .unwrap();
+
+            pending.write(&bytes).await.unwrap();
+
+            let finished = pending.finish(&config).await.unwrap();
+            assert_eq!(finished.size, bytes.len() as u64);
+
+            hashes.push(finished.hash);
+        }
+
+        assert_eq!(hashes[0], hashes[1]);
+        assert_eq!(hashes[0], format!("{:x}", Sha256::digest(&bytes)));
+
+        // Only the stored file is left, without any temporary ones
+        let files: Vec<_> = std::fs::read_dir(directory.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+
+        assert_eq!(files, vec![config.path(&hashes[0])]);
+        assert_eq!(std::fs::read(&files[0]).unwrap(), bytes);
+    }
+}
@@ -2,8 +2,9 @@ use async_trait::async_trait;
 use parking_lot::Mutex;
 use std::{error::Error, sync::Arc};
 use turntable_core::{BoxedLoadable, Id, QueueItem, SinkId};
+use turntable_impls::MediaArtwork;
 
-use crate::{input::Input, Metadata};
+use crate::{input::Input, Metadata, PrimaryKey, StreamTitleListener};
 
 pub type TrackId = Id<Track>;
 
@@ -51,6 +52,47 @@ impl QueueItem for Track {
     }
 }
 
+impl Track {
+    /// Returns the title of what a live stream is currently playing, if the track is one and it's known.
+    pub fn stream_title(&self) -> Option<String> {
+        self.input.stream_title()
+    }
+
+    /// Sets the listener that is called whenever the title of a live stream changes.
+    pub fn on_stream_title(&self, listener: StreamTitleListener) {
+        self.input.on_stream_title(listener)
+    }
+
+    /// Returns the hash of the uploaded file the track plays, if it's an upload.
+    pub fn upload_hash(&self) -> Option<&str> {
+        self.input.upload_hash()
+    }
+
+    /// Returns the cover art that is embedded in the file of the track, if it has any.
+    pub fn artwork(&self) -> Option<&MediaArtwork> {
+        self.input.artwork()
+    }
+
+    /// Points the artwork of the track at the room it's queued in, if the cover art is embedded in its file.
+    ///
+    /// Only members of the room can fetch it from there.
+    pub fn link_artwork(&mut self, room_id: PrimaryKey) {
+        if self.metadata.artwork.is_none() && self.artwork().is_some() {
+            self.metadata.artwork = Some(format!(
+                "/v1/rooms/{}/queue/{}/artwork",
+                room_id,
+                self.id.value()
+            ));
+        }
+    }
+
+    /// Returns the title as players display it, which is what a live stream is currently playing if known.
+    pub fn display_title(&self) -> String {
+        self.stream_title()
+            .unwrap_or_else(|| self.metadata.display_title())
+    }
+}
+
 impl From<Input> for Track {
     fn from(input: Input) -> Self {
         Self {
@@ -1,4 +1,5 @@
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{thread, time::Duration};
 
 pub fn random_string(length: usize) -> String {
     let mut rng = thread_rng();
@@ -8,3 +9,17 @@ pub fn random_string(length: usize) -> String {
         .take(length)
         .collect()
 }
+
+/// Runs a task on a thread of its own every interval, until it returns false.
+pub fn spawn_periodic<F>(interval: Duration, mut task: F)
+where
+    F: FnMut() -> bool + Send + 'static,
+{
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+
+        if !task() {
+            break;
+        }
+    });
+}
@@ -21,12 +21,35 @@ pub struct Config {
     /// Lower values increase chance of buffer underruns,
     /// whilst higher values increase latency.
     pub stream_preload_cache_size_in_seconds: f32,
+    /// How many seconds of a stream are kept, so listeners can start behind live.
+    ///
+    /// The window is kept in a temporary file, which is only created once a listener or a capture needs it.
+    /// A value of 0 disables time-shifting, which is the default.
+    pub stream_timeshift_window_in_seconds: f32,
+    /// How much faster time-shifted listeners that want to catch up to live play.
+    pub stream_catch_up_speed: f32,
+    /// An estimate of how many seconds streamed audio takes to reach listeners and be played.
+    ///
+    /// This only affects the heard positions that are reported, not the stream itself.
+    pub stream_network_latency_in_seconds: f32,
     /// How many seconds of audio can exist between the currently playing offset of a sink.
     ///
     /// Higher values means more memory usage but more lenient seeking, lower values
     /// mean less memory usage but a higher likelihood of buffering when seeking too far from the
     /// playback offset.
     pub sink_preload_window_in_seconds: f32,
+    /// How many seconds of audio each HLS segment contains.
+    ///
+    /// Shorter segments lower the latency of HLS listeners, at the cost of more requests.
+    pub hls_segment_duration_in_seconds: f32,
+    /// How many segments are listed in an HLS media playlist.
+    pub hls_playlist_window: usize,
+    /// How many times in a row a sink that fails while loading is re-created from its source before it's skipped.
+    ///
+    /// A value of 0 skips failed sinks right away.
+    pub sink_recovery_attempts: usize,
+    /// How many seconds to wait before re-creating a failed sink, multiplied by the attempt.
+    pub sink_recovery_delay_in_seconds: f32,
 }
 
 impl Config {
@@ -62,6 +85,12 @@ impl Config {
         (self.stream_preload_cache_size_in_seconds * self.samples_per_sec() as f32) as usize
     }
 
+    /// How many samples are kept in a stream's time-shift window, in whole frames
+    pub fn stream_timeshift_window_size(&self) -> usize {
+        let size = self.seconds_to_samples(self.stream_timeshift_window_in_seconds);
+        size / self.channel_count * self.channel_count
+    }
+
     /// How many samples between the playback offset can be stored in a sink
     pub fn sink_preload_window_size(&self) -> usize {
         (self.sink_preload_window_in_seconds * self.samples_per_sec() as f32) as usize
@@ -98,8 +127,22 @@ impl Default for Config {
             buffer_size_in_seconds: 0.1,
             // Half a second of stream latency
             stream_preload_cache_size_in_seconds: 0.5,
+            // Time-shifting writes the stream to disk, so it has to be enabled explicitly
+            stream_timeshift_window_in_seconds: 0.,
+            // Fast enough to catch up in reasonable time, slow enough to not be too noticeable
+            stream_catch_up_speed: 1.05,
+            // Players tend to buffer a little before they start playing
+            stream_network_latency_in_seconds: 0.2,
             // 5 minutes of stored audio is more than enough
             sink_preload_window_in_seconds: 60. * 5.,
+            // Short enough for a reasonable delay, long enough to not flood the server with requests
+            hls_segment_duration_in_seconds: 4.,
+            // 20 seconds of audio lets players recover from a few failed requests
+            hls_playlist_window: 5,
+            // Enough to ride out a brief outage without stalling for too long
+            sink_recovery_attempts: 3,
+            // Gives the source some time to come back
+            sink_recovery_delay_in_seconds: 1.,
         }
     }
 }
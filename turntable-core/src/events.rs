@@ -1,6 +1,6 @@
 use crossbeam::channel::{Receiver, Sender};
 
-use crate::{PlayerId, PlayerState, SinkId, SinkLoadState};
+use crate::{HeardPosition, PlayerId, PlayerState, SinkId, SinkLoadState};
 
 pub type EventSender = Sender<PipelineEvent>;
 pub type EventReceiver = Receiver<PipelineEvent>;
@@ -25,12 +25,22 @@ pub enum PipelineEvent {
     PlayerTimeUpdate {
         player_id: PlayerId,
         /// The current position of the player, in seconds.
+        ///
+        /// This is the position of the produced audio, which listeners hear later.
         position: f32,
         /// The total position of the player, in seconds.
         total_position: f32,
+        /// The estimated positions listeners hear, for every output format.
+        heard_positions: Vec<HeardPosition>,
     },
     /// A player advanced to the next queue item.
-    PlayerAdvanced { player_id: PlayerId },
+    PlayerAdvanced {
+        player_id: PlayerId,
+        /// The total position the next item starts at, in seconds.
+        ///
+        /// Listeners hear the change once their heard total position reaches this.
+        total_position: f32,
+    },
     /// A queue item has been ingested
     QueueItemActivated {
         /// The id of the player the queue item's queue belongs to.
@@ -49,6 +59,21 @@ pub enum PipelineEvent {
         /// The error that happened while activating the queue item.
         error: String,
     },
+    /// A queue item's sink failed while loading, and its source is being re-created.
+    QueueItemRecoveryAttempt {
+        /// The id of the player the queue item's queue belongs to.
+        player_id: PlayerId,
+        /// The custom identifier of the queue item.
+        item_id: String,
+        /// The id of the sink that is kept playing once recovered.
+        sink_id: SinkId,
+        /// Which attempt this is, starting at 1.
+        attempt: usize,
+        /// The error that made the sink fail.
+        error: String,
+    },
+    /// The time-shift buffer of a player's stream failed, so its delayed listeners continue at live.
+    TimeshiftError { player_id: PlayerId, error: String },
 }
 
 /// Describes an action to be performed on the pipeline.
@@ -63,10 +88,20 @@ pub enum PipelineAction {
     PlayPlayer { player_id: PlayerId },
     /// The player of the given id should pause.
     PausePlayer { player_id: PlayerId },
+    /// The player of the given id should skip to the next queue item.
+    SkipPlayer { player_id: PlayerId },
     /// The player of the given id should seek to the given position.
     SeekPlayer {
         player_id: PlayerId,
         /// The position to seek to, in seconds.
         position: f32,
     },
+    /// The sink of the given id failed while loading, and should be re-created from its source.
+    RecoverSink {
+        sink_id: SinkId,
+        /// Which attempt this is, starting at 1.
+        attempt: usize,
+        /// The error that made the sink fail.
+        error: String,
+    },
 }
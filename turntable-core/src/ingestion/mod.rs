@@ -26,6 +26,13 @@ where
     where
         L: IntoLoadable + Send + Sync;
 
+    /// Replaces the source of a sink that failed while loading, and continues loading into the same sink.
+    ///
+    /// The new source is probed again, and loading resumes from where the old source stopped.
+    async fn recover<L>(&self, sink_id: SinkId, input: L) -> Result<(), Box<dyn Error>>
+    where
+        L: IntoLoadable + Send + Sync;
+
     /// Requests the pipeline to start loading samples into a sink.
     ///
     /// * `sink_id` - The id of the sink to load into.
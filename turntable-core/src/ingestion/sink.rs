@@ -1,7 +1,8 @@
 use std::{sync::Arc, time::Instant};
 
 use crate::{
-    BufferRead, BufferVoidDistance, Id, MultiRangeBuffer, PipelineContext, PipelineEvent, Sample,
+    BufferRead, BufferVoidDistance, Id, MultiRangeBuffer, PipelineAction, PipelineContext,
+    PipelineEvent, Sample,
 };
 use crossbeam::atomic::AtomicCell;
 use parking_lot::Mutex;
@@ -18,6 +19,8 @@ pub struct Sink {
     expected_length: Option<usize>,
     /// The current load state of the sink.
     load_state: Mutex<SinkLoadState>,
+    /// How many times in a row the sink failed and was recovered.
+    recovery_attempts: AtomicCell<usize>,
 
     /// Whether a guard has been created and exists somewhere.
     has_guard: AtomicCell<bool>,
@@ -44,6 +47,10 @@ pub enum SinkLoadState {
     ///
     /// If the sink is in this state, it will be skipped by the player when it encounters a void.
     Error(String),
+    /// Loading failed and the source of the sink is being re-created.
+    ///
+    /// If the sink is in this state, the player waits for it instead of skipping it.
+    Recovering(String),
 }
 
 impl Sink {
@@ -57,6 +64,7 @@ impl Sink {
             expected_length,
             context: context.clone(),
             load_state: Default::default(),
+            recovery_attempts: Default::default(),
             has_guard: Default::default(),
             has_write_ref: Default::default(),
             duration_since_interaction: Instant::now().into(),
@@ -115,6 +123,34 @@ impl Sink {
         self.set_load_state(SinkLoadState::Error(error));
     }
 
+    /// Reports that loading into the sink failed.
+    ///
+    /// The source is re-created if there are recovery attempts left, otherwise the sink is set to the error state.
+    pub fn fail(&self, error: String) {
+        let attempt = self.recovery_attempts.load() + 1;
+
+        if attempt > self.context.config.sink_recovery_attempts {
+            self.error(error);
+            return;
+        }
+
+        self.recovery_attempts.store(attempt);
+        self.set_load_state(SinkLoadState::Recovering(error.clone()));
+
+        self.context.dispatch(PipelineAction::RecoverSink {
+            sink_id: self.id,
+            attempt,
+            error,
+        });
+    }
+
+    /// Marks the sink as loadable again after its source was re-created.
+    pub fn recovered(&self) {
+        if matches!(self.load_state(), SinkLoadState::Recovering(_)) {
+            self.set_load_state(SinkLoadState::Idle);
+        }
+    }
+
     pub fn load_state(&self) -> SinkLoadState {
         self.load_state.lock().clone()
     }
@@ -149,6 +185,16 @@ impl Sink {
         )
     }
 
+    /// Returns true if the source of the sink is being re-created.
+    fn is_recovering(&self) -> bool {
+        matches!(self.load_state(), SinkLoadState::Recovering(_))
+    }
+
+    /// Returns true if a timeline is playing the sink.
+    pub fn is_guarded(&self) -> bool {
+        self.has_guard.load()
+    }
+
     /// Returns true if the sink can be cleared from memory.
     pub fn is_clearable(&self) -> bool {
         let has_read_ref = self.has_guard.load();
@@ -192,6 +238,11 @@ impl Sink {
 
     /// Writes samples to the sink at the given offset.
     fn internal_write(&self, offset: usize, samples: &[Sample]) {
+        // Loading works again, so later failures start over with their attempts
+        if !samples.is_empty() {
+            self.recovery_attempts.store(0);
+        }
+
         self.buffer.write(offset, samples);
     }
 }
@@ -234,6 +285,11 @@ impl SinkGuard {
         self.get_sink().can_load_more()
     }
 
+    /// Returns true if the source of the sink is being re-created.
+    pub fn is_recovering(&self) -> bool {
+        self.get_sink().is_recovering()
+    }
+
     pub fn clear_outside(&self, offset: usize, window: usize, chunk_size: usize) {
         self.get_sink().clear_outside(offset, window, chunk_size);
     }
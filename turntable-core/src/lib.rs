@@ -5,17 +5,23 @@ use std::{error::Error, sync::Arc, thread};
 mod config;
 mod events;
 mod ingestion;
+mod metrics;
 mod output;
 mod playback;
+mod quantisation;
 mod queuing;
+mod resampling;
 mod util;
 
 pub use config::*;
 pub use events::*;
 pub use ingestion::*;
+pub use metrics::*;
 pub use output::*;
 pub use playback::*;
+pub use quantisation::*;
 pub use queuing::*;
+pub use resampling::*;
 pub use util::*;
 
 // Reduces verbosity
@@ -24,6 +30,7 @@ pub type ArcedStore<Id, T> = Store<Id, Arc<T>>;
 
 /// The turntable pipeline, facilitating ingestion, playback, and output.
 pub struct Pipeline<I> {
+    config: Config,
     ingestion: Arc<I>,
     playback: Playback,
     output: Arc<Output>,
@@ -72,6 +79,7 @@ where
         spawn_action_handler_thread(&context, queuing.clone(), action_receiver);
 
         Pipeline {
+            config,
             output,
             queuing,
             playback,
@@ -80,11 +88,32 @@ where
         }
     }
 
+    /// Returns the config the pipeline was created with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     /// Creates a new player and returns its id.
     pub fn create_player(&self) -> PlayerContext {
         self.playback.create_player()
     }
 
+    /// Creates a private player that plays a single sink from the given position in seconds, independent of any queue.
+    ///
+    /// Returns `None` if the sink is already being played. The player is removed when the preview is dropped.
+    pub fn create_preview(
+        &self,
+        sink: Arc<Sink>,
+        position: f32,
+        encoder: &RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Option<Preview> {
+        let offset = self.config.seconds_to_samples(position);
+        let offset = offset - offset % self.config.channel_count;
+
+        self.playback.create_preview(sink, offset, encoder, options)
+    }
+
     /// Creates a new queue for a player and returns it.
     pub fn create_queue<T, F>(&self, player_id: PlayerId, creator: F) -> Arc<T>
     where
@@ -103,11 +132,31 @@ where
     }
 
     /// Creates a consumer for a player.
-    pub fn consume_player<E>(&self, player_id: PlayerId) -> Consumer
+    pub fn consume_player<E>(&self, player_id: PlayerId, options: EncoderOptions) -> Consumer
     where
         E: Encoder,
     {
-        self.output.consume_player::<E>(player_id)
+        self.output.consume_player::<E>(player_id, options)
+    }
+
+    /// Creates a consumer for a player, with an encoder from the registry.
+    pub fn consume_player_as(
+        &self,
+        player_id: PlayerId,
+        encoder: &RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Consumer {
+        self.output.consume_player_as(player_id, encoder, options)
+    }
+
+    /// Returns the last `seconds` of what a player streamed, if time-shifting is enabled.
+    pub fn capture_player(&self, player_id: PlayerId, seconds: f32) -> Option<Vec<Sample>> {
+        self.output.capture_player(player_id, seconds)
+    }
+
+    /// Returns the registry of encoders that consumers can be created with.
+    pub fn encoders(&self) -> &EncoderRegistry {
+        self.output.encoders()
     }
 
     /// Receive events from the pipeline.
@@ -158,22 +207,37 @@ fn spawn_action_handler_thread(
             PipelineAction::NotifyQueueUpdate { player_id } => {
                 queueing.notify_queue_update(player_id);
             }
+            // Preview players can be removed before their actions are handled, so those are ignored
             PipelineAction::PlayPlayer { player_id } => {
-                let player = players.get(&player_id).expect("player exists");
-                player.play();
+                if let Some(player) = players.get(&player_id) {
+                    player.play();
+                }
             }
             PipelineAction::PausePlayer { player_id } => {
-                let player = players.get(&player_id).expect("player exists");
-                player.pause();
+                if let Some(player) = players.get(&player_id) {
+                    player.pause();
+                }
+            }
+            PipelineAction::SkipPlayer { player_id } => {
+                if let Some(player) = players.get(&player_id) {
+                    player.skip();
+                }
             }
             PipelineAction::SeekPlayer {
                 player_id,
                 position,
             } => {
-                let player = players.get(&player_id).expect("player exists");
-                let position_in_samples = config.seconds_to_samples(position);
-
-                player.seek(position_in_samples);
+                if let Some(player) = players.get(&player_id) {
+                    let position_in_samples = config.seconds_to_samples(position);
+                    player.seek(position_in_samples);
+                }
+            }
+            PipelineAction::RecoverSink {
+                sink_id,
+                attempt,
+                error,
+            } => {
+                queueing.recover_sink(sink_id, attempt, error);
             }
         }
     };
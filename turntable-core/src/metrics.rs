@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Sample;
+
+static METRICS: Metrics = Metrics::new();
+
+/// Counters describing the audio quality of the pipeline, shared across the whole process.
+///
+/// Samples are counted once per stream as they're played into it, so the numbers don't grow with the amount of listeners.
+#[derive(Debug)]
+pub struct Metrics {
+    streamed_samples: AtomicU64,
+    clipped_samples: AtomicU64,
+}
+
+/// A copy of the [Metrics] at a point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The amount of samples played into streams.
+    pub streamed_samples: u64,
+    /// The amount of streamed samples beyond full scale, which are clamped when converted to integer PCM.
+    pub clipped_samples: u64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            streamed_samples: AtomicU64::new(0),
+            clipped_samples: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the metrics of this process.
+    pub fn global() -> &'static Metrics {
+        &METRICS
+    }
+
+    /// Counts samples that are played into a stream, and how many of them are beyond full scale.
+    pub fn count_streamed(&self, samples: &[Sample]) {
+        let clipped = samples.iter().filter(|s| s.abs() > 1.).count() as u64;
+
+        self.streamed_samples
+            .fetch_add(samples.len() as u64, Ordering::Relaxed);
+
+        if clipped > 0 {
+            self.clipped_samples.fetch_add(clipped, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            streamed_samples: self.streamed_samples.load(Ordering::Relaxed),
+            clipped_samples: self.clipped_samples.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_streamed() {
+        let metrics = Metrics::new();
+
+        metrics.count_streamed(&[1.5, -1.5, 1., -1., 0.5]);
+        metrics.count_streamed(&[0.; 3]);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.streamed_samples, 8);
+        assert_eq!(
+            snapshot.clipped_samples, 2,
+            "full scale itself doesn't clip"
+        );
+    }
+}
@@ -1,8 +1,14 @@
-use crossbeam::channel::{unbounded, Receiver, Sender};
+use crossbeam::{
+    atomic::AtomicCell,
+    channel::{unbounded, Receiver, Sender},
+};
 use parking_lot::Mutex;
 use std::{
     io::Read,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
     time::Duration,
 };
 
@@ -21,6 +27,12 @@ pub struct Consumer {
     encoder: Arc<Mutex<Box<dyn Encoder>>>,
     /// Receives a unit type when new samples are available
     receiver: Receiver<()>,
+    /// The amount of samples that have been given to the encoder
+    encoded_samples: Arc<AtomicU64>,
+    /// The total position of the player at the end of the samples given to the encoder, in seconds
+    total_position: Arc<AtomicCell<f32>>,
+    /// The config of the samples given to the encoder
+    config: Config,
 }
 
 /// The producer part of a consumer
@@ -28,16 +40,19 @@ pub struct Producer {
     encoder: Arc<Mutex<Box<dyn Encoder>>>,
     /// Used to notify the consumer of new samples
     sender: Sender<()>,
+    encoded_samples: Arc<AtomicU64>,
+    total_position: Arc<AtomicCell<f32>>,
 }
 
 impl Consumer {
-    pub fn new<E>(config: Config, stream: Weak<Stream>) -> (Self, Producer)
-    where
-        E: Encoder,
-    {
-        let encoder = E::new(config);
-        let boxed_encoder: Box<dyn Encoder> = Box::new(encoder);
-        let arced_encoder = Arc::new(Mutex::new(boxed_encoder));
+    pub fn new(
+        encoder: Box<dyn Encoder>,
+        config: Config,
+        stream: Weak<Stream>,
+    ) -> (Self, Producer) {
+        let arced_encoder = Arc::new(Mutex::new(encoder));
+        let encoded_samples = Arc::new(AtomicU64::new(0));
+        let total_position = Arc::new(AtomicCell::new(0.));
 
         let (sender, receiver) = unbounded();
 
@@ -46,11 +61,16 @@ impl Consumer {
             id: ConsumerId::new(),
             encoder: arced_encoder.clone(),
             receiver,
+            encoded_samples: encoded_samples.clone(),
+            total_position: total_position.clone(),
+            config,
         };
 
         let producer = Producer {
             encoder: arced_encoder,
             sender,
+            encoded_samples,
+            total_position,
         };
 
         (me, producer)
@@ -61,6 +81,34 @@ impl Consumer {
         self.encoder.lock().content_type()
     }
 
+    /// Returns the config of the samples given to the encoder, which is the pipeline config with the consumer format applied.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the amount of samples that have been given to the encoder so far.
+    pub fn encoded_samples(&self) -> u64 {
+        self.encoded_samples.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total position of the player at the end of the samples given to the encoder, in seconds.
+    ///
+    /// Once everything available is read, this is the position of the end of the read data,
+    /// apart from what the encoder holds back to fill a frame.
+    pub fn total_position(&self) -> f32 {
+        self.total_position.load()
+    }
+
+    /// Reads whatever encoded data is available, without waiting for more.
+    pub fn read_available(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.encoder.lock().read(buf)
+    }
+
+    /// Waits until new samples are encoded, returning false if the timeout elapsed first.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        self.receiver.recv_timeout(timeout).is_ok()
+    }
+
     /// Reads the encoded data from the consumer.
     /// Note: This will block if the requested amount is not available yet
     pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -97,8 +145,17 @@ impl Consumer {
 
 impl Producer {
     /// Push the provided samples to the consumer and encode them.
-    pub fn push(&self, samples: &[Sample]) {
-        self.encoder.lock().encode(samples);
+    ///
+    /// * `total_position` - The total position of the player at the end of the samples, in seconds.
+    pub fn push(&self, samples: &[Sample], total_position: f32) {
+        let mut encoder = self.encoder.lock();
+
+        encoder.encode(samples);
+        self.encoded_samples
+            .fetch_add(samples.len() as u64, Ordering::Relaxed);
+        self.total_position.store(total_position);
+
+        drop(encoder);
 
         // Notify the consumer of new samples so we can avoid busywaiting
         self.sender.send(()).expect("notifies consumer");
@@ -0,0 +1,160 @@
+use super::EncoderOptions;
+use crate::{Config, DynamicResampler, Sample};
+
+/// The sample rates samples can be converted to.
+/// Resampling between rates with unusual ratios is very expensive, or not possible at all.
+pub const SUPPORTED_SAMPLE_RATES: &[usize] = &[
+    8000, 11025, 16000, 22050, 24000, 32000, 44100, 48000, 88200, 96000, 176400, 192000,
+];
+
+/// The sample rate and channel count of the samples handed to an encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConversionFormat {
+    pub sample_rate: usize,
+    pub channel_count: usize,
+}
+
+/// Converts samples from the pipeline's format into a [ConversionFormat].
+pub struct Converter {
+    source_channel_count: usize,
+    format: ConversionFormat,
+    /// Only present if the sample rate differs from the pipeline.
+    resampler: Option<DynamicResampler>,
+}
+
+impl ConversionFormat {
+    /// Returns the format requested in the options, falling back to the pipeline's format.
+    ///
+    /// Sample rates that aren't in [SUPPORTED_SAMPLE_RATES] are ignored.
+    pub fn from_options(config: &Config, options: &EncoderOptions) -> Self {
+        Self {
+            sample_rate: options
+                .sample_rate
+                .filter(|rate| SUPPORTED_SAMPLE_RATES.contains(rate))
+                .unwrap_or(config.sample_rate),
+            channel_count: options.channel_count.unwrap_or(config.channel_count),
+        }
+    }
+
+    /// Returns a copy of the config with this format applied.
+    pub fn apply(&self, config: &Config) -> Config {
+        Config {
+            sample_rate: self.sample_rate,
+            channel_count: self.channel_count,
+            ..config.clone()
+        }
+    }
+}
+
+impl Converter {
+    pub fn new(config: &Config, format: ConversionFormat) -> Self {
+        let resampler = (format.sample_rate != config.sample_rate).then(|| {
+            DynamicResampler::with_rates(
+                config.sample_rate,
+                format.sample_rate,
+                format.channel_count,
+            )
+            .expect("resampler is created for a valid sample rate")
+        });
+
+        Self {
+            source_channel_count: config.channel_count,
+            format,
+            resampler,
+        }
+    }
+
+    /// Converts samples in the pipeline's format into the target format.
+    ///
+    /// Note: Resampling works in chunks, so the output can lag behind the input slightly.
+    pub fn convert(&mut self, samples: &[Sample]) -> Vec<Sample> {
+        let remixed = remix_channels(
+            samples,
+            self.source_channel_count,
+            self.format.channel_count,
+        );
+
+        match &mut self.resampler {
+            Some(resampler) => resampler.push(&remixed),
+            None => remixed,
+        }
+    }
+}
+
+/// Converts interleaved samples between channel counts.
+///
+/// Mono is downmixed by averaging all channels, every other conversion maps channels in order,
+/// repeating the source channels if there are fewer of them.
+pub fn remix_channels(samples: &[Sample], from: usize, to: usize) -> Vec<Sample> {
+    if from == to {
+        return samples.to_vec();
+    }
+
+    samples
+        .chunks_exact(from)
+        .flat_map(|frame| {
+            (0..to).map(move |channel| {
+                if to == 1 {
+                    frame.iter().sum::<Sample>() / from as Sample
+                } else {
+                    frame[channel % from]
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remix_channels() {
+        let stereo = [0.5, 0.1, -0.2, 0.4];
+
+        assert_eq!(remix_channels(&stereo, 2, 2), stereo.to_vec());
+        assert_eq!(remix_channels(&stereo, 2, 1), vec![0.3, 0.1]);
+        assert_eq!(remix_channels(&[0.5, 0.1], 1, 2), vec![0.5, 0.5, 0.1, 0.1]);
+    }
+
+    #[test]
+    fn test_unsupported_sample_rate_is_ignored() {
+        let config = Config::default();
+        let options = |sample_rate| EncoderOptions {
+            sample_rate: Some(sample_rate),
+            ..Default::default()
+        };
+
+        let supported = ConversionFormat::from_options(&config, &options(22050));
+        assert_eq!(supported.sample_rate, 22050);
+
+        let unsupported = ConversionFormat::from_options(&config, &options(44101));
+        assert_eq!(unsupported.sample_rate, config.sample_rate);
+    }
+
+    #[test]
+    fn test_converter_resamples() {
+        let config = Config::default();
+        let format = ConversionFormat {
+            sample_rate: config.sample_rate / 2,
+            channel_count: 1,
+        };
+
+        let mut converter = Converter::new(&config, format);
+
+        // Two seconds of stereo silence, pushed in small uneven pieces
+        let samples = vec![0.; config.sample_rate * 2 * config.channel_count];
+        let converted: usize = samples
+            .chunks(1000)
+            .map(|chunk| converter.convert(chunk).len())
+            .sum();
+
+        // Everything but the last incomplete chunk and the resampler delay comes out
+        let expected = format.sample_rate * 2;
+        assert!(converted <= expected, "no more than expected is produced");
+        assert!(
+            converted >= expected - DynamicResampler::CHUNK_SIZE * 2,
+            "close to everything is produced, got {converted} of {expected}"
+        );
+    }
+}
@@ -6,7 +6,7 @@ pub trait Encoder: Read
 where
     Self: 'static + Send + Sync,
 {
-    fn new(config: Config) -> Self
+    fn new(config: Config, options: EncoderOptions) -> Self
     where
         Self: Sized;
 
@@ -17,4 +17,74 @@ where
 
     /// Returns the content type of the encoded data.
     fn content_type(&self) -> String;
+
+    /// Returns how many seconds of audio the encoder can hold back before it's readable, such as to fill a frame.
+    fn latency(_config: &Config) -> f32
+    where
+        Self: Sized,
+    {
+        0.
+    }
+}
+
+/// Options that tune an [Encoder] for a single consumer.
+///
+/// Encoders ignore the options they do not support, and fall back to their own defaults for anything left unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncoderOptions {
+    /// The target bitrate in bits per second.
+    pub bitrate: Option<u32>,
+    /// The computational complexity of the encoder, where higher is slower but better quality.
+    pub complexity: Option<u8>,
+    /// Whether the encoder should use a variable bitrate, with `bitrate` as its target.
+    pub variable_bitrate: Option<bool>,
+    /// The sample rate the consumer wants, if it differs from the pipeline.
+    pub sample_rate: Option<usize>,
+    /// The channel count the consumer wants, if it differs from the pipeline.
+    pub channel_count: Option<usize>,
+    /// How PCM encoders store samples.
+    pub sample_format: Option<SampleFormat>,
+    /// Whether integer PCM encoders should shape the dither noise towards higher frequencies.
+    pub noise_shaping: Option<bool>,
+    /// How many seconds behind live the consumer starts, as far as the time-shift window of the stream allows.
+    pub delay: Option<u32>,
+    /// Whether a delayed consumer should play slightly faster until it's back at live.
+    pub catch_up: Option<bool>,
+}
+
+/// How samples are stored by PCM encoders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SampleFormat {
+    #[default]
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl SampleFormat {
+    /// The amount of bits a single sample takes up.
+    pub fn bit_depth(&self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Float32 => 32,
+        }
+    }
+}
+
+impl EncoderOptions {
+    /// Returns a new set of options, where the values set in `other` take precedence.
+    pub fn merge(&self, other: &EncoderOptions) -> EncoderOptions {
+        EncoderOptions {
+            bitrate: other.bitrate.or(self.bitrate),
+            complexity: other.complexity.or(self.complexity),
+            variable_bitrate: other.variable_bitrate.or(self.variable_bitrate),
+            sample_rate: other.sample_rate.or(self.sample_rate),
+            channel_count: other.channel_count.or(self.channel_count),
+            sample_format: other.sample_format.or(self.sample_format),
+            noise_shaping: other.noise_shaping.or(self.noise_shaping),
+            delay: other.delay.or(self.delay),
+            catch_up: other.catch_up.or(self.catch_up),
+        }
+    }
 }
@@ -0,0 +1,396 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    mem,
+    sync::{Arc, Weak},
+    thread,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+
+use super::{Consumer, RegisteredEncoder};
+use crate::Config;
+
+/// Cuts the encoded output of a [Consumer] into numbered segments, and keeps a rolling media playlist of them for HTTP Live Streaming.
+///
+/// Segments are served as packed audio, so only formats that can be cut at any frame are supported.
+pub struct HlsPackager {
+    /// The file extension of the segments, such as `mp3`.
+    extension: String,
+    content_type: String,
+    target_duration: u32,
+    state: Arc<Mutex<HlsState>>,
+}
+
+/// A single segment of an HLS stream.
+#[derive(Debug, Clone)]
+pub struct HlsSegment {
+    pub sequence: u64,
+    pub duration_in_seconds: f32,
+    /// The encoded data, prefixed with an ID3 timestamp.
+    pub bytes: Arc<Vec<u8>>,
+}
+
+struct HlsState {
+    segments: VecDeque<HlsSegment>,
+    next_sequence: u64,
+    window: usize,
+}
+
+impl HlsPackager {
+    /// The content types of the packed audio formats that can be segmented.
+    pub const CONTENT_TYPES: [&'static str; 2] = ["audio/mpeg", "audio/aac"];
+
+    /// The content type of an HLS media playlist.
+    pub const PLAYLIST_CONTENT_TYPE: &'static str = "application/vnd.apple.mpegurl";
+
+    /// How many segments that have left the playlist are kept, for players that are still fetching them.
+    const RETAINED_SEGMENTS: usize = 2;
+
+    /// Returns true if the encoder produces a format that can be segmented.
+    ///
+    /// Neither `wav` nor `pcm` can be, so HLS needs an MP3 or AAC encoder to be registered.
+    pub fn supports(encoder: &RegisteredEncoder) -> bool {
+        Self::CONTENT_TYPES
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&encoder.content_type))
+    }
+
+    /// Starts packaging the output of the consumer, with segments named after the given extension.
+    ///
+    /// Packaging stops when the packager is dropped.
+    pub fn new(consumer: Arc<Consumer>, extension: &str) -> Self {
+        let config = consumer.config();
+
+        let state = Arc::new(Mutex::new(HlsState {
+            segments: Default::default(),
+            next_sequence: 0,
+            window: config.hls_playlist_window.max(1),
+        }));
+
+        let packager = Self {
+            extension: extension.to_string(),
+            content_type: consumer.content_type(),
+            target_duration: config.hls_segment_duration_in_seconds.ceil() as u32,
+            state: state.clone(),
+        };
+
+        spawn_packaging_thread(consumer, Arc::downgrade(&state));
+        packager
+    }
+
+    /// Returns the content type of the segments.
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// Returns the file extension of the segments.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Returns the media playlist, listing the latest segments as `{sequence}.{extension}`.
+    pub fn media_playlist(&self) -> String {
+        let state = self.state.lock();
+        let listed = state.listed();
+
+        // Segment durations are approximate, so make sure each of them fits the target duration when rounded
+        let target_duration = listed
+            .iter()
+            .map(|s| s.duration_in_seconds.round() as u32)
+            .fold(self.target_duration, u32::max);
+
+        let media_sequence = listed.first().map_or(state.next_sequence, |s| s.sequence);
+
+        let mut playlist = String::new();
+
+        writeln!(playlist, "#EXTM3U").unwrap();
+        writeln!(playlist, "#EXT-X-VERSION:3").unwrap();
+        writeln!(playlist, "#EXT-X-TARGETDURATION:{target_duration}").unwrap();
+        writeln!(playlist, "#EXT-X-MEDIA-SEQUENCE:{media_sequence}").unwrap();
+
+        for segment in listed {
+            writeln!(playlist, "#EXTINF:{:.3},", segment.duration_in_seconds).unwrap();
+            writeln!(playlist, "{}.{}", segment.sequence, self.extension).unwrap();
+        }
+
+        playlist
+    }
+
+    /// Gets a segment by its sequence number, if it's still available.
+    pub fn segment(&self, sequence: u64) -> Option<HlsSegment> {
+        self.state
+            .lock()
+            .segments
+            .iter()
+            .find(|s| s.sequence == sequence)
+            .cloned()
+    }
+}
+
+impl HlsState {
+    /// Adds a new segment, dropping the oldest ones once they're no longer needed.
+    fn push(&mut self, bytes: Vec<u8>, duration_in_seconds: f32, timestamp: u64) {
+        let mut tagged = id3_timestamp(timestamp);
+        tagged.extend_from_slice(&bytes);
+
+        self.segments.push_back(HlsSegment {
+            sequence: self.next_sequence,
+            duration_in_seconds,
+            bytes: tagged.into(),
+        });
+
+        self.next_sequence += 1;
+
+        while self.segments.len() > self.window + HlsPackager::RETAINED_SEGMENTS {
+            self.segments.pop_front();
+        }
+    }
+
+    /// Returns the segments that are listed in the playlist.
+    fn listed(&self) -> Vec<&HlsSegment> {
+        let skipped = self.segments.len().saturating_sub(self.window);
+        self.segments.iter().skip(skipped).collect()
+    }
+}
+
+fn spawn_packaging_thread(consumer: Arc<Consumer>, state: Weak<Mutex<HlsState>>) {
+    let config = consumer.config().clone();
+    let samples_per_segment =
+        config.seconds_to_samples(config.hls_segment_duration_in_seconds) as u64;
+
+    let run = move || {
+        let mut buf = vec![0; 1024 * 16];
+        let mut bytes = Vec::new();
+        let mut segment_start = 0;
+
+        loop {
+            consumer.wait(Duration::from_secs(1));
+
+            let Some(state) = state.upgrade() else {
+                break;
+            };
+
+            while let Ok(amount @ 1..) = consumer.read_available(&mut buf) {
+                bytes.extend_from_slice(&buf[..amount]);
+            }
+
+            let encoded_samples = consumer.encoded_samples();
+            let samples_in_segment = encoded_samples - segment_start;
+
+            if samples_in_segment < samples_per_segment || bytes.is_empty() {
+                continue;
+            }
+
+            state.lock().push(
+                mem::take(&mut bytes),
+                config.samples_to_seconds(samples_in_segment as usize),
+                timestamp(segment_start, &config),
+            );
+
+            segment_start = encoded_samples;
+        }
+    };
+
+    thread::spawn(run);
+}
+
+/// Converts a sample offset to a 33-bit MPEG timestamp, with a 90kHz clock.
+fn timestamp(samples: u64, config: &Config) -> u64 {
+    const CLOCK_RATE: u64 = 90_000;
+    const MASK: u64 = (1 << 33) - 1;
+
+    (samples * CLOCK_RATE / config.samples_per_sec() as u64) & MASK
+}
+
+/// Creates the ID3 tag that packed audio segments start with, telling the player the timestamp of the first sample.
+fn id3_timestamp(timestamp: u64) -> Vec<u8> {
+    const OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp\0";
+
+    let frame_size = OWNER.len() + 8;
+    let tag_size = 10 + frame_size;
+
+    let mut tag = Vec::with_capacity(10 + tag_size);
+
+    tag.extend_from_slice(b"ID3");
+    tag.extend_from_slice(&[4, 0, 0]);
+    tag.extend_from_slice(&synchsafe(tag_size as u32));
+
+    tag.extend_from_slice(b"PRIV");
+    tag.extend_from_slice(&synchsafe(frame_size as u32));
+    tag.extend_from_slice(&[0, 0]);
+    tag.extend_from_slice(OWNER);
+    tag.extend_from_slice(&timestamp.to_be_bytes());
+
+    tag
+}
+
+/// Encodes a size as an ID3 synchsafe integer, where the top bit of every byte is unused.
+fn synchsafe(value: u32) -> [u8; 4] {
+    [
+        (value >> 21) as u8 & 0x7F,
+        (value >> 14) as u8 & 0x7F,
+        (value >> 7) as u8 & 0x7F,
+        value as u8 & 0x7F,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::{Encoder, EncoderOptions, EncoderRegistry, Sample, Stream};
+
+    /// Outputs a byte per sample
+    struct ByteEncoder(Vec<u8>);
+
+    impl Encoder for ByteEncoder {
+        fn new(_config: Config, _options: EncoderOptions) -> Self {
+            Self(vec![])
+        }
+
+        fn encode(&mut self, samples: &[Sample]) {
+            self.0.extend(samples.iter().map(|s| *s as u8));
+        }
+
+        fn content_type(&self) -> String {
+            "audio/mpeg".to_string()
+        }
+    }
+
+    impl Read for ByteEncoder {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let amount = crate::assign_slice(&self.0, buf);
+            self.0.drain(..amount);
+
+            Ok(amount)
+        }
+    }
+
+    fn state() -> HlsState {
+        HlsState {
+            segments: Default::default(),
+            next_sequence: 0,
+            window: 3,
+        }
+    }
+
+    #[test]
+    fn test_id3_timestamp() {
+        let tag = id3_timestamp(90_000);
+
+        assert_eq!(&tag[..3], b"ID3");
+        assert_eq!(tag[6..10], [0, 0, 0, 63], "tag size excludes the header");
+        assert_eq!(&tag[10..14], b"PRIV");
+        assert_eq!(tag[14..18], [0, 0, 0, 53]);
+        assert_eq!(tag.len(), 73);
+        assert_eq!(tag[65..], 90_000u64.to_be_bytes());
+
+        assert_eq!(synchsafe(255), [0, 0, 1, 127]);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let config = Config::default();
+
+        assert_eq!(timestamp(0, &config), 0);
+        assert_eq!(
+            timestamp(config.samples_per_sec() as u64 * 2, &config),
+            180_000
+        );
+    }
+
+    #[test]
+    fn test_window() {
+        let mut state = state();
+
+        for i in 0..8 {
+            state.push(vec![i], 4., 0);
+        }
+
+        let listed: Vec<_> = state.listed().iter().map(|s| s.sequence).collect();
+
+        assert_eq!(listed, [5, 6, 7], "only the window is listed");
+        assert_eq!(state.segments.len(), 5, "a few older segments are retained");
+        assert_eq!(state.segments[0].sequence, 3);
+        assert_eq!(
+            state.segments[0].bytes.last(),
+            Some(&3),
+            "data follows the tag"
+        );
+    }
+
+    #[test]
+    fn test_media_playlist() {
+        let mut state = state();
+
+        state.push(vec![0], 4.02, 0);
+        state.push(vec![1], 4.6, 0);
+
+        let packager = HlsPackager {
+            extension: "mp3".to_string(),
+            content_type: "audio/mpeg".to_string(),
+            target_duration: 4,
+            state: Arc::new(Mutex::new(state)),
+        };
+
+        let playlist = packager.media_playlist();
+
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n\
+            #EXT-X-VERSION:3\n\
+            #EXT-X-TARGETDURATION:5\n\
+            #EXT-X-MEDIA-SEQUENCE:0\n\
+            #EXTINF:4.020,\n\
+            0.mp3\n\
+            #EXTINF:4.600,\n\
+            1.mp3\n"
+        );
+
+        assert!(packager.segment(1).is_some());
+        assert!(packager.segment(2).is_none());
+    }
+
+    #[test]
+    fn test_packaging() {
+        let config = Config {
+            sample_rate: 100,
+            channel_count: 1,
+            stream_preload_cache_size_in_seconds: 0.,
+            hls_segment_duration_in_seconds: 1.,
+            ..Default::default()
+        };
+
+        let registry = EncoderRegistry::default();
+        registry.register::<ByteEncoder>("mp3", "audio/mpeg");
+
+        let encoder = registry.get("mp3").unwrap();
+        assert!(HlsPackager::supports(&encoder));
+
+        let stream = Stream::new(config);
+        let consumer = stream.consume_registered(&encoder, Default::default());
+        let packager = HlsPackager::new(Arc::new(consumer), "mp3");
+
+        // Two and a half segments
+        for _ in 0..25 {
+            stream.push(&[1.; 10], 0);
+        }
+
+        thread::sleep(Duration::from_millis(100));
+        stream.push(&[1.; 10], 0);
+        thread::sleep(Duration::from_millis(100));
+
+        let first = packager.segment(0).expect("first segment is cut");
+        let tag_size = id3_timestamp(0).len();
+
+        assert_eq!(&first.bytes[..3], b"ID3");
+        assert!(
+            first.bytes.len() - tag_size >= 100,
+            "segment holds at least its duration"
+        );
+        assert!(packager.media_playlist().contains("0.mp3"));
+    }
+}
@@ -0,0 +1,134 @@
+/// Interleaves ICY metadata into encoded audio at a fixed byte interval, the way Icecast and SHOUTcast servers do.
+///
+/// Every `interval` bytes of audio are followed by a metadata block, which is empty unless the title changed.
+pub struct IcyInterleaver {
+    interval: usize,
+    bytes_until_metadata: usize,
+    title: Option<String>,
+    /// Whether the title has changed since it was last sent
+    is_title_pending: bool,
+}
+
+impl IcyInterleaver {
+    /// The interval most clients and servers use, which is sent in the `icy-metaint` header.
+    pub const DEFAULT_INTERVAL: usize = 16_000;
+
+    /// The largest metadata block, since its length is stored in a single byte as a multiple of 16.
+    const MAX_BLOCK_SIZE: usize = 255 * 16;
+
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval,
+            bytes_until_metadata: interval,
+            title: None,
+            // Clients show nothing until the first title, so always send it once
+            is_title_pending: true,
+        }
+    }
+
+    /// Returns the amount of audio bytes between metadata blocks.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Sets the title sent in the next metadata block, if it changed.
+    pub fn set_title(&mut self, title: Option<&str>) {
+        if self.title.as_deref() != title {
+            self.title = title.map(|t| t.to_string());
+            self.is_title_pending = true;
+        }
+    }
+
+    /// Returns the given audio bytes with metadata blocks inserted at every interval.
+    pub fn interleave(&mut self, mut bytes: &[u8]) -> Vec<u8> {
+        let mut interleaved = Vec::with_capacity(bytes.len() + 1);
+
+        while !bytes.is_empty() {
+            let amount = bytes.len().min(self.bytes_until_metadata);
+
+            interleaved.extend_from_slice(&bytes[..amount]);
+            bytes = &bytes[amount..];
+            self.bytes_until_metadata -= amount;
+
+            if self.bytes_until_metadata == 0 {
+                interleaved.extend_from_slice(&self.next_block());
+                self.bytes_until_metadata = self.interval;
+            }
+        }
+
+        interleaved
+    }
+
+    /// Returns the next metadata block, which is a single zero byte if nothing changed.
+    fn next_block(&mut self) -> Vec<u8> {
+        if !self.is_title_pending {
+            return vec![0];
+        }
+
+        self.is_title_pending = false;
+
+        // Quotes end the title early in most clients, and there is no way to escape them
+        let title = self.title.as_deref().unwrap_or_default().replace('\'', "’");
+        let mut metadata = format!("StreamTitle='{title}';").into_bytes();
+
+        if metadata.len() > Self::MAX_BLOCK_SIZE {
+            metadata.truncate(Self::MAX_BLOCK_SIZE - 2);
+            metadata.extend_from_slice(b"';");
+        }
+
+        let length = metadata.len().div_ceil(16);
+        metadata.resize(length * 16, 0);
+
+        let mut block = vec![length as u8];
+        block.extend_from_slice(&metadata);
+
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interleave() {
+        let mut interleaver = IcyInterleaver::new(4);
+        interleaver.set_title(Some("Artist - Title"));
+
+        let interleaved = interleaver.interleave(&[1, 2, 3, 4, 5, 6]);
+
+        let block = &interleaved[4..];
+        assert_eq!(interleaved[..4], [1, 2, 3, 4], "audio comes first");
+        assert_eq!(block[0], 2, "length is in blocks of 16 bytes");
+        assert_eq!(&block[1..30], b"StreamTitle='Artist - Title';");
+        assert!(block[30..33].iter().all(|b| *b == 0), "block is padded");
+        assert_eq!(block[33..], [5, 6]);
+
+        let interleaved = interleaver.interleave(&[7, 8, 9]);
+        assert_eq!(interleaved, [7, 8, 0, 9], "unchanged titles are not resent");
+
+        interleaver.set_title(Some("Don't"));
+        let interleaved = interleaver.interleave(&[0; 3]);
+
+        assert_eq!(
+            interleaved[3], 2,
+            "changed titles are sent at the next interval"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&interleaved[4..]).trim_end_matches('\0'),
+            "StreamTitle='Don’t';"
+        );
+    }
+
+    #[test]
+    fn test_long_title_is_truncated() {
+        let mut interleaver = IcyInterleaver::new(1);
+        interleaver.set_title(Some(&"a".repeat(5000)));
+
+        let interleaved = interleaver.interleave(&[0]);
+
+        assert_eq!(interleaved[1], 255);
+        assert_eq!(interleaved.len(), 2 + 255 * 16);
+        assert!(interleaved.ends_with(b"';"));
+    }
+}
@@ -1,27 +1,56 @@
 use std::{sync::Arc, thread};
 
-use crate::{Config, PipelineContext, PlayerId, Sample};
+use crate::{Config, PipelineContext, PipelineEvent, PlayerId, Sample};
 use crossbeam::channel::{unbounded, Receiver, Sender};
 use dashmap::DashMap;
 
 mod consumer;
+mod conversion;
 mod encoder;
+mod hls;
+mod icy;
+mod registry;
 mod stream;
+mod timeshift;
 
 pub use consumer::*;
+pub use conversion::*;
 pub use encoder::*;
+pub use hls::*;
+pub use icy::*;
+pub use registry::*;
 pub use stream::*;
+pub use timeshift::*;
 
 /// Manages streams for consuming a [Player].
 pub struct Output {
+    context: PipelineContext,
     config: Config,
     streams: Arc<DashMap<PlayerId, Arc<Stream>>>,
     sample_sender: Sender<ProcessedSamples>,
+    encoders: EncoderRegistry,
+}
+
+/// The estimated position of what listeners of a format are hearing.
+///
+/// Listeners are behind the produced audio by the stream preload cache, the buffering of the encoder and the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeardPosition {
+    /// The name of the format, such as `wav`.
+    pub format: String,
+    /// The estimated position in the current item, in seconds.
+    ///
+    /// This is negative right after the player advanced, while the previous item is still heard.
+    pub position: f32,
+    /// The estimated total position, in seconds.
+    pub total_position: f32,
 }
 
 struct ProcessedSamples {
     player_id: PlayerId,
     samples: Vec<Sample>,
+    /// The total offset of the player at the end of the samples.
+    total_offset: usize,
 }
 
 impl Output {
@@ -32,20 +61,46 @@ impl Output {
         spawn_output_thread(sample_receiver, streams.clone());
 
         Self {
+            context: context.clone(),
             config: context.config.clone(),
             streams,
             sample_sender,
+            encoders: Default::default(),
         }
     }
 
     /// Creates a new stream for the given player.
     pub fn register_player(&self, player_id: PlayerId) {
         let new_stream = Stream::new(self.config.clone());
+        let context = self.context.clone();
+
+        new_stream.on_timeshift_error(Box::new(move |error| {
+            context.emit(PipelineEvent::TimeshiftError {
+                player_id,
+                error: error.to_string(),
+            })
+        }));
+
         self.streams.insert(player_id, new_stream);
     }
 
-    /// Gets a consumer for the associated player, with the given encoder.
-    pub fn consume_player<E>(&self, player_id: PlayerId) -> Consumer
+    /// Creates a new stream for a preview player, which isn't time-shifted since nothing listens to it behind live.
+    pub fn register_preview_player(&self, player_id: PlayerId) {
+        let config = Config {
+            stream_timeshift_window_in_seconds: 0.,
+            ..self.config.clone()
+        };
+
+        self.streams.insert(player_id, Stream::new(config));
+    }
+
+    /// Removes the stream of a player, which ends its consumers.
+    pub fn remove_player(&self, player_id: PlayerId) {
+        self.streams.remove(&player_id);
+    }
+
+    /// Gets a consumer for the associated player, with the given encoder and its options.
+    pub fn consume_player<E>(&self, player_id: PlayerId, options: EncoderOptions) -> Consumer
     where
         E: Encoder,
     {
@@ -54,13 +109,73 @@ impl Output {
             .get(&player_id)
             .expect("consume_player() is not called with a player that does not exist");
 
-        stream.consume::<E>()
+        stream.consume::<E>(options)
+    }
+
+    /// Gets a consumer for the associated player, with an encoder picked at runtime.
+    pub fn consume_player_as(
+        &self,
+        player_id: PlayerId,
+        encoder: &RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Consumer {
+        let stream = self
+            .streams
+            .get(&player_id)
+            .expect("consume_player_as() is not called with a player that does not exist");
+
+        stream.consume_registered(encoder, options)
+    }
+
+    /// Returns the last `seconds` of the associated player's stream, if time-shifting is enabled.
+    pub fn capture_player(&self, player_id: PlayerId, seconds: f32) -> Option<Vec<Sample>> {
+        let stream = self
+            .streams
+            .get(&player_id)
+            .expect("capture_player() is not called with a player that does not exist");
+
+        stream.capture(seconds)
+    }
+
+    /// Returns the registry of encoders that can be picked at runtime.
+    pub fn encoders(&self) -> &EncoderRegistry {
+        &self.encoders
+    }
+
+    /// Returns the estimated amount of seconds between producing audio and listeners of the encoder hearing it.
+    pub fn latency(&self, encoder: &RegisteredEncoder) -> f32 {
+        self.config.stream_preload_cache_size_in_seconds
+            + encoder.latency(&self.config)
+            + self.config.stream_network_latency_in_seconds
+    }
+
+    /// Returns the estimated heard positions of every registered format, for the given produced positions in seconds.
+    pub fn heard_positions(&self, position: f32, total_position: f32) -> Vec<HeardPosition> {
+        self.encoders
+            .list()
+            .iter()
+            .map(|encoder| {
+                let latency = self.latency(encoder);
+
+                HeardPosition {
+                    format: encoder.name.clone(),
+                    position: position - latency,
+                    total_position: (total_position - latency).max(0.),
+                }
+            })
+            .collect()
     }
 
     /// Pushes samples to the associated player's stream.
-    pub fn push(&self, player_id: PlayerId, samples: Vec<Sample>) {
+    ///
+    /// * `total_offset` - The total offset of the player at the end of the samples.
+    pub fn push(&self, player_id: PlayerId, samples: Vec<Sample>, total_offset: usize) {
         self.sample_sender
-            .send(ProcessedSamples { player_id, samples })
+            .send(ProcessedSamples {
+                player_id,
+                samples,
+                total_offset,
+            })
             .expect("processed samples are sent");
     }
 }
@@ -73,7 +188,7 @@ fn spawn_output_thread(
         let processed_samples = receiver.recv().expect("processed samples are received");
 
         if let Some(stream) = streams.get(&processed_samples.player_id) {
-            stream.push(&processed_samples.samples);
+            stream.push(&processed_samples.samples, processed_samples.total_offset);
         }
     };
 
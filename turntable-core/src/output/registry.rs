@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use super::{Encoder, EncoderOptions};
+use crate::Config;
+
+/// Creates a boxed [Encoder] at runtime.
+pub type EncoderFactory = Arc<dyn Fn(Config, EncoderOptions) -> Box<dyn Encoder> + Send + Sync>;
+
+/// An encoder that can be picked at runtime by its name or content type.
+#[derive(Clone)]
+pub struct RegisteredEncoder {
+    /// The short name of the format, such as `wav`.
+    pub name: String,
+    /// The MIME type of the encoded data, such as `audio/wav`.
+    pub content_type: String,
+    factory: EncoderFactory,
+    latency: fn(&Config) -> f32,
+}
+
+/// Maps format names and MIME types to encoders, so the format of a consumer can be chosen at runtime.
+///
+/// Encoders are kept in the order they were registered, and the first one is the default.
+#[derive(Clone, Default)]
+pub struct EncoderRegistry {
+    encoders: Arc<RwLock<Vec<RegisteredEncoder>>>,
+}
+
+impl RegisteredEncoder {
+    /// Creates a new instance of the encoder.
+    pub fn create(&self, config: Config, options: EncoderOptions) -> Box<dyn Encoder> {
+        (self.factory)(config, options)
+    }
+
+    /// Returns how many seconds of audio the encoder can hold back, as in [Encoder::latency].
+    pub fn latency(&self, config: &Config) -> f32 {
+        (self.latency)(config)
+    }
+
+    /// Returns true if the given media range, such as `audio/*`, includes this encoder.
+    fn matches_range(&self, range: &str) -> bool {
+        match range.split_once('/') {
+            Some(("*", "*")) => true,
+            Some((kind, "*")) => self
+                .content_type
+                .split_once('/')
+                .is_some_and(|(k, _)| k.eq_ignore_ascii_case(kind)),
+            _ => self.content_type.eq_ignore_ascii_case(range),
+        }
+    }
+}
+
+impl EncoderRegistry {
+    /// Registers an encoder, replacing any encoder with the same name.
+    pub fn register<E>(&self, name: &str, content_type: &str)
+    where
+        E: Encoder,
+    {
+        let registered = RegisteredEncoder {
+            name: name.to_string(),
+            content_type: content_type.to_string(),
+            factory: Arc::new(|config, options| Box::new(E::new(config, options))),
+            latency: E::latency,
+        };
+
+        let mut encoders = self.encoders.write();
+
+        match encoders.iter_mut().find(|e| e.name == name) {
+            Some(existing) => *existing = registered,
+            None => encoders.push(registered),
+        }
+    }
+
+    /// Returns all registered encoders.
+    pub fn list(&self) -> Vec<RegisteredEncoder> {
+        self.encoders.read().clone()
+    }
+
+    /// Returns the default encoder, which is the first one registered.
+    pub fn default_encoder(&self) -> Option<RegisteredEncoder> {
+        self.encoders.read().first().cloned()
+    }
+
+    /// Gets an encoder by its name or its content type.
+    pub fn get(&self, name_or_type: &str) -> Option<RegisteredEncoder> {
+        let name_or_type = name_or_type.trim();
+        let without_parameters = strip_parameters(name_or_type);
+
+        self.encoders
+            .read()
+            .iter()
+            .find(|e| {
+                e.name.eq_ignore_ascii_case(name_or_type)
+                    || e.content_type.eq_ignore_ascii_case(without_parameters)
+            })
+            .cloned()
+    }
+
+    /// Picks the best encoder for the value of an `Accept` header.
+    ///
+    /// Wildcard ranges prefer the `fallback` encoder if it's in the range.
+    /// Returns `None` if none of the acceptable types are registered.
+    pub fn negotiate(&self, accept: &str, fallback: Option<&str>) -> Option<RegisteredEncoder> {
+        let fallback = fallback.and_then(|f| self.get(f));
+        let encoders = self.encoders.read();
+
+        let mut ranges: Vec<_> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';').map(str::trim);
+                let media_range = parts.next().filter(|r| !r.is_empty())?;
+
+                let quality = parts
+                    .filter_map(|p| p.strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.);
+
+                Some((media_range, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.)
+            .collect();
+
+        // Sorting is stable, so equal qualities keep the order of the header
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges.into_iter().find_map(|(range, _)| {
+            fallback
+                .as_ref()
+                .filter(|f| range.contains('*') && f.matches_range(range))
+                .or_else(|| encoders.iter().find(|e| e.matches_range(range)))
+                .cloned()
+        })
+    }
+}
+
+/// Removes the parameters from a MIME type, such as `; codecs=opus`.
+fn strip_parameters(content_type: &str) -> &str {
+    content_type
+        .split(';')
+        .next()
+        .map(str::trim)
+        .unwrap_or(content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::Sample;
+
+    struct TestEncoder;
+
+    impl Encoder for TestEncoder {
+        fn new(_config: Config, _options: EncoderOptions) -> Self {
+            Self
+        }
+
+        fn encode(&mut self, _samples: &[Sample]) {}
+
+        fn content_type(&self) -> String {
+            "audio/test".to_string()
+        }
+    }
+
+    impl Read for TestEncoder {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    /// Holds back a frame of 1000 samples per channel.
+    struct FramedEncoder;
+
+    impl Encoder for FramedEncoder {
+        fn new(_config: Config, _options: EncoderOptions) -> Self {
+            Self
+        }
+
+        fn encode(&mut self, _samples: &[Sample]) {}
+
+        fn content_type(&self) -> String {
+            "audio/framed".to_string()
+        }
+
+        fn latency(config: &Config) -> f32 {
+            1000. / config.sample_rate as f32
+        }
+    }
+
+    impl Read for FramedEncoder {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    fn registry() -> EncoderRegistry {
+        let registry = EncoderRegistry::default();
+
+        registry.register::<TestEncoder>("wav", "audio/wav");
+        registry.register::<TestEncoder>("opus", "audio/ogg");
+        registry.register::<TestEncoder>("mp3", "audio/mpeg");
+
+        registry
+    }
+
+    fn name(encoder: Option<RegisteredEncoder>) -> Option<String> {
+        encoder.map(|e| e.name)
+    }
+
+    #[test]
+    fn test_get() {
+        let registry = registry();
+
+        assert_eq!(name(registry.get("opus")), Some("opus".into()));
+        assert_eq!(name(registry.get("MP3")), Some("mp3".into()));
+        assert_eq!(
+            name(registry.get("audio/ogg; codecs=opus")),
+            Some("opus".into())
+        );
+        assert_eq!(name(registry.get("flac")), None);
+        assert_eq!(name(registry.default_encoder()), Some("wav".into()));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let registry = registry();
+
+        assert_eq!(
+            name(registry.negotiate("audio/mpeg", None)),
+            Some("mp3".into()),
+            "exact match"
+        );
+
+        assert_eq!(
+            name(registry.negotiate("audio/ogg;q=0.5, audio/mpeg;q=0.9", None)),
+            Some("mp3".into()),
+            "highest quality wins"
+        );
+
+        assert_eq!(
+            name(registry.negotiate("audio/flac, audio/wav;q=0.1", None)),
+            Some("wav".into()),
+            "unsupported types are skipped"
+        );
+
+        assert_eq!(
+            name(registry.negotiate("*/*", Some("opus"))),
+            Some("opus".into()),
+            "wildcard prefers the fallback"
+        );
+
+        assert_eq!(
+            name(registry.negotiate("audio/*", None)),
+            Some("wav".into()),
+            "wildcard picks the first registered without a fallback"
+        );
+
+        assert_eq!(
+            name(registry.negotiate("audio/mpeg;q=0, application/json", None)),
+            None,
+            "nothing acceptable"
+        );
+    }
+
+    #[test]
+    fn test_latency() {
+        let registry = registry();
+        registry.register::<FramedEncoder>("framed", "audio/framed");
+
+        let config = Config {
+            sample_rate: 1000,
+            ..Default::default()
+        };
+
+        assert_eq!(registry.get("wav").unwrap().latency(&config), 0.);
+        assert_eq!(registry.get("framed").unwrap().latency(&config), 1.);
+    }
+}
@@ -1,10 +1,21 @@
-use std::sync::{Arc, Weak};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Weak},
+    thread,
+};
 
-use dashmap::DashMap;
+use crossbeam::{
+    atomic::AtomicCell,
+    channel::{unbounded, Receiver, Sender},
+};
 use parking_lot::Mutex;
 
-use super::{Consumer, ConsumerId, Encoder};
-use crate::{Config, Producer, Sample};
+use super::{
+    Consumer, ConsumerId, ConversionFormat, Converter, Encoder, EncoderOptions, RegisteredEncoder,
+    TimeshiftBuffer,
+};
+use crate::{Config, DynamicResampler, Metrics, Producer, Sample};
 
 /// A stream is the destination of a [Player], and manages consumers for said player.
 ///
@@ -13,11 +24,64 @@ pub struct Stream {
     config: Config,
     /// A weak reference is required because dropped consumers need to be removed.
     me: Weak<Stream>,
-    /// A preloaded cache of samples used to instantly fill a consumer,
-    /// so that there isn't a delay before a consumer returns data.
+    /// A preloaded cache of samples in the pipeline's format, used to fill new consumer groups.
     preload_cache: Mutex<Vec<Sample>>,
-    /// The producer parts of consumers that have been created for this stream.
-    producers: DashMap<ConsumerId, Producer>,
+    /// The consumers of this stream, grouped by format so each conversion only happens once.
+    groups: Mutex<Vec<ConsumerGroup>>,
+    /// The total position of the player at the end of the latest pushed samples, in seconds.
+    total_position: AtomicCell<f32>,
+    /// The recent samples of the stream, once something needed them and if time-shifting is enabled.
+    timeshift: Mutex<Option<Arc<Timeshift>>>,
+    /// Called when the time-shift buffer can't be created or written.
+    timeshift_error_listener: Mutex<Option<TimeshiftErrorListener>>,
+    /// Consumers that are behind live, and read from the time-shift buffer instead of a group.
+    delayed: Mutex<Vec<DelayedProducer>>,
+}
+
+/// Called with the error whenever the time-shift buffer of a stream fails.
+pub type TimeshiftErrorListener = Box<dyn Fn(io::Error) + Send + Sync>;
+
+/// The time-shift buffer of a stream, which is written on a thread of its own so disk access doesn't hold up live consumers.
+struct Timeshift {
+    buffer: Mutex<TimeshiftBuffer>,
+    sender: Sender<TimeshiftMessage>,
+}
+
+enum TimeshiftMessage {
+    /// Samples to write, with the total position of the player at the end of them.
+    Push(Vec<Sample>, f32),
+    /// Answers once everything sent before has been written.
+    #[cfg(test)]
+    Flush(Sender<()>),
+}
+
+/// Consumers that share the same format, and the conversion into that format.
+struct ConsumerGroup {
+    format: ConversionFormat,
+    /// The pipeline config with the format of this group applied.
+    config: Config,
+    converter: Converter,
+    /// A preloaded cache of converted samples used to instantly fill a consumer,
+    /// so that there isn't a delay before a consumer returns data.
+    preload_cache: Vec<Sample>,
+    /// The producer parts of consumers that have been created for this group.
+    producers: HashMap<ConsumerId, Producer>,
+}
+
+/// A consumer that reads from the time-shift buffer, with its own conversion.
+struct DelayedProducer {
+    id: ConsumerId,
+    format: ConversionFormat,
+    converter: Converter,
+    /// The channel count of the pipeline, which the time-shift buffer is in.
+    channel_count: usize,
+    /// How many samples are read for every sample played, which is above 1 when catching up.
+    speed: f32,
+    /// Speeds up playback if the consumer wants to catch up to live.
+    catch_up: Option<DynamicResampler>,
+    /// The index of the next sample to read from the time-shift buffer.
+    cursor: u64,
+    producer: Producer,
 }
 
 impl Stream {
@@ -25,52 +89,572 @@ impl Stream {
         Arc::new_cyclic(|me| Self {
             config,
             me: me.clone(),
-            producers: Default::default(),
+            groups: Default::default(),
             preload_cache: Default::default(),
+            total_position: Default::default(),
+            timeshift: Default::default(),
+            timeshift_error_listener: Default::default(),
+            delayed: Default::default(),
         })
     }
 
+    /// Sets the listener that is called whenever the time-shift buffer fails.
+    ///
+    /// Delayed consumers continue at live when that happens, since their audio would be missing otherwise.
+    pub fn on_timeshift_error(&self, listener: TimeshiftErrorListener) {
+        *self.timeshift_error_listener.lock() = Some(listener);
+    }
+
     /// Gets a new consumer for this stream.
-    pub fn consume<E>(&self) -> Consumer
+    pub fn consume<E>(&self, options: EncoderOptions) -> Consumer
     where
         E: Encoder,
     {
-        let (consumer, producer) = Consumer::new::<E>(self.config.clone(), self.me.clone());
-        let preload_cache = self.preload_cache.lock();
+        let format = ConversionFormat::from_options(&self.config, &options);
+        let encoder = E::new(format.apply(&self.config), options.clone());
+
+        self.consume_with(format, Box::new(encoder), &options)
+    }
+
+    /// Gets a new consumer for this stream, with an encoder picked at runtime.
+    pub fn consume_registered(
+        &self,
+        encoder: &RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Consumer {
+        let format = ConversionFormat::from_options(&self.config, &options);
+        let encoder = encoder.create(format.apply(&self.config), options.clone());
+
+        self.consume_with(format, encoder, &options)
+    }
+
+    fn consume_with(
+        &self,
+        format: ConversionFormat,
+        encoder: Box<dyn Encoder>,
+        options: &EncoderOptions,
+    ) -> Consumer {
+        // Without a time-shift buffer, delayed consumers just start at live
+        if let Some(delay) = options.delay.filter(|d| *d > 0) {
+            if let Some(timeshift) = self.ensure_timeshift() {
+                let catch_up = options.catch_up.unwrap_or_default();
+                return self.consume_delayed(&timeshift, format, encoder, delay, catch_up);
+            }
+        }
+
+        let mut groups = self.groups.lock();
+
+        let group = self.group_for(&mut groups, format);
+
+        let (consumer, producer) = Consumer::new(encoder, group.config.clone(), self.me.clone());
+
+        producer.push(&group.preload_cache, self.total_position.load());
+        group.producers.insert(consumer.id, producer);
+
+        consumer
+    }
+
+    /// Creates a consumer that starts the given amount of whole seconds behind live, reading from the time-shift buffer.
+    ///
+    /// It starts at the oldest buffered sample if the buffer doesn't reach back that far, such as right after it was created.
+    fn consume_delayed(
+        &self,
+        timeshift: &Timeshift,
+        format: ConversionFormat,
+        encoder: Box<dyn Encoder>,
+        delay: u32,
+        catch_up: bool,
+    ) -> Consumer {
+        let timeshift = timeshift.buffer.lock();
+
+        let delay_in_frames = self.config.sample_rate * delay as usize;
+        let delay_in_samples = (delay_in_frames * self.config.channel_count) as u64;
+        let cursor = timeshift
+            .written()
+            .saturating_sub(delay_in_samples)
+            .max(timeshift.oldest());
 
-        producer.push(&preload_cache);
-        self.producers.insert(consumer.id, producer);
+        let catch_up = catch_up.then(|| {
+            // Treating the samples as if they had a higher sample rate plays them faster
+            let sped_up_rate = self.config.sample_rate as f32 * self.config.stream_catch_up_speed;
 
+            DynamicResampler::with_rates(
+                sped_up_rate.round() as usize,
+                self.config.sample_rate,
+                self.config.channel_count,
+            )
+            .expect("resampler is created for a valid speed")
+        });
+
+        let config = format.apply(&self.config);
+        let (consumer, producer) = Consumer::new(encoder, config, self.me.clone());
+
+        let mut delayed = DelayedProducer {
+            id: consumer.id,
+            format,
+            converter: Converter::new(&self.config, format),
+            channel_count: self.config.channel_count,
+            speed: match catch_up {
+                Some(_) => self.config.stream_catch_up_speed,
+                None => 1.,
+            },
+            catch_up,
+            cursor,
+            producer,
+        };
+
+        // Fill the consumer like a group would, so there isn't a delay before it returns data
+        delayed.advance(&timeshift, self.config.stream_preload_cache_size());
+        drop(timeshift);
+
+        self.delayed.lock().push(delayed);
         consumer
     }
 
-    /// Removes a producer from this stream.
+    /// Removes a producer from this stream, and its group if it was the last one in it.
     pub fn remove(&self, consumer_id: ConsumerId) {
-        self.producers.remove(&consumer_id);
+        self.delayed.lock().retain(|d| d.id != consumer_id);
+
+        let mut groups = self.groups.lock();
+
+        for group in groups.iter_mut() {
+            group.producers.remove(&consumer_id);
+        }
+
+        groups.retain(|g| !g.producers.is_empty());
     }
 
     /// Push new samples to the stream.
     ///
+    /// * `total_offset` - The total offset of the player at the end of the samples.
+    ///
     /// Note: This function must not be called on the playback thread.
-    pub fn push(&self, samples: &[Sample]) {
-        for producer in self.producers.iter() {
-            producer.push(samples);
+    pub fn push(&self, samples: &[Sample], total_offset: usize) {
+        let total_position = self.config.samples_to_seconds(total_offset);
+        self.total_position.store(total_position);
+
+        // Counted before conversion, so every consumer and format of the stream counts the same samples once
+        Metrics::global().count_streamed(samples);
+
+        for group in self.groups.lock().iter_mut() {
+            group.push(samples, total_position);
         }
 
-        self.push_preload(samples)
+        self.push_preload(samples);
+
+        if let Some(timeshift) = self.timeshift.lock().as_ref() {
+            let message = TimeshiftMessage::Push(samples.to_vec(), total_position);
+            let _ = timeshift.sender.send(message);
+        }
+    }
+
+    /// Returns the last `seconds` of the stream from the time-shift buffer, or `None` if time-shifting is disabled.
+    ///
+    /// The buffer is started by the first capture if no delayed consumer started it yet,
+    /// so less is returned if the buffer, the stream or the window is shorter than that.
+    pub fn capture(&self, seconds: f32) -> Option<Vec<Sample>> {
+        let timeshift = self.ensure_timeshift()?;
+        let timeshift = timeshift.buffer.lock();
+
+        // Rounded down to whole frames, so the channels stay in order
+        let amount = self.config.seconds_to_samples(seconds) / self.config.channel_count
+            * self.config.channel_count;
+
+        let from = timeshift
+            .written()
+            .saturating_sub(amount as u64)
+            .max(timeshift.oldest());
+
+        let mut samples = vec![0.; (timeshift.written() - from) as usize];
+        let read = timeshift.read(from, &mut samples).ok()?;
+
+        samples.truncate(read);
+        Some(samples)
     }
 
     /// Pushes samples to the preload cache.
     pub fn push_preload(&self, samples: &[Sample]) {
         let mut preload_cache = self.preload_cache.lock();
 
-        preload_cache.extend_from_slice(samples);
+        extend_preload_cache(&mut preload_cache, samples, &self.config);
+    }
+}
+
+impl Stream {
+    /// Returns the group of the given format, creating it from the preload cache if it doesn't exist yet.
+    fn group_for<'a>(
+        &self,
+        groups: &'a mut Vec<ConsumerGroup>,
+        format: ConversionFormat,
+    ) -> &'a mut ConsumerGroup {
+        match groups.iter().position(|g| g.format == format) {
+            Some(index) => &mut groups[index],
+            None => {
+                let mut group = ConsumerGroup::new(&self.config, format);
+                group.push_preload(&self.preload_cache.lock());
 
-        let preload_size = self.config.stream_preload_cache_size();
-        let amount_overflowing = preload_cache.len().saturating_sub(preload_size);
+                groups.push(group);
+                groups.last_mut().expect("group was just added")
+            }
+        }
+    }
 
-        if amount_overflowing > 0 {
-            preload_cache.drain(..amount_overflowing);
+    /// Returns the time-shift buffer, creating it if time-shifting is enabled and it doesn't exist yet.
+    fn ensure_timeshift(&self) -> Option<Arc<Timeshift>> {
+        if self.config.stream_timeshift_window_size() == 0 {
+            return None;
         }
+
+        let mut timeshift = self.timeshift.lock();
+
+        if let Some(timeshift) = timeshift.as_ref() {
+            return Some(timeshift.clone());
+        }
+
+        let buffer = match TimeshiftBuffer::new(&self.config) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                self.report_timeshift_error(e);
+                return None;
+            }
+        };
+
+        let (sender, receiver) = unbounded();
+        let created = Arc::new(Timeshift {
+            buffer: Mutex::new(buffer),
+            sender,
+        });
+
+        spawn_timeshift_thread(self.me.clone(), Arc::downgrade(&created), receiver);
+
+        *timeshift = Some(created.clone());
+        Some(created)
+    }
+
+    /// Writes samples to the time-shift buffer, and advances the delayed consumers by the same amount.
+    fn write_timeshift(&self, timeshift: &Timeshift, samples: &[Sample], total_position: f32) {
+        let mut buffer = timeshift.buffer.lock();
+
+        if let Err(e) = buffer.push(samples, total_position) {
+            // The audio of delayed consumers would be missing, so they continue at live and the buffer is started over
+            self.timeshift.lock().take();
+            drop(buffer);
+
+            let delayed: Vec<_> = self.delayed.lock().drain(..).collect();
+            self.move_to_live(delayed);
+            self.report_timeshift_error(e);
+            return;
+        }
+
+        let mut delayed = self.delayed.lock();
+
+        for consumer in delayed.iter_mut() {
+            consumer.advance(&buffer, samples.len());
+        }
+
+        let (caught_up, behind): (Vec<_>, Vec<_>) = delayed
+            .drain(..)
+            .partition(|d| d.catch_up.is_some() && d.cursor >= buffer.written());
+
+        *delayed = behind;
+        self.move_to_live(caught_up);
+    }
+
+    /// Moves delayed consumers into groups, so they get the live samples from now on.
+    fn move_to_live(&self, consumers: Vec<DelayedProducer>) {
+        let mut groups = self.groups.lock();
+
+        for consumer in consumers {
+            let group = self.group_for(&mut groups, consumer.format);
+            group.producers.insert(consumer.id, consumer.producer);
+        }
+    }
+
+    fn report_timeshift_error(&self, error: io::Error) {
+        if let Some(listener) = self.timeshift_error_listener.lock().as_ref() {
+            listener(error);
+        }
+    }
+
+    /// Waits until everything pushed so far has been written to the time-shift buffer.
+    #[cfg(test)]
+    fn flush_timeshift(&self) {
+        let Some(timeshift) = self.timeshift.lock().clone() else {
+            return;
+        };
+
+        let (sender, receiver) = unbounded();
+        let _ = timeshift.sender.send(TimeshiftMessage::Flush(sender));
+        let _ = receiver.recv();
+    }
+}
+
+impl DelayedProducer {
+    /// Reads the next samples from the time-shift buffer, and pushes them to the consumer.
+    ///
+    /// * `amount` - The amount of samples to play, of which more are read when catching up.
+    fn advance(&mut self, timeshift: &TimeshiftBuffer, amount: usize) {
+        let frames = (amount / self.channel_count) as f32 * self.speed;
+        let mut samples = vec![0.; frames.round() as usize * self.channel_count];
+
+        // Reading starts at the oldest sample if the cursor fell out of the window
+        self.cursor = self.cursor.max(timeshift.oldest());
+
+        let read = timeshift
+            .read(self.cursor, &mut samples)
+            .unwrap_or_default();
+        samples.truncate(read);
+        self.cursor += read as u64;
+
+        let samples = match &mut self.catch_up {
+            Some(resampler) => resampler.push(&samples),
+            None => samples,
+        };
+
+        let converted = self.converter.convert(&samples);
+        self.producer
+            .push(&converted, timeshift.position_at(self.cursor));
+    }
+}
+
+impl ConsumerGroup {
+    fn new(config: &Config, format: ConversionFormat) -> Self {
+        Self {
+            format,
+            config: format.apply(config),
+            converter: Converter::new(config, format),
+            preload_cache: Default::default(),
+            producers: Default::default(),
+        }
+    }
+
+    /// Converts the samples once, and pushes them to every consumer in the group.
+    fn push(&mut self, samples: &[Sample], total_position: f32) {
+        let converted = self.push_preload(samples);
+
+        for producer in self.producers.values() {
+            producer.push(&converted, total_position);
+        }
+    }
+
+    /// Converts the samples and pushes them to the preload cache, returning the converted samples.
+    fn push_preload(&mut self, samples: &[Sample]) -> Vec<Sample> {
+        let converted = self.converter.convert(samples);
+        extend_preload_cache(&mut self.preload_cache, &converted, &self.config);
+
+        converted
+    }
+}
+
+/// Extends a preload cache, dropping the oldest samples if it grows past the preload size.
+fn extend_preload_cache(preload_cache: &mut Vec<Sample>, samples: &[Sample], config: &Config) {
+    preload_cache.extend_from_slice(samples);
+
+    let preload_size = config.stream_preload_cache_size();
+
+    // Only drop whole frames, so the channels stay in order
+    let amount_overflowing = preload_cache
+        .len()
+        .saturating_sub(preload_size)
+        .next_multiple_of(config.channel_count);
+
+    if amount_overflowing > 0 {
+        preload_cache.drain(..amount_overflowing);
+    }
+}
+
+/// Writes the samples sent to a time-shift buffer, until the buffer or its stream is gone.
+fn spawn_timeshift_thread(
+    stream: Weak<Stream>,
+    timeshift: Weak<Timeshift>,
+    receiver: Receiver<TimeshiftMessage>,
+) {
+    let run = move || {
+        while let Ok(message) = receiver.recv() {
+            let (Some(stream), Some(timeshift)) = (stream.upgrade(), timeshift.upgrade()) else {
+                break;
+            };
+
+            match message {
+                TimeshiftMessage::Push(samples, total_position) => {
+                    stream.write_timeshift(&timeshift, &samples, total_position)
+                }
+                #[cfg(test)]
+                TimeshiftMessage::Flush(sender) => {
+                    let _ = sender.send(());
+                }
+            }
+        }
+    };
+
+    thread::spawn(run);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    /// Writes the samples as little-endian floats.
+    struct FloatEncoder {
+        bytes: Vec<u8>,
+    }
+
+    impl Encoder for FloatEncoder {
+        fn new(_config: Config, _options: EncoderOptions) -> Self {
+            Self { bytes: Vec::new() }
+        }
+
+        fn encode(&mut self, samples: &[Sample]) {
+            self.bytes
+                .extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+        }
+
+        fn content_type(&self) -> String {
+            "audio/float".to_string()
+        }
+    }
+
+    impl Read for FloatEncoder {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let amount = buf.len().min(self.bytes.len());
+            buf[..amount].copy_from_slice(&self.bytes[..amount]);
+            self.bytes.drain(..amount);
+
+            Ok(amount)
+        }
+    }
+
+    fn config(sample_rate: usize) -> Config {
+        Config {
+            sample_rate,
+            channel_count: 1,
+            stream_preload_cache_size_in_seconds: 0.,
+            stream_timeshift_window_in_seconds: 10.,
+            ..Default::default()
+        }
+    }
+
+    fn read_samples(consumer: &Consumer) -> Vec<Sample> {
+        let mut bytes = vec![0; 1024];
+        let amount = consumer.read_available(&mut bytes).unwrap();
+
+        bytes[..amount]
+            .chunks_exact(4)
+            .map(|b| Sample::from_le_bytes(b.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_timeshift_is_created_lazily() {
+        let stream = Stream::new(config(10));
+
+        stream.push(&[0.; 10], 10);
+        assert!(stream.timeshift.lock().is_none(), "nothing needs it yet");
+
+        let options = EncoderOptions {
+            delay: Some(2),
+            ..Default::default()
+        };
+
+        let _consumer = stream.consume::<FloatEncoder>(options);
+        assert!(stream.timeshift.lock().is_some());
+    }
+
+    #[test]
+    fn test_delayed_consumer() {
+        let stream = Stream::new(config(10));
+
+        // Capturing starts the buffer, like the first delayed consumer would
+        stream.capture(0.);
+
+        for second in 0..3 {
+            let samples: Vec<_> = (second * 10..(second + 1) * 10).map(|s| s as f32).collect();
+            stream.push(&samples, (second + 1) * 10);
+        }
+
+        stream.flush_timeshift();
+
+        let options = EncoderOptions {
+            delay: Some(2),
+            ..Default::default()
+        };
+
+        let consumer = stream.consume::<FloatEncoder>(options);
+        let live = stream.consume::<FloatEncoder>(Default::default());
+
+        stream.push(&[30.; 10], 40);
+        stream.flush_timeshift();
+
+        let expected: Vec<_> = (10..20).map(|s| s as f32).collect();
+
+        assert_eq!(
+            read_samples(&consumer),
+            expected,
+            "plays two seconds behind"
+        );
+        assert_eq!(consumer.total_position(), 2., "position is from the past");
+        assert_eq!(read_samples(&live), [30.; 10]);
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let stream = Stream::new(Config {
+            stream_catch_up_speed: 2.,
+            ..config(1000)
+        });
+
+        stream.capture(0.);
+        stream.push(&[0.; 3000], 3000);
+        stream.flush_timeshift();
+
+        let options = EncoderOptions {
+            delay: Some(2),
+            catch_up: Some(true),
+            ..Default::default()
+        };
+
+        let consumer = stream.consume::<FloatEncoder>(options);
+
+        // Reading twice as fast closes the gap of 2000 samples after 20 pushes
+        for _ in 0..25 {
+            stream.push(&[0.; 100], 0);
+        }
+
+        stream.flush_timeshift();
+
+        assert!(stream.delayed.lock().is_empty(), "consumer caught up");
+        assert!(
+            stream.groups.lock()[0].producers.contains_key(&consumer.id),
+            "consumer gets live samples"
+        );
+    }
+
+    #[test]
+    fn test_capture() {
+        let stream = Stream::new(config(10));
+
+        assert_eq!(stream.capture(1.), Some(vec![]));
+
+        let samples: Vec<_> = (0..30).map(|s| s as f32).collect();
+        stream.push(&samples, 30);
+        stream.flush_timeshift();
+
+        let expected: Vec<_> = (15..30).map(|s| s as f32).collect();
+        assert_eq!(stream.capture(1.5), Some(expected));
+
+        assert_eq!(
+            stream.capture(60.).map(|s| s.len()),
+            Some(30),
+            "longer than the stream"
+        );
+
+        let disabled = Stream::new(Config {
+            stream_timeshift_window_in_seconds: 0.,
+            ..config(10)
+        });
+
+        assert_eq!(disabled.capture(1.), None);
     }
 }
@@ -0,0 +1,219 @@
+use std::{
+    collections::VecDeque,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{Config, Sample};
+
+/// A rolling window of the samples pushed to a [Stream](super::Stream), so consumers can start behind live.
+///
+/// The samples are kept in a temporary file, since a window of several minutes would take up a lot of memory.
+pub struct TimeshiftBuffer {
+    file: File,
+    /// The amount of samples the window holds.
+    capacity: u64,
+    /// The amount of samples written since the buffer was created.
+    written: u64,
+    /// The amount of samples written at the end of every push, along with the total position of the player there.
+    positions: VecDeque<(u64, f32)>,
+}
+
+impl TimeshiftBuffer {
+    /// Creates a buffer that holds the time-shift window of the config.
+    pub fn new(config: &Config) -> io::Result<Self> {
+        static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+
+        let capacity = config.stream_timeshift_window_size() as u64;
+
+        let path = env::temp_dir().join(format!(
+            "turntable-timeshift-{}-{}.raw",
+            process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+
+        // The open file can still be used, and the space is freed as soon as it's dropped.
+        // This fails on Windows, where the file stays until the temporary directory is cleaned.
+        let _ = fs::remove_file(&path);
+
+        Ok(Self {
+            file,
+            capacity,
+            written: 0,
+            positions: VecDeque::new(),
+        })
+    }
+
+    /// The amount of samples written since the buffer was created, which is the index of the live edge.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// The index of the oldest sample that can still be read.
+    pub fn oldest(&self) -> u64 {
+        self.written.saturating_sub(self.capacity)
+    }
+
+    /// Appends samples to the window, overwriting the oldest ones if it's full.
+    ///
+    /// * `total_position` - The total position of the player at the end of the samples, in seconds.
+    pub fn push(&mut self, samples: &[Sample], total_position: f32) -> io::Result<()> {
+        // Only the end of the samples fits if there are more than the window holds
+        let skipped = samples.len().saturating_sub(self.capacity as usize);
+        let mut index = self.written + skipped as u64;
+
+        for piece in self.pieces(index, samples.len() - skipped) {
+            let start = (index - self.written) as usize;
+            let bytes: Vec<_> = samples[start..start + piece.1]
+                .iter()
+                .flat_map(|s| s.to_le_bytes())
+                .collect();
+
+            self.file.seek(SeekFrom::Start(piece.0))?;
+            self.file.write_all(&bytes)?;
+
+            index += piece.1 as u64;
+        }
+
+        self.written += samples.len() as u64;
+        self.positions.push_back((self.written, total_position));
+
+        let oldest = self.oldest();
+
+        while self.positions.front().is_some_and(|(end, _)| *end < oldest) {
+            self.positions.pop_front();
+        }
+
+        Ok(())
+    }
+
+    /// Reads samples starting at the given index, returning how many were read.
+    ///
+    /// Reading starts at the oldest sample if the index fell out of the window already.
+    pub fn read(&self, from: u64, buf: &mut [Sample]) -> io::Result<usize> {
+        let from = from.max(self.oldest());
+        let amount = buf.len().min(self.written.saturating_sub(from) as usize);
+
+        let mut file = &self.file;
+        let mut read = 0;
+
+        for (file_offset, length) in self.pieces(from, amount) {
+            let mut bytes = vec![0; length * Config::SAMPLES_IN_BYTES];
+
+            file.seek(SeekFrom::Start(file_offset))?;
+            file.read_exact(&mut bytes)?;
+
+            for (sample, bytes) in buf[read..read + length]
+                .iter_mut()
+                .zip(bytes.chunks_exact(Config::SAMPLES_IN_BYTES))
+            {
+                *sample = Sample::from_le_bytes(bytes.try_into().expect("chunk is a sample"));
+            }
+
+            read += length;
+        }
+
+        Ok(read)
+    }
+
+    /// Returns the total position of the player at the given index, in seconds.
+    pub fn position_at(&self, index: u64) -> f32 {
+        self.positions
+            .iter()
+            .find(|(end, _)| *end >= index)
+            .or(self.positions.back())
+            .map(|(_, position)| *position)
+            .unwrap_or_default()
+    }
+
+    /// Splits a range of samples into the byte offsets and lengths it takes up in the file, wrapping around its end.
+    fn pieces(&self, from: u64, amount: usize) -> Vec<(u64, usize)> {
+        if self.capacity == 0 || amount == 0 {
+            return vec![];
+        }
+
+        let start = (from % self.capacity) as usize;
+        let until_end = self.capacity as usize - start;
+        let to_bytes = |index: usize| (index * Config::SAMPLES_IN_BYTES) as u64;
+
+        if amount <= until_end {
+            return vec![(to_bytes(start), amount)];
+        }
+
+        vec![(to_bytes(start), until_end), (0, amount - until_end)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(capacity_in_samples: usize) -> TimeshiftBuffer {
+        let config = Config {
+            sample_rate: capacity_in_samples,
+            channel_count: 1,
+            stream_timeshift_window_in_seconds: 1.,
+            ..Default::default()
+        };
+
+        TimeshiftBuffer::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_wraps_around() {
+        let mut buffer = buffer(4);
+
+        buffer.push(&[1., 2., 3.], 1.).unwrap();
+        buffer.push(&[4., 5., 6.], 2.).unwrap();
+
+        assert_eq!(buffer.written(), 6);
+        assert_eq!(buffer.oldest(), 2);
+
+        let mut buf = [0.; 8];
+        let amount = buffer.read(0, &mut buf).unwrap();
+
+        assert_eq!(
+            &buf[..amount],
+            [3., 4., 5., 6.],
+            "reads from the oldest sample"
+        );
+
+        let amount = buffer.read(5, &mut buf).unwrap();
+        assert_eq!(&buf[..amount], [6.]);
+    }
+
+    #[test]
+    fn test_push_larger_than_window() {
+        let mut buffer = buffer(2);
+
+        buffer.push(&[1., 2., 3., 4., 5.], 1.).unwrap();
+
+        let mut buf = [0.; 2];
+        buffer.read(0, &mut buf).unwrap();
+
+        assert_eq!(buf, [4., 5.]);
+    }
+
+    #[test]
+    fn test_position_at() {
+        let mut buffer = buffer(100);
+
+        buffer.push(&[0.; 10], 1.).unwrap();
+        buffer.push(&[0.; 10], 2.).unwrap();
+
+        assert_eq!(buffer.position_at(5), 1.);
+        assert_eq!(buffer.position_at(10), 1.);
+        assert_eq!(buffer.position_at(15), 2.);
+        assert_eq!(buffer.position_at(50), 2.);
+    }
+}
@@ -6,12 +6,17 @@ use std::{
 use tokio::time::sleep;
 
 mod player;
+mod preview;
 mod timeline;
 
 pub use player::*;
+pub use preview::*;
 pub use timeline::*;
 
-use crate::{get_or_create_handle, Config, Ingestion, Output, PipelineContext};
+use crate::{
+    get_or_create_handle, Config, EncoderOptions, Ingestion, Output, PipelineContext,
+    RegisteredEncoder, Sink,
+};
 
 /// The playback type is responsible for managing players, processing playback, and preloading sinks as needed.
 pub struct Playback {
@@ -43,6 +48,39 @@ impl Playback {
 
         context
     }
+
+    /// Creates a private player that only plays the given sink from the given offset, and consumes it.
+    ///
+    /// Returns `None` if the sink is already being played, since a sink can only be in one timeline.
+    pub fn create_preview(
+        &self,
+        sink: Arc<Sink>,
+        offset: usize,
+        encoder: &RegisteredEncoder,
+        options: EncoderOptions,
+    ) -> Option<Preview> {
+        if sink.is_guarded() {
+            return None;
+        }
+
+        let player = Player::new(&self.context, self.output.clone());
+        let context = player.context();
+
+        player.set_sinks(vec![sink]);
+        player.seek(offset);
+
+        self.output.register_preview_player(player.id);
+        self.context.players.insert(player.id, player.into());
+
+        let consumer = self.output.consume_player_as(context.id, encoder, options);
+
+        Some(Preview::new(
+            context,
+            consumer,
+            &self.context,
+            self.output.clone(),
+        ))
+    }
 }
 
 fn spawn_processing_thread(context: &PipelineContext) {
@@ -70,14 +70,16 @@ impl Player {
 
         // If the player is not supposed to play, we just push silence.
         if !self.should_play.load() {
-            self.output.push(self.id, samples);
+            self.output
+                .push(self.id, samples, self.timeline.total_offset());
             self.set_state_if_different(PlayerState::Idle);
 
             return;
         }
 
-        // Get the current sink before advancing the timeline.
+        // Get the current sink and offset before advancing the timeline.
         let current_sink = self.timeline.current_sink();
+        let start_offset = self.timeline.total_offset();
         let reads = self.timeline.advance(samples.len());
 
         // If this new sink is different, we can be sure that we advanced to the next sink.
@@ -97,6 +99,14 @@ impl Player {
             self.set_state_if_different(PlayerState::Playing);
         }
 
+        // The total offset the new sink starts at, which is after everything read before it.
+        let new_sink_offset = start_offset
+            + reads
+                .iter()
+                .take_while(|r| Some(r.sink_id) != new_sink)
+                .map(|r| r.amount)
+                .sum::<usize>();
+
         for read in reads {
             let slice = &mut samples[amount_read..];
 
@@ -111,22 +121,25 @@ impl Player {
         }
 
         if new_sink != current_sink && current_sink.is_some() {
-            self.advance_queue_if_exists()
+            self.advance_queue_if_exists(new_sink_offset)
         }
 
         // Emit the current time and total time.
         if !was_empty {
-            let current_time = self.timeline.current_offset();
-            let current_total_time = self.timeline.total_offset();
+            let config = &self.context.config;
+            let position = config.samples_to_seconds(self.timeline.current_offset());
+            let total_position = config.samples_to_seconds(self.timeline.total_offset());
 
             self.context.emit(PipelineEvent::PlayerTimeUpdate {
                 player_id: self.id,
-                position: self.context.config.samples_to_seconds(current_time),
-                total_position: self.context.config.samples_to_seconds(current_total_time),
+                position,
+                total_position,
+                heard_positions: self.output.heard_positions(position, total_position),
             })
         }
 
-        self.output.push(self.id, samples);
+        self.output
+            .push(self.id, samples, self.timeline.total_offset());
     }
 
     /// Clears samples that are not needed, to save memory.
@@ -149,6 +162,14 @@ impl Player {
         self.timeline.seek(offset);
     }
 
+    /// Skips the current sink and advances the queue.
+    ///
+    /// This is the only way past sinks of unknown length, such as live streams, since they never end.
+    pub fn skip(&self) {
+        self.timeline.skip();
+        self.advance_queue_if_exists(self.timeline.total_offset());
+    }
+
     /// Returns the context for this player.
     pub fn context(&self) -> PlayerContext {
         PlayerContext {
@@ -171,7 +192,9 @@ impl Player {
     }
 
     /// Advances the queue associated with this player if it exists.
-    fn advance_queue_if_exists(&self) {
+    ///
+    /// * `total_offset` - The total offset the next sink started playing at.
+    fn advance_queue_if_exists(&self, total_offset: usize) {
         let queue = self.context.queues.get(&self.id);
 
         if let Some(queue) = queue {
@@ -179,8 +202,10 @@ impl Player {
             queue.next();
 
             // Emit an event to notify that the player has advanced.
-            self.context
-                .emit(PipelineEvent::PlayerAdvanced { player_id: self.id });
+            self.context.emit(PipelineEvent::PlayerAdvanced {
+                player_id: self.id,
+                total_position: self.context.config.samples_to_seconds(total_offset),
+            });
         }
     }
 }
@@ -207,6 +232,12 @@ impl PlayerContext {
         });
     }
 
+    /// Skips to the next item in the queue.
+    pub fn skip(&self) {
+        self.context
+            .dispatch(PipelineAction::SkipPlayer { player_id: self.id });
+    }
+
     /// Returns the current position in seconds.
     pub fn current_time(&self) -> f32 {
         self.context
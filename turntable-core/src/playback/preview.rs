@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use crate::{Consumer, Output, PipelineContext, PlayerContext, PlayerId};
+
+/// A private player for a single sink, with its own [Timeline](super::Timeline) and stream.
+///
+/// It plays independently of any queue, and is removed along with its stream when dropped.
+pub struct Preview {
+    pub player: PlayerContext,
+    consumer: Arc<Consumer>,
+    context: PipelineContext,
+    output: Arc<Output>,
+}
+
+impl Preview {
+    pub(super) fn new(
+        player: PlayerContext,
+        consumer: Consumer,
+        context: &PipelineContext,
+        output: Arc<Output>,
+    ) -> Self {
+        Self {
+            player,
+            consumer: consumer.into(),
+            context: context.clone(),
+            output,
+        }
+    }
+
+    pub fn id(&self) -> PlayerId {
+        self.player.id
+    }
+
+    /// Returns the consumer of the preview stream.
+    pub fn consumer(&self) -> Arc<Consumer> {
+        self.consumer.clone()
+    }
+
+    /// Whether the sink played to its end and the consumer was given all of it, after which it only gets silence.
+    pub fn is_finished(&self) -> bool {
+        self.player.current_sink().is_none()
+            && self.consumer.total_position() >= self.player.current_total_time()
+    }
+}
+
+impl Drop for Preview {
+    fn drop(&mut self) {
+        // Dropping the player releases the guard of the sink, so it's cleared once it's inactive
+        self.context.players.remove(&self.player.id);
+        self.output.remove_player(self.player.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crossbeam::channel::unbounded;
+
+    use super::*;
+    use crate::{Config, Encoder, EncoderOptions, Player, Sample, Sink};
+
+    /// Discards the samples, since only the positions they were pushed at matter.
+    struct NullEncoder;
+
+    impl Encoder for NullEncoder {
+        fn new(_config: Config, _options: EncoderOptions) -> Self {
+            Self
+        }
+
+        fn encode(&mut self, _samples: &[Sample]) {}
+
+        fn content_type(&self) -> String {
+            "application/octet-stream".to_string()
+        }
+    }
+
+    impl Read for NullEncoder {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_preview_finishes_at_end_of_sink() {
+        // The player emits events while processing, so they need to be received somewhere
+        let (action_sender, _actions) = unbounded();
+        let (event_sender, _events) = unbounded();
+
+        let context = PipelineContext {
+            action_sender,
+            event_sender,
+            ..Default::default()
+        };
+
+        let output = Arc::new(Output::new(&context));
+
+        let length = context.config.buffer_size_in_samples() * 3;
+        let sink = Arc::new(Sink::new(&context, Some(length)));
+
+        context.sinks.insert(sink.id, sink.clone());
+        sink.write().write(0, &vec![0.5; length]);
+        sink.seal();
+
+        let player = Player::new(&context, output.clone());
+        player.set_sinks(vec![sink]);
+
+        output.register_preview_player(player.id);
+        let consumer = output.consume_player::<NullEncoder>(player.id, Default::default());
+        let preview = Preview::new(player.context(), consumer, &context, output.clone());
+
+        player.process();
+        assert!(!preview.is_finished(), "the sink is still playing");
+
+        // Processing past the end only pushes silence
+        for _ in 0..4 {
+            player.process();
+        }
+
+        let started = Instant::now();
+        while !preview.is_finished() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(
+            preview.consumer().total_position(),
+            context.config.samples_to_seconds(length)
+        );
+    }
+}
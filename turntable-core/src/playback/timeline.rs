@@ -95,9 +95,11 @@ impl Timeline {
             playback_offset = 0;
 
             // Let's break down the conditions for moving on to the next sink.
-            // 1. The sink is sealed/not loadable, meaning there won't be any more samples to load, and
-            // 2. There are no more remaining samples to read.
+            // 1. The sink is sealed/not loadable, meaning there won't be any more samples to load,
+            // 2. The sink isn't being recovered, which makes it loadable again, and
+            // 3. There are no more remaining samples to read.
             let should_move_on = !sink.can_load_more()
+                && !sink.is_recovering()
                 && available_until_void.distance.saturating_sub(amount_to_read) == 0;
 
             // Stop here if we're not moving on to the next sink.
@@ -175,6 +177,17 @@ impl Timeline {
         self.offset.store(0);
     }
 
+    /// Removes the current sink, so the next one starts playing from the beginning.
+    pub fn skip(&self) {
+        let mut sinks = self.sinks.lock();
+
+        if !sinks.is_empty() {
+            sinks.remove(0);
+        }
+
+        self.offset.store(0);
+    }
+
     /// Seeks to a specific offset in the timeline.
     pub fn seek(&self, offset: usize) {
         self.offset.store(offset);
@@ -0,0 +1,137 @@
+use crate::Sample;
+
+/// Converts samples to integer PCM of a given bit depth, with TPDF dither and optional noise shaping.
+///
+/// Samples beyond full scale are clamped. They're counted in the [Metrics](crate::Metrics) by the stream, since every consumer has a quantiser of its own.
+pub struct Quantiser {
+    /// The largest positive value at the bit depth.
+    max: i32,
+    channel_count: usize,
+    /// The channel of the next sample.
+    channel_index: usize,
+    noise_shaping: bool,
+    /// The quantisation error of the previous sample of each channel, fed back when noise shaping.
+    errors: Vec<Sample>,
+    /// The state of the xorshift generator used for dither.
+    random_state: u32,
+}
+
+impl Quantiser {
+    /// Creates a quantiser for interleaved samples with the given channel count.
+    pub fn new(bit_depth: u16, channel_count: usize) -> Self {
+        Self {
+            max: (1 << (bit_depth - 1)) - 1,
+            channel_count,
+            channel_index: 0,
+            noise_shaping: false,
+            errors: vec![0.; channel_count],
+            random_state: 0x9E37_79B9,
+        }
+    }
+
+    /// Enables first order noise shaping, which moves the dither noise towards higher, less audible frequencies.
+    pub fn with_noise_shaping(mut self, enabled: bool) -> Self {
+        self.noise_shaping = enabled;
+        self
+    }
+
+    /// Quantises interleaved samples.
+    pub fn quantise(&mut self, samples: &[Sample]) -> Vec<i32> {
+        samples.iter().map(|s| self.quantise_sample(*s)).collect()
+    }
+
+    /// Quantises a single sample.
+    fn quantise_sample(&mut self, sample: Sample) -> i32 {
+        let channel = self.channel_index;
+        self.channel_index = (self.channel_index + 1) % self.channel_count;
+
+        let max = self.max as Sample;
+        let min = -max - 1.;
+
+        let scaled = sample * max;
+        let did_clip = scaled > max || scaled < min;
+
+        let wanted = if self.noise_shaping {
+            scaled - self.errors[channel]
+        } else {
+            scaled
+        };
+
+        let dithered = wanted + self.tpdf();
+        let quantised = dithered.round().clamp(min, max);
+
+        // Feeding back the error of a clipped sample would only push the next ones further out
+        self.errors[channel] = if did_clip { 0. } else { quantised - wanted };
+
+        quantised as i32
+    }
+
+    /// Returns triangular dither noise between -1 and 1 least significant bits.
+    fn tpdf(&mut self) -> Sample {
+        self.uniform() + self.uniform() - 1.
+    }
+
+    /// Returns a uniformly distributed value between 0 and 1.
+    fn uniform(&mut self) -> Sample {
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random_state = x;
+
+        (x >> 8) as Sample / (1 << 24) as Sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_and_clipping() {
+        let mut quantiser = Quantiser::new(16, 1);
+        let quantised = quantiser.quantise(&[1.5, -1.5, 1., -1.]);
+
+        assert_eq!(quantised[0], i16::MAX as i32, "positive overs are clamped");
+        assert_eq!(quantised[1], i16::MIN as i32, "negative overs are clamped");
+        assert!(
+            quantised[2] >= i16::MAX as i32 - 1,
+            "full scale stays near the top"
+        );
+        assert!(
+            quantised[3] <= -(i16::MAX as i32) + 1,
+            "full scale stays near the bottom"
+        );
+    }
+
+    #[test]
+    fn test_dither_is_unbiased() {
+        let mut quantiser = Quantiser::new(16, 2);
+
+        // A value halfway between two steps should average out to itself
+        let sample = 100.5 / i16::MAX as Sample;
+        let quantised = quantiser.quantise(&vec![sample; 100_000]);
+
+        let mean = quantised.iter().map(|q| *q as f64).sum::<f64>() / quantised.len() as f64;
+        let spread = quantised.iter().max().unwrap() - quantised.iter().min().unwrap();
+
+        assert!((mean - 100.5).abs() < 0.05, "mean is {mean}");
+        assert!(spread <= 3, "dither stays within a couple of steps");
+    }
+
+    #[test]
+    fn test_noise_shaping_is_unbiased() {
+        let mut quantiser = Quantiser::new(24, 1).with_noise_shaping(true);
+
+        let sample = 0.25;
+        let quantised = quantiser.quantise(&vec![sample; 10_000]);
+
+        let expected = sample as f64 * ((1 << 23) - 1) as f64;
+        let mean = quantised.iter().map(|q| *q as f64).sum::<f64>() / quantised.len() as f64;
+
+        assert!(
+            (mean - expected).abs() < 0.05,
+            "mean is {mean}, expected {expected}"
+        );
+    }
+}
@@ -1,14 +1,16 @@
 mod queue;
 mod queue_item;
 
-use std::{sync::Arc, thread};
+use std::{sync::Arc, thread, time::Duration};
 
 use crossbeam::channel::{unbounded, Receiver, Sender};
 pub use queue::*;
 pub use queue_item::*;
+use tokio::time::sleep;
 
 use crate::{
     get_or_create_handle, Ingestion, PipelineAction, PipelineContext, PipelineEvent, PlayerId,
+    SinkId,
 };
 
 /// A type passed to a queue to allow it to notify the Pipeline that it changed.
@@ -36,6 +38,14 @@ impl QueueNotifier {
 pub struct Queuing {
     context: PipelineContext,
     sender: Sender<PlayerId>,
+    recovery_sender: Sender<SinkRecovery>,
+}
+
+/// A request to re-create the source of a sink that failed while loading.
+struct SinkRecovery {
+    sink_id: SinkId,
+    attempt: usize,
+    error: String,
 }
 
 impl Queuing {
@@ -45,10 +55,16 @@ impl Queuing {
     {
         let context = context.clone();
         let (sender, receiver) = unbounded();
+        let (recovery_sender, recovery_receiver) = unbounded();
 
-        spawn_update_task_thread(&context, ingestion, receiver);
+        spawn_update_task_thread(&context, ingestion.clone(), receiver);
+        spawn_recovery_thread(&context, ingestion, recovery_receiver);
 
-        Self { context, sender }
+        Self {
+            context,
+            sender,
+            recovery_sender,
+        }
     }
 
     /// Creates a new queue for a player.
@@ -73,6 +89,17 @@ impl Queuing {
     pub fn notify_queue_update(&self, player_id: PlayerId) {
         self.sender.send(player_id).unwrap();
     }
+
+    /// Re-creates the source of a sink that failed while loading, from the queue item it belongs to.
+    pub fn recover_sink(&self, sink_id: SinkId, attempt: usize, error: String) {
+        self.recovery_sender
+            .send(SinkRecovery {
+                sink_id,
+                attempt,
+                error,
+            })
+            .unwrap();
+    }
 }
 
 fn spawn_update_task_thread<I>(
@@ -95,6 +122,26 @@ fn spawn_update_task_thread<I>(
     thread::spawn(run);
 }
 
+fn spawn_recovery_thread<I>(
+    context: &PipelineContext,
+    ingestion: Arc<I>,
+    receiver: Receiver<SinkRecovery>,
+) where
+    I: Ingestion + 'static,
+{
+    let handle = get_or_create_handle();
+    let context = context.clone();
+
+    let run = move || loop {
+        let recovery = receiver.recv().unwrap();
+        let fut = recover_sink(&context, ingestion.clone(), recovery);
+
+        handle.block_on(fut)
+    };
+
+    thread::spawn(run);
+}
+
 /// Called when a queue is updated.
 async fn update_sinks<I>(context: &PipelineContext, ingestion: Arc<I>, player_id: PlayerId)
 where
@@ -168,3 +215,48 @@ where
 
     player.set_sinks(new_sinks);
 }
+
+/// Called when a sink fails while loading.
+async fn recover_sink<I>(context: &PipelineContext, ingestion: Arc<I>, recovery: SinkRecovery)
+where
+    I: Ingestion + 'static,
+{
+    let Some(sink) = context.sinks.get(&recovery.sink_id).map(|s| s.clone()) else {
+        return;
+    };
+
+    // Only sinks of queue items can be recovered, since the item is needed to re-create the source.
+    let owner = context.queues.iter().find_map(|queue| {
+        queue
+            .peek()
+            .into_iter()
+            .find(|item| item.sink_id() == Some(sink.id))
+            .map(|item| (*queue.key(), item))
+    });
+
+    let Some((player_id, item)) = owner else {
+        sink.error(recovery.error);
+        return;
+    };
+
+    context.emit(PipelineEvent::QueueItemRecoveryAttempt {
+        player_id,
+        item_id: item.item_id(),
+        sink_id: sink.id,
+        attempt: recovery.attempt,
+        error: recovery.error,
+    });
+
+    let delay = context.config.sink_recovery_delay_in_seconds * recovery.attempt as f32;
+    sleep(Duration::from_secs_f32(delay)).await;
+
+    let result = match item.loadable().await {
+        Ok(loadable) => ingestion.recover(sink.id, loadable).await,
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(()) => sink.recovered(),
+        Err(err) => sink.fail(err.to_string()),
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{Config, Sample};
+use rubato::{FftFixedInOut, Resampler};
+use std::error::Error;
+
+type FftResampler = FftFixedInOut<Sample>;
+
+/// Uninterleaves a chunk of samples into a vector where each sub-vector is a channel.
+pub fn uninterleave_samples(samples: Vec<Sample>, channels: usize) -> Vec<Vec<Sample>> {
+    let mut uninterleaved_samples = vec![];
+    let chunks: Vec<Vec<f32>> = samples.chunks_exact(channels).map(|c| c.to_vec()).collect();
+
+    for c in 0..channels {
+        let mut channel_samples = vec![];
+
+        for chunk in chunks.iter() {
+            channel_samples.push(chunk[c]);
+        }
+
+        uninterleaved_samples.push(channel_samples);
+    }
+
+    uninterleaved_samples
+}
+
+/// Interleaves vectors of channels into a single vector of samples
+pub fn interleave_samples(samples: Vec<Vec<Sample>>) -> Vec<Sample> {
+    if samples.is_empty() {
+        return vec![];
+    }
+    let channel_len = samples[0].len();
+    assert!(samples.iter().all(|channel| channel.len() == channel_len));
+
+    (0..channel_len)
+        .flat_map(|sample_index| samples.iter().map(move |channel| channel[sample_index]))
+        .collect()
+}
+
+/// A resampler that can take any length of samples as input
+pub struct DynamicResampler {
+    resampler: FftResampler,
+    /// Samples that didn't fill a whole chunk yet, used by [DynamicResampler::push].
+    pending: Vec<Sample>,
+    channel_count: usize,
+    source_sample_rate: usize,
+    target_sample_rate: usize,
+    did_remove_silence: bool,
+}
+
+impl DynamicResampler {
+    pub const CHUNK_SIZE: usize = 1024;
+
+    /// Creates a resampler that converts from the source sample rate to the pipeline's sample rate.
+    pub fn new(source_sample_rate: usize, config: &Config) -> Result<Self, Box<dyn Error>> {
+        Self::with_rates(source_sample_rate, config.sample_rate, config.channel_count)
+    }
+
+    /// Creates a resampler between any two sample rates.
+    pub fn with_rates(
+        source_sample_rate: usize,
+        target_sample_rate: usize,
+        channel_count: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let resampler = FftResampler::new(
+            source_sample_rate,
+            target_sample_rate,
+            Self::CHUNK_SIZE,
+            channel_count,
+        )?;
+
+        Ok(Self {
+            resampler,
+            pending: Vec::new(),
+            source_sample_rate,
+            channel_count,
+            target_sample_rate,
+            did_remove_silence: false,
+        })
+    }
+
+    /// Resamples the given interleaved samples, keeping any incomplete chunk until the next call.
+    ///
+    /// Use this for continuous streams where no samples may be lost.
+    pub fn push(&mut self, samples: &[Sample]) -> Vec<Sample> {
+        if self.target_sample_rate == self.source_sample_rate {
+            return samples.to_vec();
+        }
+
+        self.pending.extend_from_slice(samples);
+
+        let chunk_length = Self::CHUNK_SIZE * self.channel_count;
+        let whole_length = self.pending.len() / chunk_length * chunk_length;
+
+        if whole_length == 0 {
+            return vec![];
+        }
+
+        let chunks: Vec<_> = self.pending.drain(..whole_length).collect();
+        self.process(chunks)
+    }
+
+    /// Resamples the given interleaved samples.
+    ///
+    /// Note: Only whole chunks of [Self::CHUNK_SIZE] frames are processed, the remainder is discarded.
+    pub fn process(&mut self, samples: Vec<Sample>) -> Vec<Sample> {
+        // Don't do anything if it's not necessary
+        if self.target_sample_rate == self.source_sample_rate {
+            return samples;
+        }
+
+        let mut interleaved_result = vec![0f32; 0];
+        let chunked_channels: Vec<_> = uninterleave_samples(samples, self.channel_count)
+            .into_iter()
+            .map(|c| {
+                c.chunks_exact(Self::CHUNK_SIZE)
+                    .map(|c| c.to_vec())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        for chunk_index in 0..chunked_channels[0].len() {
+            let chunks: Vec<_> = chunked_channels
+                .iter()
+                .map(|channel| channel[chunk_index].to_owned())
+                .collect();
+
+            let resampled = self
+                .resampler
+                .process(&chunks, None)
+                .expect("processes without issue");
+
+            let interleaved = interleave_samples(resampled);
+            interleaved_result.extend_from_slice(&interleaved)
+        }
+
+        if !self.did_remove_silence {
+            self.did_remove_silence = true;
+
+            let silence_in_samples = self.resampler.output_delay() * self.channel_count;
+
+            return interleaved_result
+                .into_iter()
+                .skip(silence_in_samples)
+                .collect();
+        }
+
+        interleaved_result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uninterleave_samples() {
+        let samples = vec![1., 2., 3., 4., 5., 6.];
+        let result = uninterleave_samples(samples, 2);
+
+        assert_eq!(result, vec![vec![1., 3., 5.], vec![2., 4., 6.]])
+    }
+
+    #[test]
+    fn test_interleave_samples() {
+        let samples = vec![vec![1., 3., 5.], vec![2., 4., 6.]];
+        let result = interleave_samples(samples);
+
+        assert_eq!(result, vec![1., 2., 3., 4., 5., 6.]);
+    }
+}
@@ -1,3 +1,20 @@
+mod ogg_writer;
 mod wave_encoder;
 
+#[cfg(feature = "opus")]
+mod ogg_opus_encoder;
+
+#[cfg(feature = "mp3")]
+mod mp3_encoder;
+
+#[cfg(any(feature = "mp3", test))]
+mod mp3_frames;
+
+pub use ogg_writer::*;
 pub use wave_encoder::*;
+
+#[cfg(feature = "opus")]
+pub use ogg_opus_encoder::*;
+
+#[cfg(feature = "mp3")]
+pub use mp3_encoder::*;
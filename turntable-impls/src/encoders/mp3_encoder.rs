@@ -0,0 +1,191 @@
+use super::mp3_frames::Mp3FrameSplitter;
+use mp3lame_encoder::{Bitrate, Builder, Encoder as LameEncoder, InterleavedPcm, Quality, VbrMode};
+use parking_lot::Mutex;
+use std::io::Read;
+use turntable_core::{assign_slice, Config, Encoder, EncoderOptions, Quantiser, Sample};
+
+/// Encodes [Sample]s into an MP3 stream, using LAME.
+///
+/// Only whole frames are released, so the output always starts and ends on a frame sync.
+pub struct Mp3Encoder {
+    // The LAME encoder is not [Sync], even though it is only ever used through a mutable reference
+    lame: Mutex<LameEncoder>,
+    splitter: Mp3FrameSplitter,
+    quantiser: Quantiser,
+    channel_count: usize,
+    bytes: Vec<u8>,
+}
+
+impl Mp3Encoder {
+    const DEFAULT_BITRATE: u32 = 192_000;
+    const DEFAULT_COMPLEXITY: u8 = 7;
+
+    /// The samples per channel in an MPEG-1 layer III frame.
+    const FRAME_SIZE: usize = 1152;
+
+    /// The delay LAME adds in samples per channel, made up of its 576 sample padding and the 529 sample decoder delay.
+    const ENCODER_DELAY: usize = 1105;
+
+    /// Returns the closest bitrate supported by MPEG-1 layer III that is not above the requested one.
+    fn bitrate(bits_per_second: u32) -> Bitrate {
+        match bits_per_second / 1000 {
+            ..=39 => Bitrate::Kbps32,
+            40..=47 => Bitrate::Kbps40,
+            48..=63 => Bitrate::Kbps48,
+            64..=79 => Bitrate::Kbps64,
+            80..=95 => Bitrate::Kbps80,
+            96..=111 => Bitrate::Kbps96,
+            112..=127 => Bitrate::Kbps112,
+            128..=159 => Bitrate::Kbps128,
+            160..=191 => Bitrate::Kbps160,
+            192..=223 => Bitrate::Kbps192,
+            224..=255 => Bitrate::Kbps224,
+            256..=319 => Bitrate::Kbps256,
+            _ => Bitrate::Kbps320,
+        }
+    }
+
+    /// Maps a complexity from 0 to 10 onto the LAME quality presets, where higher complexity means better quality.
+    fn quality(complexity: u8) -> Quality {
+        match complexity {
+            0 => Quality::Worst,
+            1 => Quality::SecondWorst,
+            2 => Quality::Ok,
+            3 => Quality::Decent,
+            4 => Quality::Good,
+            5 => Quality::Nice,
+            6 => Quality::VeryNice,
+            7 => Quality::NearBest,
+            8 | 9 => Quality::SecondBest,
+            _ => Quality::Best,
+        }
+    }
+}
+
+impl Encoder for Mp3Encoder {
+    fn new(config: Config, options: EncoderOptions) -> Self
+    where
+        Self: Sized,
+    {
+        let mut builder = Builder::new().expect("lame builder is created");
+
+        builder
+            .set_num_channels(config.channel_count as u8)
+            .expect("channel count is supported");
+
+        builder
+            .set_sample_rate(config.sample_rate as u32)
+            .expect("sample rate is supported");
+
+        let bitrate = Self::bitrate(options.bitrate.unwrap_or(Self::DEFAULT_BITRATE));
+        let quality = Self::quality(options.complexity.unwrap_or(Self::DEFAULT_COMPLEXITY));
+
+        builder.set_brate(bitrate).expect("bitrate is supported");
+        builder.set_quality(quality).expect("quality is supported");
+
+        if options.variable_bitrate.unwrap_or_default() {
+            builder
+                .set_vbr_mode(VbrMode::Mtrh)
+                .expect("vbr mode is supported");
+
+            builder
+                .set_vbr_quality(quality)
+                .expect("vbr quality is supported");
+
+            // A Xing header only makes sense for files with a known length
+            builder
+                .set_to_write_vbr_tag(false)
+                .expect("vbr tag is disabled");
+        }
+
+        let lame = builder.build().expect("lame encoder is created");
+
+        Self {
+            lame: Mutex::new(lame),
+            splitter: Mp3FrameSplitter::default(),
+            quantiser: Quantiser::new(16, config.channel_count)
+                .with_noise_shaping(options.noise_shaping.unwrap_or_default()),
+            channel_count: config.channel_count,
+            bytes: Vec::new(),
+        }
+    }
+
+    fn encode(&mut self, samples: &[Sample]) {
+        let pcm: Vec<_> = self
+            .quantiser
+            .quantise(samples)
+            .into_iter()
+            .map(|s| s as i16)
+            .collect();
+
+        let frames_per_channel = pcm.len() / self.channel_count;
+        let mut encoded = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(
+            frames_per_channel,
+        ));
+
+        let Ok(size) = self
+            .lame
+            .get_mut()
+            .encode(InterleavedPcm(&pcm), encoded.spare_capacity_mut())
+        else {
+            return;
+        };
+
+        // SAFETY: The encoder initialized this many bytes of the spare capacity
+        unsafe { encoded.set_len(size) };
+
+        let frames = self.splitter.push(&encoded);
+        self.bytes.extend_from_slice(&frames);
+    }
+
+    fn content_type(&self) -> String {
+        "audio/mpeg".to_string()
+    }
+
+    fn latency(config: &Config) -> f32 {
+        // A whole frame is collected before encoding, on top of the delay LAME adds to the start
+        (Self::FRAME_SIZE + Self::ENCODER_DELAY) as f32 / config.sample_rate as f32
+    }
+}
+
+impl Read for Mp3Encoder {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let amount = assign_slice(&self.bytes, buf);
+        self.bytes.drain(..amount);
+
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoders::mp3_frames::frame_length;
+
+    #[test]
+    fn test_output_starts_on_frame_sync() {
+        let config = Config::default();
+        let mut encoder = Mp3Encoder::new(config.clone(), EncoderOptions::default());
+
+        // A second of a quiet sine wave
+        let samples: Vec<_> = (0..config.sample_rate)
+            .flat_map(|i| {
+                let sample = (i as Sample * 0.05).sin() * 0.2;
+                vec![sample; config.channel_count]
+            })
+            .collect();
+
+        encoder.encode(&samples);
+
+        let mut bytes = vec![0; 1024 * 1024];
+        let amount = encoder.read(&mut bytes).unwrap();
+        let mut bytes = &bytes[..amount];
+
+        assert!(!bytes.is_empty(), "frames are released");
+
+        while !bytes.is_empty() {
+            let length = frame_length(bytes).expect("output is made up of whole frames");
+            bytes = &bytes[length..];
+        }
+    }
+}
@@ -0,0 +1,138 @@
+/// Splits a stream of MPEG audio layer III data into whole frames.
+///
+/// Bytes are held back until the frame they belong to is complete, so the output always ends on a frame boundary.
+#[derive(Debug, Default)]
+pub(crate) struct Mp3FrameSplitter {
+    buffer: Vec<u8>,
+}
+
+impl Mp3FrameSplitter {
+    const HEADER_SIZE: usize = 4;
+
+    /// Adds encoded bytes, and returns all frames that are now complete.
+    ///
+    /// Anything that is not part of a valid frame is skipped.
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<u8> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut frames = Vec::new();
+        let mut offset = 0;
+
+        while self.buffer.len() - offset >= Self::HEADER_SIZE {
+            let header = &self.buffer[offset..offset + Self::HEADER_SIZE];
+
+            let Some(length) = frame_length(header) else {
+                offset += 1;
+                continue;
+            };
+
+            if self.buffer.len() - offset < length {
+                break;
+            }
+
+            frames.extend_from_slice(&self.buffer[offset..offset + length]);
+            offset += length;
+        }
+
+        self.buffer.drain(..offset);
+        frames
+    }
+}
+
+/// Returns the length in bytes of the layer III frame starting with the given header,
+/// or `None` if it is not a valid header.
+pub(crate) fn frame_length(header: &[u8]) -> Option<usize> {
+    const MPEG1_BITRATES: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const MPEG2_BITRATES: [u32; 15] =
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    if header.len() < 4 || header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+
+    let version = (header[1] >> 3) & 0b11;
+    let layer = (header[1] >> 1) & 0b11;
+    let bitrate_index = (header[2] >> 4) as usize;
+    let sample_rate_index = ((header[2] >> 2) & 0b11) as usize;
+    let padding = ((header[2] >> 1) & 0b1) as usize;
+
+    // Only layer III is supported, and free format bitrates can't be sized from the header alone
+    if version == 0b01 || layer != 0b01 || bitrate_index == 0 || bitrate_index == 15 {
+        return None;
+    }
+
+    let sample_rates: [u32; 3] = match version {
+        0b11 => [44100, 48000, 32000],
+        0b10 => [22050, 24000, 16000],
+        _ => [11025, 12000, 8000],
+    };
+
+    let sample_rate = *sample_rates.get(sample_rate_index)?;
+
+    let (bitrate, samples_per_byte) = match version {
+        0b11 => (MPEG1_BITRATES[bitrate_index], 144),
+        _ => (MPEG2_BITRATES[bitrate_index], 72),
+    };
+
+    Some((samples_per_byte * bitrate * 1000 / sample_rate) as usize + padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MPEG-1 layer III, 128kbps, 44.1kHz, no padding
+    const HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+
+    /// Same as [HEADER], with the padding bit set
+    const PADDED_HEADER: [u8; 4] = [0xFF, 0xFB, 0x92, 0x00];
+
+    fn frame(header: [u8; 4]) -> Vec<u8> {
+        let mut frame = header.to_vec();
+        frame.resize(frame_length(&header).unwrap(), 0);
+
+        frame
+    }
+
+    #[test]
+    fn test_frame_length() {
+        assert_eq!(frame_length(&HEADER), Some(417));
+        assert_eq!(frame_length(&PADDED_HEADER), Some(418));
+
+        // MPEG-2 layer III, 64kbps, 24kHz
+        assert_eq!(frame_length(&[0xFF, 0xF3, 0x84, 0x00]), Some(192));
+
+        assert_eq!(frame_length(&[0xFF, 0xFB, 0xF0, 0x00]), None, "bad bitrate");
+        assert_eq!(
+            frame_length(&[0xFF, 0xFB, 0x9C, 0x00]),
+            None,
+            "bad sample rate"
+        );
+        assert_eq!(frame_length(&[0x00, 0xFB, 0x90, 0x00]), None, "no sync");
+    }
+
+    #[test]
+    fn test_splitter() {
+        let mut splitter = Mp3FrameSplitter::default();
+
+        let first = frame(HEADER);
+        let second = frame(PADDED_HEADER);
+
+        let mut stream = vec![0x12, 0x34];
+        stream.extend_from_slice(&first);
+        stream.extend_from_slice(&second);
+
+        let (a, b) = stream.split_at(300);
+
+        assert!(splitter.push(a).is_empty(), "incomplete frame is held back");
+        assert_eq!(
+            splitter.push(&b[..200]),
+            first,
+            "garbage is skipped and the first frame is released"
+        );
+        assert_eq!(splitter.push(&b[200..]), second, "second frame is released");
+        assert!(splitter.push(&[]).is_empty(), "nothing is left");
+    }
+}
@@ -0,0 +1,227 @@
+use super::OggWriter;
+use audiopus::{coder::Encoder as OpusCoder, Application, Bitrate, Channels, SampleRate};
+use parking_lot::Mutex;
+use std::io::{self, Read};
+use turntable_core::{remix_channels, Config, DynamicResampler, Encoder, EncoderOptions, Sample};
+
+/// Encodes [Sample]s into an Opus stream in an Ogg container, as described in RFC 7845.
+///
+/// Every consumer gets its own encoder, so a listener joining mid-stream still starts with the headers and a granule position of zero.
+pub struct OggOpusEncoder {
+    // The opus encoder is not [Sync], even though it is only ever used through a mutable reference
+    coder: Mutex<OpusCoder>,
+    writer: OggWriter,
+    /// Only present if the pipeline does not already run at 48kHz.
+    resampler: Option<DynamicResampler>,
+    /// The channel count of the samples that are encoded.
+    source_channel_count: usize,
+    /// The channel count of the stream, which is at most stereo.
+    channel_count: usize,
+    /// Samples at 48kHz waiting for a whole opus frame.
+    unencoded: Vec<Sample>,
+    /// The amount of samples per channel at 48kHz that have been encoded so far.
+    granule_position: u64,
+    /// Why encoding failed, which ends the stream once everything before it is read.
+    error: Option<String>,
+}
+
+impl OggOpusEncoder {
+    /// Opus always works at 48kHz internally, and the granule position is always counted at 48kHz.
+    const SAMPLE_RATE: usize = 48000;
+
+    /// 20ms at 48kHz, the recommended frame size for music.
+    const FRAME_SIZE: usize = 960;
+
+    /// The lookahead of the encoder at 48kHz, which is 6.5ms for every application but restricted low delay.
+    const LOOKAHEAD: usize = 312;
+
+    /// The largest packet recommended by the opus documentation.
+    const MAX_PACKET_SIZE: usize = 4000;
+
+    const DEFAULT_BITRATE: u32 = 128_000;
+
+    // Keep the default a bit lower than the maximum, since every consumer runs its own encoder
+    const DEFAULT_COMPLEXITY: u8 = 8;
+
+    fn opus_head(channel_count: u8, pre_skip: u16, input_sample_rate: u32) -> Vec<u8> {
+        let mut head = Vec::with_capacity(19);
+
+        head.extend_from_slice(b"OpusHead");
+        head.push(1); // Version
+        head.push(channel_count);
+        head.extend_from_slice(&pre_skip.to_le_bytes());
+        head.extend_from_slice(&input_sample_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+        head.push(0); // Channel mapping family, mono or stereo
+
+        head
+    }
+
+    fn opus_tags() -> Vec<u8> {
+        let vendor = concat!("turntable ", env!("CARGO_PKG_VERSION"));
+        let mut tags = Vec::new();
+
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor.as_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes()); // No user comments
+
+        tags
+    }
+
+    /// Encodes all whole frames that are waiting.
+    ///
+    /// A frame that fails to encode stops the encoder, since skipping it would leave a gap the granule position can't account for.
+    fn encode_frames(&mut self) {
+        let frame_length = Self::FRAME_SIZE * self.channel_count;
+        let mut packet = [0; Self::MAX_PACKET_SIZE];
+
+        while self.unencoded.len() >= frame_length {
+            let frame: Vec<_> = self.unencoded.drain(..frame_length).collect();
+
+            match self.coder.get_mut().encode_float(&frame, &mut packet) {
+                Ok(size) => {
+                    self.granule_position += Self::FRAME_SIZE as u64;
+                    self.writer
+                        .write_packet(&packet[..size], self.granule_position);
+                }
+                Err(e) => {
+                    self.error = Some(format!("Opus frame could not be encoded: {e}"));
+                    self.unencoded.clear();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Encoder for OggOpusEncoder {
+    fn new(config: Config, options: EncoderOptions) -> Self
+    where
+        Self: Sized,
+    {
+        // Opus streams are mono or stereo, so any further channels are mixed down to stereo
+        let channel_count = config.channel_count.clamp(1, 2);
+        let channels = match channel_count {
+            1 => Channels::Mono,
+            _ => Channels::Stereo,
+        };
+
+        let mut coder = OpusCoder::new(SampleRate::Hz48000, channels, Application::Audio)
+            .expect("opus encoder is created");
+
+        let bitrate = options
+            .bitrate
+            .unwrap_or(Self::DEFAULT_BITRATE)
+            .clamp(6_000, 510_000);
+
+        let complexity = options
+            .complexity
+            .unwrap_or(Self::DEFAULT_COMPLEXITY)
+            .min(10);
+
+        coder
+            .set_bitrate(Bitrate::BitsPerSecond(bitrate as i32))
+            .expect("bitrate is within bounds");
+
+        coder
+            .set_complexity(complexity)
+            .expect("complexity is within bounds");
+
+        let pre_skip = coder.lookahead().unwrap_or_default() as u16;
+
+        let resampler = (config.sample_rate != Self::SAMPLE_RATE).then(|| {
+            DynamicResampler::with_rates(config.sample_rate, Self::SAMPLE_RATE, channel_count)
+                .expect("resampler is created")
+        });
+
+        // The header packets each need their own page
+        let mut writer = OggWriter::new(rand::random());
+
+        writer.write_packet(
+            &Self::opus_head(channel_count as u8, pre_skip, config.sample_rate as u32),
+            0,
+        );
+        writer.flush();
+
+        writer.write_packet(&Self::opus_tags(), 0);
+        writer.flush();
+
+        Self {
+            coder: Mutex::new(coder),
+            writer,
+            resampler,
+            source_channel_count: config.channel_count,
+            channel_count,
+            unencoded: Vec::new(),
+            granule_position: 0,
+            error: None,
+        }
+    }
+
+    fn encode(&mut self, samples: &[Sample]) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let samples = remix_channels(samples, self.source_channel_count, self.channel_count);
+
+        match &mut self.resampler {
+            Some(resampler) => self.unencoded.extend(resampler.push(&samples)),
+            None => self.unencoded.extend(samples),
+        }
+
+        self.encode_frames();
+
+        // Flush a page per call so listeners don't wait on a full page
+        self.writer.flush();
+    }
+
+    fn content_type(&self) -> String {
+        "audio/ogg".to_string()
+    }
+
+    fn latency(_config: &Config) -> f32 {
+        // A whole frame is collected before encoding, and decoders skip the lookahead of the encoder
+        (Self::FRAME_SIZE + Self::LOOKAHEAD) as f32 / Self::SAMPLE_RATE as f32
+    }
+}
+
+impl Read for OggOpusEncoder {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amount = self.writer.read(buf)?;
+
+        match &self.error {
+            Some(error) if amount == 0 && !buf.is_empty() => Err(io::Error::other(error.clone())),
+            _ => Ok(amount),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_more_than_two_channels_are_mixed_down() {
+        let config = Config {
+            channel_count: 6,
+            sample_rate: 48000,
+            ..Default::default()
+        };
+
+        let mut encoder = OggOpusEncoder::new(config.clone(), EncoderOptions::default());
+        encoder.encode(&vec![0.1; config.seconds_to_samples(0.5)]);
+
+        let mut bytes = vec![];
+        encoder.read_to_end(&mut bytes).unwrap();
+
+        // The channel count of the identification header, which starts the first page after its 28 byte header
+        let head = &bytes[28..];
+        assert_eq!(&head[..8], b"OpusHead");
+        assert_eq!(head[9], 2);
+
+        assert!(encoder.error.is_none());
+        assert!(encoder.granule_position >= 960 * 20);
+    }
+}
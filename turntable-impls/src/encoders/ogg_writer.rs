@@ -0,0 +1,269 @@
+use std::io::Read;
+use turntable_core::assign_slice;
+
+/// Packs packets into Ogg pages, as described in RFC 3533.
+///
+/// Packets are collected into a page until [OggWriter::flush] is called, or until the page is full.
+/// Finished pages can be read from the writer.
+pub struct OggWriter {
+    serial: u32,
+    sequence: u32,
+    did_write_first_page: bool,
+    /// The lacing values of the page that is being built.
+    segments: Vec<u8>,
+    /// The packet data of the page that is being built.
+    body: Vec<u8>,
+    /// Whether the page that is being built starts with the remainder of a packet.
+    is_continued: bool,
+    /// The granule position of the last packet that finished on the page that is being built.
+    granule_position: Option<u64>,
+    /// Finished pages that haven't been read yet.
+    pages: Vec<u8>,
+}
+
+impl OggWriter {
+    const CAPTURE_PATTERN: &'static [u8; 4] = b"OggS";
+    const MAX_SEGMENTS: usize = 255;
+
+    const FLAG_CONTINUED: u8 = 0x01;
+    const FLAG_FIRST_PAGE: u8 = 0x02;
+
+    /// Creates a new writer for a logical bitstream with the given serial number.
+    pub fn new(serial: u32) -> Self {
+        Self {
+            serial,
+            sequence: 0,
+            did_write_first_page: false,
+            segments: Vec::new(),
+            body: Vec::new(),
+            is_continued: false,
+            granule_position: None,
+            pages: Vec::new(),
+        }
+    }
+
+    /// Adds a packet to the current page, finishing pages along the way if the packet does not fit.
+    ///
+    /// * `granule_position` - The granule position at the end of this packet.
+    pub fn write_packet(&mut self, packet: &[u8], granule_position: u64) {
+        let mut remaining = packet;
+
+        loop {
+            if self.segments.len() == Self::MAX_SEGMENTS {
+                self.finish_page();
+                self.is_continued = true;
+            }
+
+            let segment_length = remaining.len().min(255);
+
+            self.segments.push(segment_length as u8);
+            self.body.extend_from_slice(&remaining[..segment_length]);
+            remaining = &remaining[segment_length..];
+
+            // A lacing value below 255 marks the end of a packet.
+            if segment_length < 255 {
+                break;
+            }
+        }
+
+        self.granule_position = Some(granule_position);
+    }
+
+    /// Finishes the current page, if it contains anything.
+    pub fn flush(&mut self) {
+        if !self.segments.is_empty() {
+            self.finish_page();
+        }
+    }
+
+    /// Returns the amount of bytes of finished pages that are waiting to be read.
+    pub fn pending(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn finish_page(&mut self) {
+        let mut header_type = 0;
+
+        if self.is_continued {
+            header_type |= Self::FLAG_CONTINUED;
+        }
+
+        if !self.did_write_first_page {
+            header_type |= Self::FLAG_FIRST_PAGE;
+        }
+
+        // A granule position of -1 means no packet finishes on this page.
+        let granule_position = self.granule_position.map(|g| g as i64).unwrap_or(-1);
+
+        let mut page = Vec::with_capacity(27 + self.segments.len() + self.body.len());
+
+        page.extend_from_slice(Self::CAPTURE_PATTERN);
+        page.push(0); // Version
+        page.push(header_type);
+        page.extend_from_slice(&granule_position.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]); // Checksum, filled in below
+        page.push(self.segments.len() as u8);
+        page.extend_from_slice(&self.segments);
+        page.extend_from_slice(&self.body);
+
+        let checksum = crc32(&page);
+        page[22..26].copy_from_slice(&checksum.to_le_bytes());
+
+        self.pages.extend_from_slice(&page);
+
+        self.sequence += 1;
+        self.did_write_first_page = true;
+        self.is_continued = false;
+        self.granule_position = None;
+        self.segments.clear();
+        self.body.clear();
+    }
+}
+
+impl Read for OggWriter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let amount = assign_slice(&self.pages, buf);
+        self.pages.drain(..amount);
+
+        Ok(amount)
+    }
+}
+
+/// The lookup table for the CRC used by Ogg: polynomial 0x04c11db7, no reflection, no final XOR.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |crc, byte| {
+        (crc << 8) ^ CRC_TABLE[(((crc >> 24) as u8) ^ byte) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits a stream of pages into (header type, granule position, segment table, body).
+    fn parse_pages(mut bytes: &[u8]) -> Vec<(u8, i64, Vec<u8>, Vec<u8>)> {
+        let mut pages = vec![];
+
+        while !bytes.is_empty() {
+            assert_eq!(&bytes[..4], b"OggS", "page starts with capture pattern");
+
+            let segment_count = bytes[26] as usize;
+            let segments = bytes[27..27 + segment_count].to_vec();
+            let body_length: usize = segments.iter().map(|s| *s as usize).sum();
+            let page_length = 27 + segment_count + body_length;
+
+            let mut page = bytes[..page_length].to_vec();
+            let checksum = u32::from_le_bytes(page[22..26].try_into().unwrap());
+            page[22..26].copy_from_slice(&[0; 4]);
+
+            assert_eq!(crc32(&page), checksum, "checksum is valid");
+
+            pages.push((
+                bytes[5],
+                i64::from_le_bytes(bytes[6..14].try_into().unwrap()),
+                segments,
+                bytes[27 + segment_count..page_length].to_vec(),
+            ));
+
+            bytes = &bytes[page_length..];
+        }
+
+        pages
+    }
+
+    #[test]
+    fn test_crc32() {
+        // The CRC used by Ogg is the same as CRC-32/MPEG-2 without the initial value and final XOR,
+        // which makes the check value for "123456789" 0x89a1897f.
+        assert_eq!(crc32(b"123456789"), 0x89a1_897f);
+    }
+
+    #[test]
+    fn test_write_pages() {
+        let mut writer = OggWriter::new(1);
+
+        writer.write_packet(&[1; 10], 0);
+        writer.flush();
+
+        writer.write_packet(&[2; 300], 960);
+        writer.write_packet(&[3; 255], 1920);
+        writer.flush();
+
+        let mut bytes = vec![0; writer.pending()];
+        writer.read_exact(&mut bytes).unwrap();
+
+        let pages = parse_pages(&bytes);
+        assert_eq!(pages.len(), 2, "two pages are written");
+
+        let (header_type, granule, segments, body) = &pages[0];
+        assert_eq!(
+            *header_type,
+            OggWriter::FLAG_FIRST_PAGE,
+            "first page is marked"
+        );
+        assert_eq!(*granule, 0, "granule position is correct");
+        assert_eq!(segments, &[10], "segment table is correct");
+        assert_eq!(body, &[1; 10], "body is correct");
+
+        let (header_type, granule, segments, body) = &pages[1];
+        assert_eq!(*header_type, 0, "second page has no flags");
+        assert_eq!(*granule, 1920, "granule is the one of the last packet");
+        assert_eq!(
+            segments,
+            &[255, 45, 255, 0],
+            "packets that are a multiple of 255 end with an empty segment"
+        );
+        assert_eq!(body.len(), 300 + 255, "body contains both packets");
+    }
+
+    #[test]
+    fn test_continued_packet() {
+        let mut writer = OggWriter::new(1);
+
+        // Needs 256 segments, one more than a page can hold.
+        writer.write_packet(&[1; 255 * 255 + 10], 480);
+        writer.flush();
+
+        let mut bytes = vec![0; writer.pending()];
+        writer.read_exact(&mut bytes).unwrap();
+
+        let pages = parse_pages(&bytes);
+        assert_eq!(pages.len(), 2, "packet spans two pages");
+
+        assert_eq!(pages[0].1, -1, "no packet finishes on the first page");
+        assert_eq!(
+            pages[1].0,
+            OggWriter::FLAG_CONTINUED,
+            "second page is continued"
+        );
+        assert_eq!(pages[1].1, 480, "packet finishes on the second page");
+        assert_eq!(pages[1].2, &[10], "remainder is in the second page");
+    }
+}
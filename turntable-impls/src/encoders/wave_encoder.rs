@@ -1,11 +1,20 @@
 use std::io::Read;
-use turntable_core::{assign_slice, Config, Encoder, Sample};
+use turntable_core::{
+    assign_slice, Config, Encoder, EncoderOptions, Quantiser, Sample, SampleFormat,
+};
 
 /// Encodes [Sample]s into a .wav file
 pub struct WaveEncoder {
     did_write_header: bool,
     samples: Vec<Sample>,
     header: WaveHeader,
+    /// Converts samples for integer formats, or `None` when samples are written as floats.
+    quantiser: Option<Quantiser>,
+}
+
+/// Encodes [Sample]s into headerless little-endian PCM, for clients that are told the format some other way.
+pub struct PcmEncoder {
+    wave: WaveEncoder,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -19,7 +28,9 @@ enum WaveHeaderValue {
 struct WaveHeader {
     channel_count: u16,
     sample_rate: u32,
-    bit_depth: u16,
+    sample_format: SampleFormat,
+    /// The amount of sample bytes in the file, or `None` for a live stream of unknown length
+    data_size: Option<u32>,
 }
 
 impl WaveHeaderValue {
@@ -36,8 +47,11 @@ impl WaveHeader {
     // ChunkID: Contains the letters "RIFF" in ASCII form, change last number to 80 if "RIFX" is used
     const CHUNK_ID: WaveHeaderValue = WaveHeaderValue::Ascii("RIFF");
 
-    // This is set to max because vinyl is a live audio stream
-    const CHUNK_SIZE: WaveHeaderValue = WaveHeaderValue::FourBytes(i32::MAX as u32);
+    // This is set to max when the length is unknown, because turntable is a live audio stream
+    const UNKNOWN_SIZE: u32 = i32::MAX as u32;
+
+    // The size of the header after the chunk size, which is counted in the chunk size
+    const SIZE_AFTER_CHUNK_SIZE: u32 = 36;
 
     // Format: Contains the letters "WAVE"
     const FORMAT: WaveHeaderValue = WaveHeaderValue::Ascii("WAVE");
@@ -49,32 +63,50 @@ impl WaveHeader {
     const FMT_CHUNK_SIZE: WaveHeaderValue = WaveHeaderValue::FourBytes(16);
 
     // AudioFormat: PCM = 1
-    const AUDIO_FORMAT: WaveHeaderValue = WaveHeaderValue::TwoBytes(1);
+    const AUDIO_FORMAT_PCM: WaveHeaderValue = WaveHeaderValue::TwoBytes(1);
+
+    // AudioFormat: IEEE float = 3
+    const AUDIO_FORMAT_FLOAT: WaveHeaderValue = WaveHeaderValue::TwoBytes(3);
 
     // Subchunk2ID: Contains the letters "data"
     const DATA_CHUNK_ID: WaveHeaderValue = WaveHeaderValue::Ascii("data");
 
     fn to_bytes(&self) -> Vec<u8> {
+        let bit_depth = self.sample_format.bit_depth();
+
+        let audio_format = match self.sample_format {
+            SampleFormat::Float32 => Self::AUDIO_FORMAT_FLOAT,
+            _ => Self::AUDIO_FORMAT_PCM,
+        };
+
         let num_channels = WaveHeaderValue::TwoBytes(self.channel_count);
         let sample_rate = WaveHeaderValue::FourBytes(self.sample_rate);
 
         let byte_rate = WaveHeaderValue::FourBytes(
-            self.sample_rate * self.channel_count as u32 * self.bit_depth as u32 / 8,
+            self.sample_rate * self.channel_count as u32 * bit_depth as u32 / 8,
         );
 
-        let block_align = WaveHeaderValue::TwoBytes(self.channel_count * self.bit_depth / 8);
-        let bits_per_sample = WaveHeaderValue::TwoBytes(self.bit_depth);
+        let block_align = WaveHeaderValue::TwoBytes(self.channel_count * bit_depth / 8);
+        let bits_per_sample = WaveHeaderValue::TwoBytes(bit_depth);
 
-        // This is set to max because turntable is a live audio stream
-        let data_chunk_size = WaveHeaderValue::FourBytes(i32::MAX as u32);
+        let (chunk_size, data_chunk_size) = match self.data_size {
+            Some(size) => (
+                WaveHeaderValue::FourBytes(size.saturating_add(Self::SIZE_AFTER_CHUNK_SIZE)),
+                WaveHeaderValue::FourBytes(size),
+            ),
+            None => (
+                WaveHeaderValue::FourBytes(Self::UNKNOWN_SIZE),
+                WaveHeaderValue::FourBytes(Self::UNKNOWN_SIZE),
+            ),
+        };
 
         [
             Self::CHUNK_ID,
-            Self::CHUNK_SIZE,
+            chunk_size,
             Self::FORMAT,
             Self::FMT_CHUNK_ID,
             Self::FMT_CHUNK_SIZE,
-            Self::AUDIO_FORMAT,
+            audio_format,
             num_channels,
             sample_rate,
             byte_rate,
@@ -89,21 +121,51 @@ impl WaveHeader {
     }
 }
 
+impl WaveEncoder {
+    /// The size of the header in front of the samples, in bytes.
+    pub const HEADER_SIZE: usize = 44;
+
+    /// Returns the header of a finished file holding `data_size` bytes of samples.
+    ///
+    /// Streams are written with an unknown length, so this is used to correct the header once a file is complete.
+    pub fn header_for_size(config: &Config, options: &EncoderOptions, data_size: u32) -> Vec<u8> {
+        WaveHeader {
+            channel_count: config.channel_count as u16,
+            sample_rate: config.sample_rate as u32,
+            sample_format: options.sample_format.unwrap_or_default(),
+            data_size: Some(data_size),
+        }
+        .to_bytes()
+    }
+}
+
 impl Encoder for WaveEncoder {
-    fn new(config: Config) -> Self
+    fn new(config: Config, options: EncoderOptions) -> Self
     where
         Self: Sized,
     {
+        let sample_format = options.sample_format.unwrap_or_default();
+
         let header = WaveHeader {
             channel_count: config.channel_count as u16,
             sample_rate: config.sample_rate as u32,
-            bit_depth: 16,
+            sample_format,
+            data_size: None,
+        };
+
+        let quantiser = match sample_format {
+            SampleFormat::Float32 => None,
+            _ => Some(
+                Quantiser::new(sample_format.bit_depth(), config.channel_count)
+                    .with_noise_shaping(options.noise_shaping.unwrap_or_default()),
+            ),
         };
 
         Self {
             did_write_header: false,
             samples: Vec::new(),
             header,
+            quantiser,
         }
     }
 
@@ -129,17 +191,26 @@ impl Read for WaveEncoder {
 
         let body_buf = &mut buf[bytes_written..];
 
-        let amount_to_read = body_buf.len() / (Config::SAMPLES_IN_BYTES / 2);
+        let sample_format = self.header.sample_format;
+        let bytes_per_sample = sample_format.bit_depth() as usize / 8;
+
+        let amount_to_read = body_buf.len() / bytes_per_sample;
         let safe_end = self.samples.len().min(amount_to_read);
 
         let samples_to_read = &self.samples[..safe_end];
         let amount_of_samples = samples_to_read.len();
 
-        let samples_in_bytes: Vec<_> = samples_to_read
-            .iter()
-            .map(|s| (s * i16::MAX as Sample) as i16)
-            .flat_map(|s| s.to_le_bytes())
-            .collect();
+        let samples_in_bytes: Vec<_> = match &mut self.quantiser {
+            Some(quantiser) => quantiser
+                .quantise(samples_to_read)
+                .into_iter()
+                .flat_map(|s| integer_to_bytes(s, sample_format))
+                .collect(),
+            None => samples_to_read
+                .iter()
+                .flat_map(|s| s.to_le_bytes())
+                .collect(),
+        };
 
         assign_slice(&samples_in_bytes, body_buf);
         bytes_written += samples_in_bytes.len();
@@ -150,3 +221,162 @@ impl Read for WaveEncoder {
         Ok(bytes_written)
     }
 }
+
+impl Encoder for PcmEncoder {
+    fn new(config: Config, options: EncoderOptions) -> Self
+    where
+        Self: Sized,
+    {
+        let mut wave = WaveEncoder::new(config, options);
+        wave.did_write_header = true;
+
+        Self { wave }
+    }
+
+    fn encode(&mut self, samples: &[Sample]) {
+        self.wave.encode(samples)
+    }
+
+    fn content_type(&self) -> String {
+        "audio/pcm".to_string()
+    }
+}
+
+impl Read for PcmEncoder {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.wave.read(buf)
+    }
+}
+
+/// Converts a quantised sample to the little-endian bytes of the given integer format.
+fn integer_to_bytes(value: i32, format: SampleFormat) -> Vec<u8> {
+    let bytes_per_sample = format.bit_depth() as usize / 8;
+
+    value.to_le_bytes()[..bytes_per_sample].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_header(config: Config, sample_format: SampleFormat) -> Vec<u8> {
+        let options = EncoderOptions {
+            sample_format: Some(sample_format),
+            ..Default::default()
+        };
+
+        let mut encoder = WaveEncoder::new(config, options);
+        let mut header = vec![0; 44];
+        encoder.read_exact(&mut header).unwrap();
+
+        header
+    }
+
+    #[test]
+    fn test_header_reflects_format() {
+        let config = Config {
+            sample_rate: 22050,
+            channel_count: 1,
+            ..Default::default()
+        };
+
+        let header = read_header(config.clone(), SampleFormat::Int24);
+
+        assert_eq!(header[20..22], 1u16.to_le_bytes(), "audio format is PCM");
+        assert_eq!(header[22..24], 1u16.to_le_bytes(), "channel count");
+        assert_eq!(header[24..28], 22050u32.to_le_bytes(), "sample rate");
+        assert_eq!(header[28..32], (22050u32 * 3).to_le_bytes(), "byte rate");
+        assert_eq!(header[32..34], 3u16.to_le_bytes(), "block align");
+        assert_eq!(header[34..36], 24u16.to_le_bytes(), "bit depth");
+
+        let header = read_header(config, SampleFormat::Float32);
+
+        assert_eq!(header[20..22], 3u16.to_le_bytes(), "audio format is float");
+        assert_eq!(header[34..36], 32u16.to_le_bytes(), "bit depth");
+    }
+
+    #[test]
+    fn test_integer_to_bytes() {
+        assert_eq!(
+            integer_to_bytes(i16::MAX as i32, SampleFormat::Int16),
+            i16::MAX.to_le_bytes()
+        );
+        assert_eq!(
+            integer_to_bytes(-(1 << 23) + 1, SampleFormat::Int24),
+            [0x01, 0x00, 0x80]
+        );
+    }
+
+    #[test]
+    fn test_body_is_quantised() {
+        let config = Config {
+            channel_count: 1,
+            ..Default::default()
+        };
+
+        let mut encoder = WaveEncoder::new(config.clone(), EncoderOptions::default());
+        encoder.encode(&[2., -2., 0.5]);
+
+        let mut bytes = vec![0; 44 + 6];
+        encoder.read_exact(&mut bytes).unwrap();
+
+        let body: Vec<_> = bytes[44..]
+            .chunks(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+
+        assert_eq!(body[0], i16::MAX, "overs are clamped");
+        assert_eq!(body[1], i16::MIN, "overs are clamped");
+        assert!(
+            (body[2] as i32 - i16::MAX as i32 / 2).abs() <= 2,
+            "dither stays within a couple of steps"
+        );
+
+        let mut encoder = WaveEncoder::new(
+            config,
+            EncoderOptions {
+                sample_format: Some(SampleFormat::Float32),
+                ..Default::default()
+            },
+        );
+
+        encoder.encode(&[2.]);
+
+        let mut bytes = vec![0; 44 + 4];
+        encoder.read_exact(&mut bytes).unwrap();
+
+        assert_eq!(bytes[44..], 2f32.to_le_bytes(), "floats are left untouched");
+    }
+
+    #[test]
+    fn test_pcm_has_no_header() {
+        let config = Config {
+            channel_count: 1,
+            ..Default::default()
+        };
+
+        let options = EncoderOptions {
+            sample_format: Some(SampleFormat::Float32),
+            ..Default::default()
+        };
+
+        let mut encoder = PcmEncoder::new(config, options);
+        encoder.encode(&[0.5]);
+
+        let mut bytes = vec![0; 8];
+        let amount = encoder.read(&mut bytes).unwrap();
+
+        assert_eq!(amount, 4, "only the sample is written");
+        assert_eq!(bytes[..4], 0.5f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_header_for_size() {
+        let header =
+            WaveEncoder::header_for_size(&Config::default(), &EncoderOptions::default(), 1000);
+
+        assert_eq!(header.len(), WaveEncoder::HEADER_SIZE);
+        assert_eq!(header[4..8], 1036u32.to_le_bytes(), "chunk size");
+        assert_eq!(header[40..44], 1000u32.to_le_bytes(), "data size");
+    }
+}
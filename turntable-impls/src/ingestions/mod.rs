@@ -1,3 +1,5 @@
 mod symphonia_ingestion;
+mod symphonia_tags;
 
 pub use symphonia_ingestion::*;
+pub use symphonia_tags::*;
@@ -2,7 +2,6 @@ use async_trait::async_trait;
 use crossbeam::atomic::AtomicCell;
 use dashmap::DashMap;
 use parking_lot::Mutex;
-use rubato::{FftFixedInOut, Resampler};
 use std::{
     error::Error,
     io::{ErrorKind as IoErrorKind, Read, Seek, SeekFrom},
@@ -21,12 +20,10 @@ use symphonia::core::{
 use tokio::runtime::Handle;
 
 use turntable_core::{
-    get_or_create_handle, BoxedLoadable, Config, Ingestion, IntoLoadable, Loadable, LoaderLength,
-    PipelineContext, ReadResult, Sample, Sink, SinkId, SinkWriteRef,
+    get_or_create_handle, BoxedLoadable, Config, DynamicResampler, Ingestion, IntoLoadable,
+    Loadable, LoaderLength, PipelineContext, ReadResult, Sample, Sink, SinkId, SinkWriteRef,
 };
 
-type SymphoniaResampler = FftFixedInOut<Sample>;
-
 /// An ingestion implementation for Symphonia.
 pub struct SymphoniaIngestion {
     /// A runtime is needed to bridge synchronous Symphonia with asynchronous turntable.
@@ -52,11 +49,74 @@ impl Ingestion for SymphoniaIngestion {
     }
 
     async fn ingest<L>(&self, input: L) -> Result<Arc<Sink>, Box<dyn Error>>
+    where
+        L: IntoLoadable + Send + Sync,
+    {
+        let source = self.probe(input.into_loadable().boxed()).await?;
+        let sink: Arc<_> = Sink::new(&self.context, source.sink_length).into();
+        let loader = source.into_loader(sink.clone(), &self.context.config);
+
+        self.loaders.insert(sink.id, loader.into());
+        self.context.sinks.insert(sink.id, sink.clone());
+
+        Ok(sink)
+    }
+
+    async fn recover<L>(&self, sink_id: SinkId, input: L) -> Result<(), Box<dyn Error>>
     where
         L: IntoLoadable + Send + Sync,
     {
         let input = input.into_loadable();
+        let seekable = input.seekable().await;
+
+        let old_loader = self
+            .loaders
+            .get(&sink_id)
+            .map(|l| l.clone())
+            .ok_or("Symphonia: No loader exists for the sink")?;
+
+        let source = self.probe(input.boxed()).await?;
+        let loader = source.into_loader(old_loader.sink.clone(), &self.context.config);
+
+        // A seekable source seeks to the next requested offset by itself.
+        // Otherwise, it's assumed to be live and continues where the old source stopped.
+        if !seekable {
+            loader.offset.store(old_loader.offset.load());
+        }
+
+        self.loaders.insert(sink_id, loader.into());
+        Ok(())
+    }
+
+    async fn request_load(&self, sink_id: SinkId, offset: usize, amount: usize) {
+        let loader = self.loaders.get(&sink_id).expect("loader exists").clone();
+
+        let _ = self
+            .rt
+            .spawn_blocking(move || loader.load(offset, amount))
+            .await;
+    }
+
+    fn clear_inactive(&self) {
+        let clearable_sink_ids: Vec<_> = self
+            .context
+            .sinks
+            .iter()
+            .filter_map(|s| if s.is_clearable() { Some(s.id) } else { None })
+            .collect();
+
+        self.loaders
+            .retain(|id, _| !clearable_sink_ids.contains(id));
 
+        self.context
+            .sinks
+            .retain(|id, _| !clearable_sink_ids.contains(id));
+    }
+}
+
+impl SymphoniaIngestion {
+    /// Probes the format of a loadable and creates a decoder for its audio track.
+    async fn probe(&self, input: BoxedLoadable) -> Result<ProbedSource, Box<dyn Error>> {
         let potential_sink_length = input
             .length()
             .await
@@ -64,7 +124,7 @@ impl Ingestion for SymphoniaIngestion {
 
         let loadable = LoadableMediaSource {
             rt: self.rt.clone(),
-            loadable: input.boxed(),
+            loadable: input,
         };
 
         let stream = MediaSourceStream::new(Box::new(loadable), Default::default());
@@ -86,7 +146,8 @@ impl Ingestion for SymphoniaIngestion {
             .tracks()
             .iter()
             .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .ok_or("Symphonia: No supported audio stream found")?;
+            .ok_or("Symphonia: No supported audio stream found")?
+            .clone();
 
         let sample_rate = audio_track
             .codec_params
@@ -122,47 +183,36 @@ impl Ingestion for SymphoniaIngestion {
             .map(|s| self.context.config.seconds_to_samples(s))
             .or(potential_sink_length);
 
-        let sink: Arc<_> = Sink::new(&self.context, sink_length).into();
-
-        let loader = Loader {
-            sink: sink.clone(),
-            decoder: decoder.into(),
-            track: audio_track.clone(),
-            offset: Default::default(),
-            resampler: resampler.into(),
-            config: self.context.config.clone(),
-            format_reader: format_reader.into(),
-        };
-
-        self.loaders.insert(sink.id, loader.into());
-        self.context.sinks.insert(sink.id, sink.clone());
-
-        Ok(sink)
-    }
-
-    async fn request_load(&self, sink_id: SinkId, offset: usize, amount: usize) {
-        let loader = self.loaders.get(&sink_id).expect("loader exists").clone();
-
-        let _ = self
-            .rt
-            .spawn_blocking(move || loader.load(offset, amount))
-            .await;
+        Ok(ProbedSource {
+            track: audio_track,
+            decoder,
+            format_reader,
+            resampler,
+            sink_length,
+        })
     }
+}
 
-    fn clear_inactive(&self) {
-        let clearable_sink_ids: Vec<_> = self
-            .context
-            .sinks
-            .iter()
-            .filter_map(|s| if s.is_clearable() { Some(s.id) } else { None })
-            .collect();
-
-        self.loaders
-            .retain(|id, _| !clearable_sink_ids.contains(id));
+/// A source that was probed, and is ready to be loaded into a [Sink].
+struct ProbedSource {
+    track: Track,
+    decoder: Box<dyn Decoder>,
+    format_reader: Box<dyn FormatReader>,
+    resampler: DynamicResampler,
+    sink_length: Option<usize>,
+}
 
-        self.context
-            .sinks
-            .retain(|id, _| !clearable_sink_ids.contains(id));
+impl ProbedSource {
+    fn into_loader(self, sink: Arc<Sink>, config: &Config) -> Loader {
+        Loader {
+            sink,
+            decoder: self.decoder.into(),
+            track: self.track,
+            offset: Default::default(),
+            resampler: self.resampler.into(),
+            config: config.clone(),
+            format_reader: self.format_reader.into(),
+        }
     }
 }
 
@@ -189,7 +239,7 @@ impl Loader {
                 }
             }
             Err(e) => {
-                self.sink.error(format!("{:?}", e));
+                self.sink.fail(format!("{:?}", e));
                 return Err(());
             }
         }
@@ -336,37 +386,6 @@ impl Loader {
     }
 }
 
-/// Uninterleaves a chunk of samples into a vector where each sub-vector is a channel.
-fn uninterleave_samples(samples: Vec<Sample>, channels: usize) -> Vec<Vec<Sample>> {
-    let mut uninterleaved_samples = vec![];
-    let chunks: Vec<Vec<f32>> = samples.chunks_exact(channels).map(|c| c.to_vec()).collect();
-
-    for c in 0..channels {
-        let mut channel_samples = vec![];
-
-        for chunk in chunks.iter() {
-            channel_samples.push(chunk[c]);
-        }
-
-        uninterleaved_samples.push(channel_samples);
-    }
-
-    uninterleaved_samples
-}
-
-/// Interleaves vectors of channels into a single vector of samples
-fn interleave_samples(samples: Vec<Vec<Sample>>) -> Vec<Sample> {
-    if samples.is_empty() {
-        return vec![];
-    }
-    let channel_len = samples[0].len();
-    assert!(samples.iter().all(|channel| channel.len() == channel_len));
-
-    (0..channel_len)
-        .flat_map(|sample_index| samples.iter().map(move |channel| channel[sample_index]))
-        .collect()
-}
-
 #[derive(Debug)]
 struct LoadResult {
     samples: Vec<Sample>,
@@ -374,11 +393,17 @@ struct LoadResult {
 }
 
 /// Bridges an async [Loadable] with a synchronous [MediaSource].
-struct LoadableMediaSource {
+pub(crate) struct LoadableMediaSource {
     rt: Handle,
     loadable: BoxedLoadable,
 }
 
+impl LoadableMediaSource {
+    pub(crate) fn new(rt: Handle, loadable: BoxedLoadable) -> Self {
+        Self { rt, loadable }
+    }
+}
+
 impl MediaSource for LoadableMediaSource {
     fn is_seekable(&self) -> bool {
         self.rt.block_on(self.loadable.seekable())
@@ -417,103 +442,244 @@ impl Read for LoadableMediaSource {
     }
 }
 
-/// A resampler that can take any length of samples as input
-struct DynamicResampler {
-    resampler: SymphoniaResampler,
-    channel_count: usize,
-    source_sample_rate: usize,
-    target_sample_rate: usize,
-    did_remove_silence: bool,
-}
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, thread, time::Duration};
 
-impl DynamicResampler {
-    const CHUNK_SIZE: usize = 1024;
+    use crossbeam::channel::{unbounded, Receiver};
+    use turntable_core::{
+        BoxedQueueItem, Encoder, Pipeline, PipelineEvent, Queue, QueueItem, SinkLoadState,
+    };
 
-    fn new(source_sample_rate: usize, config: &Config) -> Result<Self, Box<dyn Error>> {
-        let resampler = SymphoniaResampler::new(
-            source_sample_rate,
-            config.sample_rate,
-            Self::CHUNK_SIZE,
-            config.channel_count,
-        )?;
+    use super::*;
+    use crate::WaveEncoder;
 
-        Ok(Self {
-            resampler,
-            source_sample_rate,
-            channel_count: config.channel_count,
-            target_sample_rate: config.sample_rate,
-            did_remove_silence: false,
-        })
+    /// A live source that errors once it has read up to a given byte.
+    struct FlakyLoadable {
+        bytes: Arc<Vec<u8>>,
+        position: Mutex<usize>,
+        fail_at: usize,
     }
 
-    fn process(&mut self, samples: Vec<Sample>) -> Vec<Sample> {
-        // Don't do anything if it's not necessary
-        if self.target_sample_rate == self.source_sample_rate {
-            return samples;
+    #[async_trait]
+    impl Loadable for FlakyLoadable {
+        async fn read(&self, buf: &mut [u8]) -> Result<ReadResult, Box<dyn Error>> {
+            let mut position = self.position.lock();
+
+            if *position >= self.fail_at {
+                return Err("connection reset".into());
+            }
+
+            let end = (*position + buf.len())
+                .min(self.fail_at)
+                .min(self.bytes.len());
+            let amount = end - *position;
+
+            buf[..amount].copy_from_slice(&self.bytes[*position..end]);
+            *position = end;
+
+            if end == self.bytes.len() {
+                Ok(ReadResult::End(amount))
+            } else {
+                Ok(ReadResult::More(amount))
+            }
         }
 
-        let mut interleaved_result = vec![0f32; 0];
-        let chunked_channels: Vec<_> = uninterleave_samples(samples, self.channel_count)
-            .into_iter()
-            .map(|c| {
-                c.chunks_exact(Self::CHUNK_SIZE)
-                    .map(|c| c.to_vec())
-                    .collect::<Vec<_>>()
-            })
-            .collect();
+        async fn length(&self) -> Option<LoaderLength> {
+            None
+        }
 
-        let chunk_amount = chunked_channels[0].len();
+        async fn seek(&self, _seek: SeekFrom) -> Result<usize, Box<dyn Error>> {
+            Err("not seekable".into())
+        }
+    }
 
-        for chunk_index in 0..chunk_amount {
-            let mut chunks = vec![];
+    /// A queue item whose source fails at the next of the given bytes every time it's re-created.
+    #[derive(Clone)]
+    struct FlakyItem(Arc<FlakyItemState>);
 
-            // ok clippy
-            (0..self.channel_count).for_each(|channel_index| {
-                let channel_chunk = &chunked_channels[channel_index][chunk_index];
-                chunks.push(channel_chunk.to_owned());
-            });
+    struct FlakyItemState {
+        bytes: Arc<Vec<u8>>,
+        fail_at: Mutex<VecDeque<usize>>,
+        sink_id: Mutex<Option<SinkId>>,
+        loadables_created: AtomicCell<usize>,
+    }
+
+    #[async_trait]
+    impl QueueItem for FlakyItem {
+        fn length(&self) -> Option<f32> {
+            None
+        }
+
+        fn register_sink(&self, sink_id: SinkId) {
+            *self.0.sink_id.lock() = Some(sink_id);
+        }
 
-            let resampled = self
-                .resampler
-                .process(&chunks, None)
-                .expect("processes without issue");
+        fn sink_id(&self) -> Option<SinkId> {
+            *self.0.sink_id.lock()
+        }
 
-            let interleaved = interleave_samples(resampled);
-            interleaved_result.extend_from_slice(&interleaved)
+        fn item_id(&self) -> String {
+            "flaky".to_string()
         }
 
-        if !self.did_remove_silence {
-            self.did_remove_silence = true;
+        async fn loadable(&self) -> Result<BoxedLoadable, Box<dyn Error>> {
+            self.0.loadables_created.fetch_add(1);
 
-            let silence_in_samples = self.resampler.output_delay() * self.channel_count;
+            let mut fail_at = self.0.fail_at.lock();
+            let next = if fail_at.len() > 1 {
+                fail_at.pop_front()
+            } else {
+                fail_at.front().copied()
+            };
 
-            return interleaved_result
-                .into_iter()
-                .skip(silence_in_samples)
-                .collect();
+            Ok(FlakyLoadable {
+                bytes: self.0.bytes.clone(),
+                position: Default::default(),
+                fail_at: next.unwrap_or(usize::MAX),
+            }
+            .boxed())
         }
+    }
 
-        interleaved_result
+    struct SingleItemQueue(FlakyItem);
+
+    impl Queue for SingleItemQueue {
+        fn peek(&self) -> Vec<BoxedQueueItem> {
+            vec![BoxedQueueItem::new(self.0.clone())]
+        }
+
+        fn next(&self) {}
+        fn previous(&self) {}
+        fn reset(&self) {}
+        fn skip(&self, _id: &str) {}
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// A second of a tone as 16-bit WAV.
+    fn wave(config: &Config) -> Vec<u8> {
+        let mut encoder = WaveEncoder::new(config.clone(), Default::default());
+        encoder.encode(&vec![0.25; config.samples_per_sec()]);
 
-    #[test]
-    fn test_uninterleave_samples() {
-        let samples = vec![1., 2., 3., 4., 5., 6.];
-        let result = uninterleave_samples(samples, 2);
+        let mut bytes = vec![];
+        encoder.read_to_end(&mut bytes).unwrap();
 
-        assert_eq!(result, vec![vec![1., 3., 5.], vec![2., 4., 6.]])
+        let data_size = (bytes.len() - WaveEncoder::HEADER_SIZE) as u32;
+        let header = WaveEncoder::header_for_size(config, &Default::default(), data_size);
+        bytes[..WaveEncoder::HEADER_SIZE].copy_from_slice(&header);
+
+        bytes
     }
 
-    #[test]
-    fn test_interleave_samples() {
-        let samples = vec![vec![1., 3., 5.], vec![2., 4., 6.]];
-        let result = interleave_samples(samples);
+    /// Plays an item through a pipeline, returning the pipeline's events.
+    fn play_item(config: Config, item: FlakyItem) -> Receiver<PipelineEvent> {
+        let pipeline = Arc::new(Pipeline::<SymphoniaIngestion>::new(config));
+        let player = pipeline.create_player();
+
+        // Only the loading matters, so the player doesn't need to move
+        player.pause();
+
+        let queue = pipeline.create_queue(player.id, |notifier| {
+            notifier.notify();
+            SingleItemQueue(item)
+        });
+        drop(queue);
+
+        let (sender, receiver) = unbounded();
+
+        thread::spawn(move || loop {
+            if sender.send(pipeline.wait_for_event()).is_err() {
+                break;
+            }
+        });
+
+        receiver
+    }
+
+    fn test_config() -> Config {
+        Config {
+            // Small loads, so the source fails after the first
+            preload_size_in_seconds: 0.4,
+            sink_recovery_attempts: 2,
+            sink_recovery_delay_in_seconds: 0.01,
+            ..Default::default()
+        }
+    }
+
+    /// Waits for the recovery attempts of the sink, until it's in a state that matches.
+    fn wait_for_recovery(
+        events: &Receiver<PipelineEvent>,
+        until: impl Fn(&SinkLoadState) -> bool,
+    ) -> Vec<(usize, String)> {
+        let mut attempts = vec![];
+
+        loop {
+            let event = events
+                .recv_timeout(Duration::from_secs(10))
+                .expect("the sink should settle");
+
+            match event {
+                PipelineEvent::QueueItemRecoveryAttempt {
+                    item_id,
+                    attempt,
+                    error,
+                    ..
+                } => {
+                    assert_eq!(item_id, "flaky");
+                    attempts.push((attempt, error));
+                }
+                PipelineEvent::SinkLoadStateUpdate { new_state, .. } if until(&new_state) => {
+                    return attempts;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_sink_resumes_where_it_stopped() {
+        let config = test_config();
+        let bytes = Arc::new(wave(&config));
+
+        // Fails 0.6 seconds in, then works
+        let fail_at = WaveEncoder::HEADER_SIZE + config.samples_per_sec() * 2 * 6 / 10;
+        let item = FlakyItem(Arc::new(FlakyItemState {
+            bytes,
+            fail_at: Mutex::new([fail_at, usize::MAX].into()),
+            sink_id: Default::default(),
+            loadables_created: Default::default(),
+        }));
+
+        let events = play_item(config.clone(), item.clone());
+        let attempts = wait_for_recovery(&events, |s| *s == SinkLoadState::Sealed);
+
+        // The new source can't seek, so it only loads to the end if it continues where the old one stopped
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].0, 1);
+        assert!(attempts[0].1.contains("connection reset"));
+        assert_eq!(item.0.loadables_created.load(), 2);
+    }
 
-        assert_eq!(result, vec![1., 2., 3., 4., 5., 6.]);
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_sink_errors_after_its_attempts() {
+        let config = test_config();
+        let bytes = Arc::new(wave(&config));
+
+        // Fails 0.6 seconds in, then every time right away
+        let fail_at = WaveEncoder::HEADER_SIZE + config.samples_per_sec() * 2 * 6 / 10;
+        let item = FlakyItem(Arc::new(FlakyItemState {
+            bytes,
+            fail_at: Mutex::new([fail_at, 0].into()),
+            sink_id: Default::default(),
+            loadables_created: Default::default(),
+        }));
+
+        let events = play_item(config.clone(), item.clone());
+        let attempts = wait_for_recovery(&events, |s| matches!(s, SinkLoadState::Error(_)));
+        let numbers: Vec<_> = attempts.iter().map(|(attempt, _)| *attempt).collect();
+
+        // The source failing again is another attempt, until there are none left
+        assert_eq!(numbers, vec![1, 2]);
+        assert!(attempts[0].1.contains("connection reset"));
+        assert_eq!(item.0.loadables_created.load(), 3);
     }
 }
@@ -0,0 +1,169 @@
+use std::error::Error;
+use symphonia::core::{
+    codecs::CODEC_TYPE_NULL,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey},
+    probe::Hint,
+};
+use turntable_core::{get_or_create_handle, IntoLoadable, Loadable};
+
+use crate::LoadableMediaSource;
+
+/// The tags Symphonia could find in a media source.
+#[derive(Debug, Clone, Default)]
+pub struct MediaTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// The duration of the first audio track in seconds, if known.
+    pub duration: Option<f32>,
+    pub artwork: Option<MediaArtwork>,
+}
+
+/// An embedded picture, such as an album cover.
+#[derive(Debug, Clone)]
+pub struct MediaArtwork {
+    pub data: Vec<u8>,
+    pub media_type: String,
+}
+
+impl MediaTags {
+    fn apply(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let target = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue,
+            };
+
+            // RIFF INFO strings keep their null terminator.
+            let value = tag.value.to_string();
+            let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+
+            if target.is_none() && !value.is_empty() {
+                *target = Some(value.to_string());
+            }
+        }
+
+        if self.artwork.is_none() {
+            self.artwork = revision.visuals().first().map(|visual| MediaArtwork {
+                data: visual.data.to_vec(),
+                media_type: visual.media_type.clone(),
+            });
+        }
+    }
+}
+
+/// Probes the given input with Symphonia and reads its tags and duration.
+///
+/// Only the container headers are read, so this is cheap compared to decoding.
+pub async fn read_tags<L>(input: L) -> Result<MediaTags, Box<dyn Error + Sync + Send>>
+where
+    L: IntoLoadable,
+{
+    let rt = get_or_create_handle();
+    let source = LoadableMediaSource::new(rt.clone(), input.into_loadable().boxed());
+    let stream = MediaSourceStream::new(Box::new(source), Default::default());
+
+    rt.spawn_blocking(move || {
+        let mut probed = symphonia::default::get_probe()
+            .format(
+                &Hint::default(),
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut tags = MediaTags::default();
+
+        // Tags found before the container (e.g. ID3) take precedence over the container's own.
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+            tags.apply(revision);
+        }
+
+        if let Some(revision) = probed.format.metadata().current() {
+            tags.apply(revision);
+        }
+
+        tags.duration = probed
+            .format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .and_then(|track| {
+                let time_base = track.codec_params.time_base?;
+                let n_frames = track.codec_params.n_frames?;
+
+                Some(time_base.calc_time(n_frames))
+            })
+            .map(|time| time.seconds as f32 + time.frac as f32);
+
+        Ok(tags)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use super::*;
+    use crate::LoadableFile;
+
+    fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend((data.len() as u32).to_le_bytes());
+        chunk.extend(data);
+
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+
+        chunk
+    }
+
+    /// A second of silent 8 kHz mono 16-bit audio with a LIST INFO chunk.
+    fn tagged_wave() -> Vec<u8> {
+        let sample_rate = 8000u32;
+        let mut format = vec![];
+        format.extend(1u16.to_le_bytes());
+        format.extend(1u16.to_le_bytes());
+        format.extend(sample_rate.to_le_bytes());
+        format.extend((sample_rate * 2).to_le_bytes());
+        format.extend(2u16.to_le_bytes());
+        format.extend(16u16.to_le_bytes());
+
+        let mut info = b"INFO".to_vec();
+        info.extend(chunk(b"INAM", b"Pipes\0"));
+        info.extend(chunk(b"IART", b"Turntable\0"));
+
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", &format));
+        body.extend(chunk(b"LIST", &info));
+        body.extend(chunk(b"data", &vec![0; sample_rate as usize * 2]));
+
+        chunk(b"RIFF", &body)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_read_tags() {
+        let path = std::env::temp_dir().join(format!("turntable-tags-{}.wav", std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(&tagged_wave())
+            .unwrap();
+
+        let loadable = LoadableFile::new(File::open(&path).unwrap().into());
+        let tags = read_tags(loadable).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let tags = tags.unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Pipes"));
+        assert_eq!(tags.artist.as_deref(), Some("Turntable"));
+        assert_eq!(tags.album, None);
+        assert_eq!(tags.duration, Some(1.0));
+    }
+}
@@ -1,7 +1,11 @@
 mod encoders;
 mod ingestions;
 mod loadables;
+mod relays;
+mod util;
 
 pub use encoders::*;
 pub use ingestions::*;
 pub use loadables::*;
+pub use relays::*;
+pub use util::*;
@@ -0,0 +1,147 @@
+/// Removes ICY metadata blocks from a stream received from an Icecast or SHOUTcast server, keeping the titles.
+///
+/// This is the reverse of [IcyInterleaver](turntable_core::IcyInterleaver), for streams that were requested with the `Icy-MetaData: 1` header.
+pub struct IcyDeinterleaver {
+    interval: usize,
+    bytes_until_metadata: usize,
+    /// The length of the metadata block being read, once its length byte was read
+    block_length: Option<usize>,
+    block: Vec<u8>,
+    title: Option<String>,
+    /// Whether the title has changed since it was last taken
+    is_title_pending: bool,
+}
+
+impl IcyDeinterleaver {
+    /// * `interval` - The amount of audio bytes between metadata blocks, from the `icy-metaint` header.
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval,
+            bytes_until_metadata: interval,
+            block_length: None,
+            block: vec![],
+            title: None,
+            is_title_pending: false,
+        }
+    }
+
+    /// Returns the audio bytes of the given stream bytes, reading the metadata blocks in between.
+    ///
+    /// Blocks can be split across calls, so the bytes are expected to be given in order.
+    pub fn deinterleave(&mut self, mut bytes: &[u8]) -> Vec<u8> {
+        let mut audio = Vec::with_capacity(bytes.len());
+
+        while !bytes.is_empty() {
+            if self.bytes_until_metadata > 0 {
+                let amount = bytes.len().min(self.bytes_until_metadata);
+
+                audio.extend_from_slice(&bytes[..amount]);
+                bytes = &bytes[amount..];
+                self.bytes_until_metadata -= amount;
+
+                continue;
+            }
+
+            let Some(length) = self.block_length else {
+                self.block_length = Some(bytes[0] as usize * 16);
+                bytes = &bytes[1..];
+
+                self.finish_block_if_read();
+                continue;
+            };
+
+            let amount = bytes.len().min(length - self.block.len());
+
+            self.block.extend_from_slice(&bytes[..amount]);
+            bytes = &bytes[amount..];
+
+            self.finish_block_if_read();
+        }
+
+        audio
+    }
+
+    /// Returns the title if it changed since it was last taken.
+    pub fn take_title(&mut self) -> Option<String> {
+        if !self.is_title_pending {
+            return None;
+        }
+
+        self.is_title_pending = false;
+        self.title.clone()
+    }
+
+    /// Reads the title from the current block once all of it was received, and starts the next interval.
+    fn finish_block_if_read(&mut self) {
+        if self.block_length != Some(self.block.len()) {
+            return;
+        }
+
+        let metadata = String::from_utf8_lossy(&self.block);
+
+        // Empty blocks mean the title is unchanged
+        if let Some(title) = parse_stream_title(&metadata) {
+            if self.title.as_deref() != Some(title) {
+                self.title = Some(title.to_string());
+                self.is_title_pending = true;
+            }
+        }
+
+        self.block.clear();
+        self.block_length = None;
+        self.bytes_until_metadata = self.interval;
+    }
+}
+
+/// Returns the value of `StreamTitle` in an ICY metadata block, such as `StreamTitle='Artist - Title';`.
+fn parse_stream_title(metadata: &str) -> Option<&str> {
+    let start = metadata.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &metadata[start..];
+
+    // Titles can contain quotes, so only a quote followed by a semicolon ends one
+    let end = rest.find("';").unwrap_or(rest.trim_end_matches('\0').len());
+
+    Some(&rest[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use turntable_core::IcyInterleaver;
+
+    use super::*;
+
+    #[test]
+    fn test_deinterleave() {
+        let mut interleaver = IcyInterleaver::new(5);
+        interleaver.set_title(Some("Don't Stop - Artist"));
+
+        let audio: Vec<u8> = (1..=12).collect();
+        let interleaved = interleaver.interleave(&audio);
+
+        let mut deinterleaver = IcyDeinterleaver::new(5);
+        let mut deinterleaved = vec![];
+
+        // Split the stream awkwardly, so blocks are read across calls
+        for chunk in interleaved.chunks(3) {
+            deinterleaved.extend(deinterleaver.deinterleave(chunk));
+        }
+
+        assert_eq!(deinterleaved, audio);
+        assert_eq!(
+            deinterleaver.take_title().as_deref(),
+            Some("Don’t Stop - Artist")
+        );
+        assert_eq!(deinterleaver.take_title(), None, "titles are taken once");
+
+        let mut deinterleaver = IcyDeinterleaver::new(2);
+        let block = b"StreamTitle='It's';StreamUrl='';";
+        let mut stream = vec![1, 2, 2];
+
+        stream.extend_from_slice(block);
+        stream.resize(3 + 32, 0);
+        stream.extend_from_slice(&[3, 4, 0, 5]);
+
+        assert_eq!(deinterleaver.deinterleave(&stream), [1, 2, 3, 4, 5]);
+        assert_eq!(deinterleaver.take_title().as_deref(), Some("It's"));
+    }
+}
@@ -0,0 +1,192 @@
+use std::{error::Error, io::SeekFrom};
+
+use async_trait::async_trait;
+use reqwest::{redirect, Client, Response};
+use tokio::sync::Mutex;
+use turntable_core::{assign_slice, Loadable, LoaderLength, ReadResult};
+
+use crate::{IcyDeinterleaver, NetworkOptions, NetworkStreamError};
+
+/// Called with the new title whenever the title of a live stream changes.
+pub type IcyTitleListener = Box<dyn Fn(String) + Send + Sync>;
+
+/// A loadable that reads a live internet radio stream from an Icecast or SHOUTcast server.
+///
+/// The stream is requested with ICY metadata, which is stripped out of the audio before it is decoded.
+/// Live streams have no length, so they can't be seeked.
+pub struct LoadableIcyStream {
+    /// The name of the station, from the `icy-name` header
+    pub name: Option<String>,
+    /// Whether the server sent any ICY headers, which tells radio streams apart from regular files
+    is_icy: bool,
+    state: Mutex<IcyStreamState>,
+    on_title: IcyTitleListener,
+}
+
+struct IcyStreamState {
+    response: Response,
+    /// Removes the metadata blocks, if the server interleaves them
+    deinterleaver: Option<IcyDeinterleaver>,
+    /// Audio that was received but not read yet
+    pending: Vec<u8>,
+    ended: bool,
+}
+
+impl LoadableIcyStream {
+    /// Connects to the stream at the given url, calling the listener whenever the title changes.
+    pub async fn connect<S>(
+        url: S,
+        options: NetworkOptions,
+        on_title: IcyTitleListener,
+    ) -> Result<Self, NetworkStreamError>
+    where
+        S: Into<String>,
+    {
+        let client = Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .read_timeout(options.read_timeout)
+            .redirect(redirect::Policy::limited(options.max_redirects))
+            .build()?;
+
+        let response = client
+            .get(url.into())
+            .header("Icy-MetaData", "1")
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            return Err(NetworkStreamError::Status(status));
+        }
+
+        let headers = response.headers();
+
+        let is_icy = headers.keys().any(|k| k.as_str().starts_with("icy-"));
+
+        let name = headers
+            .get("icy-name")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        // Servers that don't support metadata leave out the interval, and send plain audio
+        let deinterleaver = headers
+            .get("icy-metaint")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|interval| *interval > 0)
+            .map(IcyDeinterleaver::new);
+
+        Ok(Self {
+            name,
+            is_icy,
+            state: Mutex::new(IcyStreamState {
+                response,
+                deinterleaver,
+                pending: vec![],
+                ended: false,
+            }),
+            on_title,
+        })
+    }
+
+    /// Returns true if the server identified itself as an internet radio server.
+    pub fn is_icy(&self) -> bool {
+        self.is_icy
+    }
+}
+
+#[async_trait]
+impl Loadable for LoadableIcyStream {
+    async fn read(&self, buf: &mut [u8]) -> Result<ReadResult, Box<dyn Error>> {
+        let mut state = self.state.lock().await;
+
+        while state.pending.len() < buf.len() && !state.ended {
+            let Some(chunk) = state.response.chunk().await? else {
+                state.ended = true;
+                break;
+            };
+
+            let (audio, title) = match &mut state.deinterleaver {
+                Some(deinterleaver) => {
+                    let audio = deinterleaver.deinterleave(&chunk);
+                    (audio, deinterleaver.take_title())
+                }
+                None => (chunk.to_vec(), None),
+            };
+
+            state.pending.extend_from_slice(&audio);
+
+            if let Some(title) = title {
+                (self.on_title)(title);
+            }
+        }
+
+        let amount = assign_slice(&state.pending, buf);
+        state.pending.drain(..amount);
+
+        if state.ended && state.pending.is_empty() {
+            return Ok(ReadResult::End(amount));
+        }
+
+        Ok(ReadResult::More(amount))
+    }
+
+    async fn length(&self) -> Option<LoaderLength> {
+        None
+    }
+
+    async fn seek(&self, _seek: SeekFrom) -> Result<usize, Box<dyn Error>> {
+        Err("Live streams can't be seeked".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+    use turntable_core::IcyInterleaver;
+
+    use crate::loadables::loadable_network_stream::tests::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_strips_metadata() {
+        let audio = data();
+
+        let (url, _) = {
+            let audio = audio.clone();
+
+            stand_in(move |_, _| {
+                let mut interleaver = IcyInterleaver::new(1000);
+                interleaver.set_title(Some("Artist - Title"));
+
+                let mut response =
+                    b"HTTP/1.1 200 OK\r\nicy-name: Test FM\r\nicy-metaint: 1000\r\nConnection: close\r\n\r\n"
+                        .to_vec();
+
+                response.extend(interleaver.interleave(&audio));
+                response
+            })
+        };
+
+        let titles = Arc::new(Mutex::new(vec![]));
+        let listener = {
+            let titles = titles.clone();
+            Box::new(move |title| titles.lock().push(title))
+        };
+
+        let loadable = LoadableIcyStream::connect(url, options(), listener)
+            .await
+            .unwrap();
+
+        assert!(loadable.is_icy());
+        assert_eq!(loadable.name.as_deref(), Some("Test FM"));
+        assert_eq!(read_all(&loadable).await, audio);
+        assert_eq!(*titles.lock(), ["Artist - Title"]);
+        assert!(loadable.length().await.is_none());
+    }
+}
@@ -1,18 +1,63 @@
-use std::{error::Error, io::SeekFrom};
+use std::{error::Error, io::SeekFrom, time::Duration};
 
 use async_trait::async_trait;
 use crossbeam::atomic::AtomicCell;
 use parking_lot::Mutex;
-use reqwest::Client;
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+        CONTENT_TYPE, RANGE,
+    },
+    redirect, Client, StatusCode,
+};
+use thiserror::Error;
 use turntable_core::{assign_slice, Loadable, LoaderLength, ReadResult};
 
+use crate::percent_decode;
+
+/// Options for the connection of a [LoadableNetworkStream].
+#[derive(Debug, Clone)]
+pub struct NetworkOptions {
+    /// How long to wait for a connection to be established
+    pub connect_timeout: Duration,
+    /// How long to wait for more data before the connection is considered stalled
+    pub read_timeout: Duration,
+    /// How many times a request is retried after a transient failure without any progress
+    pub max_retries: usize,
+    /// The delay before the first retry, which doubles with every attempt
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    pub max_redirects: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum NetworkStreamError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("Request failed with status code {0}")]
+    Status(StatusCode),
+    #[error("Connection closed after {received} of {expected} bytes")]
+    Incomplete { received: usize, expected: usize },
+}
+
 /// A loadable that reads from a network stream.
 /// If the stream supports byte ranges, it can be seeked.
+///
+/// Transient failures are retried with an exponential backoff,
+/// resuming from the last byte that was received if the server supports byte ranges.
 pub struct LoadableNetworkStream {
+    /// The url after following redirects
     url: String,
     client: Client,
+    options: NetworkOptions,
     length: Option<usize>,
     supports_byte_ranges: bool,
+    content_type: Option<String>,
+    content_disposition: Option<String>,
+    /// How many bytes from the start can be read, if only a prefix of the stream is needed
+    limit: Option<usize>,
+    /// Whether the end of a stream of unknown length has been reached
+    reached_end: AtomicCell<bool>,
     read_offset: AtomicCell<usize>,
     /// A cache of bytes that are preloaded to prevent spamming the network.
     /// This is because the ingestion may request very small amounts of data at a time.
@@ -20,66 +65,229 @@ pub struct LoadableNetworkStream {
     loaded_bytes_offset: AtomicCell<usize>,
 }
 
+/// What is known about a network stream before loading it.
+struct Probe {
+    url: String,
+    length: Option<usize>,
+    supports_byte_ranges: bool,
+    content_type: Option<String>,
+    content_disposition: Option<String>,
+}
+
 impl LoadableNetworkStream {
     const MAX_CHUNK_SIZE: usize = 50_000_000; // 50MB
     const MIN_CHUNK_SIZE: usize = 5_000_000; // 5MB
 
-    pub async fn new<S>(url: S) -> Result<Self, reqwest::Error>
+    pub async fn new<S>(url: S) -> Result<Self, NetworkStreamError>
     where
         S: Into<String>,
     {
-        let url = url.into();
-        let client = Client::new();
-
-        let response = client.head(&url).send().await?;
-        let headers = response.headers();
+        Self::with_options(url, Default::default()).await
+    }
 
-        let supports_byte_ranges = headers
-            .get("Accept-Ranges")
-            .map(|v| v == "bytes")
-            .unwrap_or_default();
+    pub async fn with_options<S>(
+        url: S,
+        options: NetworkOptions,
+    ) -> Result<Self, NetworkStreamError>
+    where
+        S: Into<String>,
+    {
+        let client = Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .read_timeout(options.read_timeout)
+            .redirect(redirect::Policy::limited(options.max_redirects))
+            .build()?;
 
-        let length = headers
-            .get("Content-Length")
-            .map(|v| str::parse::<usize>(v.to_str().unwrap()).unwrap_or_default());
+        let url = url.into();
+        let mut attempt = 0;
+
+        let probe = loop {
+            match Self::probe(&client, &url).await {
+                Ok(probe) => break probe,
+                Err(e) if e.is_transient() && attempt < options.max_retries => {
+                    tokio::time::sleep(options.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
 
         Ok(Self {
-            url,
+            url: probe.url,
             client,
-            length,
-            supports_byte_ranges,
+            options,
+            length: probe.length,
+            supports_byte_ranges: probe.supports_byte_ranges,
+            content_type: probe.content_type,
+            content_disposition: probe.content_disposition,
+            limit: None,
+            reached_end: Default::default(),
             read_offset: Default::default(),
             loaded_bytes: Default::default(),
             loaded_bytes_offset: Default::default(),
         })
     }
 
+    /// Only reads the first `limit` bytes of the stream, after which reads end.
+    ///
+    /// This bounds what is downloaded when only the headers are needed, even from servers that don't support byte ranges.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Finds the length of the stream and whether it supports byte ranges.
+    ///
+    /// Some servers don't allow `HEAD` requests, such as ones with urls that are signed for `GET` only,
+    /// so a request for the first byte is made instead if it fails.
+    async fn probe(client: &Client, url: &str) -> Result<Probe, NetworkStreamError> {
+        if let Ok(response) = client.head(url).send().await {
+            let headers = response.headers();
+            let length = content_length(headers);
+
+            if response.status().is_success() && length.is_some() {
+                return Ok(Probe {
+                    url: response.url().to_string(),
+                    length,
+                    supports_byte_ranges: accepts_byte_ranges(headers),
+                    content_type: header_value(headers, CONTENT_TYPE),
+                    content_disposition: header_value(headers, CONTENT_DISPOSITION),
+                });
+            }
+        }
+
+        let response = client.get(url).header(RANGE, "bytes=0-0").send().await?;
+        let status = response.status();
+        let headers = response.headers();
+
+        let probe = match status {
+            StatusCode::PARTIAL_CONTENT => Probe {
+                url: response.url().to_string(),
+                length: headers
+                    .get(CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.rsplit_once('/'))
+                    .and_then(|(_, total)| total.parse().ok()),
+                supports_byte_ranges: true,
+                content_type: header_value(headers, CONTENT_TYPE),
+                content_disposition: header_value(headers, CONTENT_DISPOSITION),
+            },
+            status if status.is_success() => Probe {
+                url: response.url().to_string(),
+                length: content_length(headers),
+                supports_byte_ranges: accepts_byte_ranges(headers),
+                content_type: header_value(headers, CONTENT_TYPE),
+                content_disposition: header_value(headers, CONTENT_DISPOSITION),
+            },
+            status => return Err(NetworkStreamError::Status(status)),
+        };
+
+        Ok(probe)
+    }
+
     /// Loads an amount of bytes from the current load offset.
     async fn load(&self, amount: usize) -> Result<(), Box<dyn Error>> {
-        // Set up the offsets that we will load from and to.
         let start = self.loaded_bytes_offset.load();
-        let end = (start + amount).min(self.normal_len()).saturating_sub(1);
+        let end = start.saturating_add(amount).min(self.loadable_len());
+
+        let mut attempt = 0;
+        let mut last_offset = start;
+
+        loop {
+            let result = self.try_load(end).await;
+
+            // Attempts only count against the limit if they didn't get any further
+            let offset = self.loaded_bytes_offset.load();
+
+            if offset > last_offset {
+                last_offset = offset;
+                attempt = 0;
+            }
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_transient() && attempt < self.options.max_retries => {
+                    tokio::time::sleep(self.options.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Makes a single request for the bytes from the current load offset up to the given end,
+    /// appending them as they arrive so a failure doesn't lose what was received.
+    async fn try_load(&self, end: usize) -> Result<(), NetworkStreamError> {
+        let start = self.loaded_bytes_offset.load();
+
+        if start >= end {
+            return Ok(());
+        }
 
         let mut request = self.client.get(&self.url);
 
         if self.supports_byte_ranges {
-            let range = format!("bytes={}-{}", start, end);
-            request = request.header("Range", range);
+            let range = format!("bytes={}-{}", start, end - 1);
+            request = request.header(RANGE, range);
         }
 
-        let response = request.send().await?;
+        let mut response = request.send().await?;
+        let status = response.status();
 
-        if !response.status().is_success() {
-            return Err(format!("Request failed with status code {}", response.status()).into());
+        if !status.is_success() {
+            return Err(NetworkStreamError::Status(status));
         }
 
-        let new_bytes = response.bytes().await?;
-        let mut current_bytes = self.loaded_bytes.lock();
+        // Servers that ignore the range send the whole stream, so everything before the start is skipped
+        let is_partial = status == StatusCode::PARTIAL_CONTENT;
+        let mut skip = if is_partial { 0 } else { start };
 
-        current_bytes.extend_from_slice(&new_bytes);
-        self.loaded_bytes_offset.store(end + 1);
+        // The rest of the stream is kept in that case, unless only a prefix of it is needed
+        let stop_at = self.limit.filter(|_| !is_partial);
 
-        Ok(())
+        let expected = match self.length {
+            Some(_) if is_partial => Some(end - start),
+            Some(_) => Some(self.loadable_len().saturating_sub(start)),
+            None => None,
+        };
+
+        let mut received = 0;
+
+        while let Some(chunk) = response.chunk().await? {
+            let skipped = skip.min(chunk.len());
+            let mut new_bytes = &chunk[skipped..];
+
+            if let Some(stop_at) = stop_at {
+                let remaining = stop_at.saturating_sub(self.loaded_bytes_offset.load());
+                new_bytes = &new_bytes[..new_bytes.len().min(remaining)];
+            }
+
+            skip -= skipped;
+            received += new_bytes.len();
+
+            self.loaded_bytes.lock().extend_from_slice(new_bytes);
+            self.loaded_bytes_offset
+                .store(self.loaded_bytes_offset.load() + new_bytes.len());
+
+            if stop_at.is_some_and(|s| self.loaded_bytes_offset.load() >= s) {
+                break;
+            }
+        }
+
+        match expected {
+            Some(expected) if received < expected => {
+                Err(NetworkStreamError::Incomplete { received, expected })
+            }
+            Some(_) => Ok(()),
+            None => {
+                // Without a length, a range that comes back short means the stream ended
+                if !is_partial || received < end - start {
+                    self.reached_end.store(true);
+                }
+
+                Ok(())
+            }
+        }
     }
 
     /// Ensures that the given amount of bytes at the given offset are preloaded from the network.
@@ -90,13 +298,11 @@ impl LoadableNetworkStream {
 
         // This number is relative to the vector
         let new_read_offset = current_read_offset + requested_amount;
-        let remaining_bytes = self
-            .length
-            .unwrap_or(usize::MAX)
-            .saturating_sub(current_load_offset);
+        let remaining_bytes = self.loadable_len().saturating_sub(current_load_offset);
 
         // Skip preloading if we have enough data for the read, or if there's no more data to read
-        if new_read_offset <= current_loaded_len || remaining_bytes == 0 {
+        if new_read_offset <= current_loaded_len || remaining_bytes == 0 || self.reached_end.load()
+        {
             return Ok(());
         }
 
@@ -109,10 +315,39 @@ impl LoadableNetworkStream {
         Ok(())
     }
 
+    /// Returns the url of the stream, after following redirects.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the media type the server reported, without parameters such as the charset.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type
+            .as_deref()
+            .map(|t| t.split(';').next().unwrap_or_default().trim())
+    }
+
+    /// Returns the file name the server suggested in the `Content-Disposition` header, if any.
+    pub fn file_name(&self) -> Option<String> {
+        self.content_disposition
+            .as_deref()
+            .and_then(parse_file_name)
+    }
+
+    /// Returns the byte offset that the next read starts from.
+    pub fn position(&self) -> usize {
+        self.absolute_read_offset()
+    }
+
     fn normal_len(&self) -> usize {
         self.length.unwrap_or(usize::MAX)
     }
 
+    /// The length of the stream, up to the limit.
+    fn loadable_len(&self) -> usize {
+        self.normal_len().min(self.limit.unwrap_or(usize::MAX))
+    }
+
     fn loaded_length(&self) -> usize {
         self.loaded_bytes.lock().len()
     }
@@ -128,6 +363,100 @@ impl LoadableNetworkStream {
     }
 }
 
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            max_retries: 5,
+            min_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(8),
+            max_redirects: 10,
+        }
+    }
+}
+
+impl NetworkOptions {
+    /// Returns how long to wait before the given retry attempt.
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.min(31) as u32);
+
+        self.min_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl NetworkStreamError {
+    /// Whether the request could succeed if it's made again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(e) => !e.is_builder() && !e.is_redirect(),
+            Self::Status(status) => {
+                status.is_server_error()
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+                    || *status == StatusCode::REQUEST_TIMEOUT
+            }
+            Self::Incomplete { .. } => true,
+        }
+    }
+
+    /// Whether the server refused the request in a way that suggests the url has expired,
+    /// such as signed urls that are only valid for a while.
+    pub fn is_expired(&self) -> bool {
+        matches!(
+            self,
+            Self::Status(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::GONE)
+        )
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+fn header_value(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// Returns the file name of a `Content-Disposition` header, preferring the encoded `filename*` parameter.
+fn parse_file_name(disposition: &str) -> Option<String> {
+    let parameters: Vec<_> = disposition
+        .split(';')
+        .filter_map(|p| p.split_once('='))
+        .map(|(key, value)| (key.trim().to_lowercase(), value.trim()))
+        .collect();
+
+    // Encoded names look like `UTF-8''file%20name.mp3`, with an optional language between the quotes
+    let encoded = parameters
+        .iter()
+        .find(|(key, _)| key == "filename*")
+        .and_then(|(_, value)| value.splitn(3, '\'').nth(2))
+        .map(percent_decode);
+
+    let plain = || {
+        parameters
+            .iter()
+            .find(|(key, _)| key == "filename")
+            .map(|(_, value)| value.trim_matches('"').to_string())
+    };
+
+    encoded.or_else(plain).filter(|name| !name.is_empty())
+}
+
+fn accepts_byte_ranges(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT_RANGES)
+        .map(|v| v == "bytes")
+        .unwrap_or_default()
+}
+
 #[async_trait]
 impl Loadable for LoadableNetworkStream {
     async fn read(&self, buf: &mut [u8]) -> Result<ReadResult, Box<dyn Error>> {
@@ -179,6 +508,7 @@ impl Loadable for LoadableNetworkStream {
             || new_absolute_read_offset > absolute_load_end_offset
         {
             self.read_offset.store(0);
+            self.reached_end.store(false);
             self.loaded_bytes.lock().clear();
             self.loaded_bytes_offset.store(safe_new_offset);
         } else {
@@ -188,3 +518,319 @@ impl Loadable for LoadableNetworkStream {
         Ok(safe_new_offset)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::Arc,
+        thread,
+    };
+
+    use super::*;
+
+    /// A request received by the stand-in server.
+    #[derive(Debug, Clone)]
+    pub(crate) struct Request {
+        pub method: String,
+        pub path: String,
+        pub range: Option<(usize, usize)>,
+    }
+
+    pub(crate) fn data() -> Vec<u8> {
+        (0..10_000).map(|i| (i % 251) as u8).collect()
+    }
+
+    pub(crate) fn options() -> NetworkOptions {
+        NetworkOptions {
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(5),
+            min_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            ..Default::default()
+        }
+    }
+
+    /// Serves connections with the given handler, which returns the raw response for each request,
+    /// and returns the url of the server along with every request it received.
+    pub(crate) fn stand_in<F>(handler: F) -> (String, Arc<Mutex<Vec<Request>>>)
+    where
+        F: Fn(usize, &Request) -> Vec<u8> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    break;
+                };
+
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+
+                let mut parts = line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+                let mut range = None;
+
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+
+                    if let Some(value) = line.to_lowercase().strip_prefix("range: bytes=") {
+                        let (start, end) = value.trim().split_once('-').unwrap();
+                        range = Some((start.parse().unwrap(), end.parse().unwrap()));
+                    }
+                }
+
+                let request = Request {
+                    method,
+                    path,
+                    range,
+                };
+
+                let index = {
+                    let mut requests = received.lock();
+                    requests.push(request.clone());
+                    requests.len() - 1
+                };
+
+                let response = handler(index, &request);
+                let _ = reader.get_mut().write_all(&response);
+            }
+        });
+
+        (format!("http://127.0.0.1:{port}"), requests)
+    }
+
+    /// Responds like a regular file server that supports byte ranges.
+    pub(crate) fn respond(request: &Request, data: &[u8]) -> Vec<u8> {
+        if request.method == "HEAD" {
+            return format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n",
+                data.len()
+            )
+            .into_bytes();
+        }
+
+        match request.range {
+            Some((start, end)) => {
+                let end = end.min(data.len() - 1);
+                let mut response = format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                    end - start + 1,
+                    start,
+                    end,
+                    data.len()
+                )
+                .into_bytes();
+
+                response.extend_from_slice(&data[start..=end]);
+                response
+            }
+            None => {
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    data.len()
+                )
+                .into_bytes();
+
+                response.extend_from_slice(data);
+                response
+            }
+        }
+    }
+
+    pub(crate) fn status(line: &str) -> Vec<u8> {
+        format!("HTTP/1.1 {line}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").into_bytes()
+    }
+
+    pub(crate) async fn read_all(loadable: &impl Loadable) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut buf = vec![0; 1024];
+
+        loop {
+            match loadable.read(&mut buf).await.unwrap() {
+                ReadResult::More(amount) => bytes.extend_from_slice(&buf[..amount]),
+                ReadResult::End(amount) => {
+                    bytes.extend_from_slice(&buf[..amount]);
+                    break;
+                }
+            }
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name("attachment; filename=\"song.mp3\"").as_deref(),
+            Some("song.mp3")
+        );
+        assert_eq!(
+            parse_file_name("attachment; filename=fallback.mp3; filename*=UTF-8''caf%C3%A9.mp3")
+                .as_deref(),
+            Some("café.mp3")
+        );
+        assert_eq!(parse_file_name("inline"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_dropped_connection() {
+        let (url, requests) = stand_in(|index, request| {
+            let data = data();
+            let mut response = respond(request, &data);
+
+            // Cut the first download off halfway through the body
+            if index == 1 {
+                response.truncate(response.len() - data.len() / 2);
+            }
+
+            response
+        });
+
+        let loadable = LoadableNetworkStream::with_options(url, options())
+            .await
+            .unwrap();
+
+        assert_eq!(read_all(&loadable).await, data());
+
+        let requests = requests.lock();
+        let resumed = requests[2].range.unwrap();
+
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].range, Some((0, data().len() - 1)));
+        assert_eq!(resumed, (data().len() / 2, data().len() - 1));
+    }
+
+    #[tokio::test]
+    async fn test_head_not_allowed() {
+        let (url, _) = stand_in(|_, request| {
+            if request.method == "HEAD" {
+                return status("405 Method Not Allowed");
+            }
+
+            respond(request, &data())
+        });
+
+        let loadable = LoadableNetworkStream::with_options(url, options())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            loadable.length().await,
+            Some(LoaderLength::Bytes(10_000))
+        ));
+
+        loadable.seek(SeekFrom::Start(5_000)).await.unwrap();
+        assert_eq!(read_all(&loadable).await, data()[5_000..]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_unavailable() {
+        let (url, requests) = stand_in(|index, request| {
+            if index == 1 {
+                return status("503 Service Unavailable");
+            }
+
+            respond(request, &data())
+        });
+
+        let loadable = LoadableNetworkStream::with_options(url, options())
+            .await
+            .unwrap();
+
+        assert_eq!(read_all(&loadable).await, data());
+        assert_eq!(requests.lock().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up() {
+        let (url, _) = stand_in(|_, request| {
+            if request.method == "HEAD" {
+                return respond(request, &data());
+            }
+
+            status("404 Not Found")
+        });
+
+        let loadable = LoadableNetworkStream::with_options(url, options())
+            .await
+            .unwrap();
+
+        let mut buf = vec![0; 1024];
+        assert!(loadable.read(&mut buf).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_follows_redirects() {
+        let (url, requests) = stand_in(|_, request| {
+            if request.path == "/old" {
+                return b"HTTP/1.1 302 Found\r\nLocation: /new\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec();
+            }
+
+            respond(request, &data())
+        });
+
+        let loadable = LoadableNetworkStream::with_options(format!("{url}/old"), options())
+            .await
+            .unwrap();
+
+        assert_eq!(read_all(&loadable).await, data());
+
+        // Loading goes straight to the url that was redirected to
+        let requests = requests.lock();
+        assert_eq!(requests.last().unwrap().path, "/new");
+        assert_eq!(requests.iter().filter(|r| r.path == "/old").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_limit_bounds_range_requests() {
+        let (url, requests) = stand_in(|_, request| respond(request, &data()));
+
+        let loadable = LoadableNetworkStream::with_options(url, options())
+            .await
+            .unwrap()
+            .with_limit(1_000);
+
+        assert_eq!(read_all(&loadable).await, data()[..1_000]);
+
+        // Seeking past the limit doesn't load anything
+        loadable.seek(SeekFrom::Start(5_000)).await.unwrap();
+        assert!(read_all(&loadable).await.is_empty());
+
+        let requests = requests.lock();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].range, Some((0, 999)));
+    }
+
+    #[tokio::test]
+    async fn test_limit_without_byte_ranges() {
+        // Sends the whole stream no matter what, without saying how long it is
+        let (url, requests) = stand_in(|_, _| {
+            let mut response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_vec();
+            response.extend_from_slice(&data());
+            response
+        });
+
+        let loadable = LoadableNetworkStream::with_options(url, options())
+            .await
+            .unwrap()
+            .with_limit(1_000);
+
+        assert_eq!(read_all(&loadable).await, data()[..1_000]);
+
+        // The two probes, and a single load that stops at the limit
+        assert_eq!(requests.lock().len(), 3);
+    }
+}
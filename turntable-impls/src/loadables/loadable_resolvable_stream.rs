@@ -0,0 +1,193 @@
+use std::{error::Error, io::SeekFrom, sync::Arc};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use turntable_core::{Loadable, LoaderLength, ReadResult};
+
+use crate::{LoadableNetworkStream, NetworkOptions, NetworkStreamError};
+
+/// Resolves the url of a network stream, typically the input that the stream originates from.
+#[async_trait]
+pub trait StreamResolver
+where
+    Self: 'static + Sync + Send,
+{
+    /// Returns a fresh url for the stream.
+    async fn resolve(&self) -> Result<String, Box<dyn Error + Sync + Send>>;
+}
+
+/// A network stream with a url that can expire, such as a signed url.
+///
+/// When the server refuses the url, a fresh one is resolved and reading continues from the same byte offset.
+pub struct LoadableResolvableStream {
+    resolver: Box<dyn StreamResolver>,
+    options: NetworkOptions,
+    stream: Mutex<Arc<LoadableNetworkStream>>,
+}
+
+impl LoadableResolvableStream {
+    /// Creates the stream from an already resolved url.
+    pub async fn new<S, R>(url: S, resolver: R) -> Result<Self, NetworkStreamError>
+    where
+        S: Into<String>,
+        R: StreamResolver,
+    {
+        Self::with_options(url, resolver, Default::default()).await
+    }
+
+    pub async fn with_options<S, R>(
+        url: S,
+        resolver: R,
+        options: NetworkOptions,
+    ) -> Result<Self, NetworkStreamError>
+    where
+        S: Into<String>,
+        R: StreamResolver,
+    {
+        let stream = LoadableNetworkStream::with_options(url, options.clone()).await?;
+
+        Ok(Self {
+            resolver: Box::new(resolver),
+            options,
+            stream: Mutex::new(stream.into()),
+        })
+    }
+
+    fn stream(&self) -> Arc<LoadableNetworkStream> {
+        self.stream.lock().clone()
+    }
+
+    /// Replaces the stream with one from a fresh url, starting at the given byte offset.
+    async fn resolve(&self, position: usize) -> Result<(), Box<dyn Error>> {
+        let url = self
+            .resolver
+            .resolve()
+            .await
+            .map_err(|e| e as Box<dyn Error>)?;
+        let stream = LoadableNetworkStream::with_options(url, self.options.clone()).await?;
+
+        stream.seek(SeekFrom::Start(position as u64)).await?;
+        *self.stream.lock() = stream.into();
+
+        Ok(())
+    }
+}
+
+fn is_expired(error: &(dyn Error + 'static)) -> bool {
+    error
+        .downcast_ref::<NetworkStreamError>()
+        .is_some_and(NetworkStreamError::is_expired)
+}
+
+#[async_trait]
+impl Loadable for LoadableResolvableStream {
+    async fn read(&self, buf: &mut [u8]) -> Result<ReadResult, Box<dyn Error>> {
+        let mut resolved = false;
+
+        loop {
+            let stream = self.stream();
+
+            // A url that is refused right after being resolved won't get any better
+            match stream.read(buf).await {
+                Ok(result) => return Ok(result),
+                Err(e) if resolved || !is_expired(e.as_ref()) => return Err(e),
+                Err(_) => {}
+            }
+
+            self.resolve(stream.position()).await?;
+            resolved = true;
+        }
+    }
+
+    async fn length(&self) -> Option<LoaderLength> {
+        self.stream().length().await
+    }
+
+    async fn seek(&self, seek: SeekFrom) -> Result<usize, Box<dyn Error>> {
+        self.stream().seek(seek).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loadables::loadable_network_stream::tests::*;
+
+    use super::*;
+
+    /// Resolves to a fixed url, counting how often it's asked to.
+    struct FixedResolver {
+        url: String,
+        count: Arc<Mutex<usize>>,
+    }
+
+    #[async_trait]
+    impl StreamResolver for FixedResolver {
+        async fn resolve(&self) -> Result<String, Box<dyn Error + Sync + Send>> {
+            *self.count.lock() += 1;
+            Ok(self.url.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolves_expired_url() {
+        let (url, requests) = stand_in(|index, request| {
+            let data = data();
+            let mut response = respond(request, &data);
+
+            // The signed url expires halfway through the first download
+            if request.path == "/signed" && request.method == "GET" {
+                if index > 1 {
+                    return status("403 Forbidden");
+                }
+
+                response.truncate(response.len() - data.len() / 2);
+            }
+
+            response
+        });
+
+        let count = Arc::new(Mutex::new(0));
+        let resolver = FixedResolver {
+            url: format!("{url}/fresh"),
+            count: count.clone(),
+        };
+
+        let loadable =
+            LoadableResolvableStream::with_options(format!("{url}/signed"), resolver, options())
+                .await
+                .unwrap();
+
+        assert_eq!(read_all(&loadable).await, data());
+        assert_eq!(*count.lock(), 1);
+
+        let requests = requests.lock();
+        assert_eq!(requests.last().unwrap().path, "/fresh");
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_when_still_expired() {
+        let (url, _) = stand_in(|_, request| {
+            if request.method == "GET" {
+                return status("403 Forbidden");
+            }
+
+            respond(request, &data())
+        });
+
+        let count = Arc::new(Mutex::new(0));
+        let resolver = FixedResolver {
+            url: format!("{url}/fresh"),
+            count: count.clone(),
+        };
+
+        let loadable =
+            LoadableResolvableStream::with_options(format!("{url}/signed"), resolver, options())
+                .await
+                .unwrap();
+
+        let mut buf = vec![0; 1024];
+
+        assert!(loadable.read(&mut buf).await.is_err());
+        assert_eq!(*count.lock(), 1);
+    }
+}
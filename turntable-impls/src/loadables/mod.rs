@@ -1,5 +1,11 @@
+mod icy_deinterleaver;
 mod loadable_file;
+mod loadable_icy_stream;
 mod loadable_network_stream;
+mod loadable_resolvable_stream;
 
+pub use icy_deinterleaver::*;
 pub use loadable_file::*;
+pub use loadable_icy_stream::*;
 pub use loadable_network_stream::*;
+pub use loadable_resolvable_stream::*;
@@ -0,0 +1,3 @@
+mod icecast_source;
+
+pub use icecast_source::*;
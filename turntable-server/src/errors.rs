@@ -1,9 +1,10 @@
 use axum::{
+    extract::multipart::MultipartError,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use thiserror::Error;
-use turntable_collab::{AuthError, DatabaseError, InputError, RoomError};
+use turntable_collab::{AuthError, DatabaseError, InputError, LibraryError, RoomError};
 
 pub type ServerResult<T> = Result<T, ServerError>;
 
@@ -37,6 +38,35 @@ pub enum ServerError {
     StreamKeyNotOwn,
     #[error("Stream key does not exist")]
     StreamKeyNotFound,
+    #[error("Stream format {0} is not supported")]
+    UnsupportedStreamFormat(String),
+    #[error("None of the acceptable stream formats are supported")]
+    NoAcceptableStreamFormat,
+    #[error("HLS is not available, since none of the stream formats can be segmented")]
+    HlsUnavailable,
+    #[error("User did not create this relay")]
+    RelayNotOwn,
+    #[error("{0}")]
+    InvalidRelayTarget(String),
+    #[error("Clips can't be captured, because time-shifting is disabled")]
+    ClipsUnavailable,
+    #[error("Nothing was buffered to capture a clip from yet")]
+    ClipEmpty,
+    #[error("Clips of a room can take up at most {0} bytes")]
+    ClipTooLarge(u64),
+    #[error("Track could not be loaded for previewing: {0}")]
+    PreviewFailed(String),
+    // Uploads
+    #[error("User does not own this upload")]
+    UploadNotOwn,
+    #[error("Uploads can be at most {0} bytes")]
+    UploadTooLarge(u64),
+    #[error("Uploads of type {0} are not supported")]
+    UnsupportedUploadType(String),
+    #[error("Uploads can have at most {0} fields")]
+    TooManyUploadFields(usize),
+    #[error("Upload is malformed: {0}")]
+    InvalidUpload(String),
     // Inputs
     #[error("Input type is supported but resource was not found")]
     InputNotFound,
@@ -50,6 +80,12 @@ pub enum ServerError {
     InputParseError(String),
     #[error("Resource is invalid")]
     InputInvalid,
+    #[error("Access to {0} is not allowed")]
+    InputPathDenied(String),
+    #[error("Playing from {0} is not allowed")]
+    InputExtractorDenied(String),
+    #[error("Failed to extract resource: {0}")]
+    InputExtractionFailed(String),
 }
 
 impl ServerError {
@@ -70,10 +106,27 @@ impl ServerError {
             Self::UserNotInRoom => StatusCode::FORBIDDEN,
             Self::StreamKeyNotFound => StatusCode::NOT_FOUND,
             Self::StreamKeyNotOwn => StatusCode::FORBIDDEN,
+            Self::UnsupportedStreamFormat(_) => StatusCode::BAD_REQUEST,
+            Self::NoAcceptableStreamFormat => StatusCode::NOT_ACCEPTABLE,
+            Self::HlsUnavailable => StatusCode::NOT_IMPLEMENTED,
+            Self::RelayNotOwn => StatusCode::FORBIDDEN,
+            Self::InvalidRelayTarget(_) => StatusCode::BAD_REQUEST,
+            Self::ClipsUnavailable => StatusCode::CONFLICT,
+            Self::ClipEmpty => StatusCode::CONFLICT,
+            Self::ClipTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::PreviewFailed(_) => StatusCode::BAD_GATEWAY,
+            Self::UploadNotOwn => StatusCode::FORBIDDEN,
+            Self::UploadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedUploadType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::TooManyUploadFields(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::InvalidUpload(_) => StatusCode::BAD_REQUEST,
             Self::InputNotFound => StatusCode::NOT_FOUND,
             Self::InputNoMatch => StatusCode::BAD_REQUEST,
             Self::UnsupportedInputType => StatusCode::BAD_REQUEST,
             Self::InputInvalid => StatusCode::BAD_REQUEST,
+            Self::InputPathDenied(_) => StatusCode::FORBIDDEN,
+            Self::InputExtractorDenied(_) => StatusCode::FORBIDDEN,
+            Self::InputExtractionFailed(_) => StatusCode::BAD_GATEWAY,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -130,11 +183,82 @@ impl From<RoomError> for ServerError {
             RoomError::UserNotInRoom => Self::UserNotInRoom,
             RoomError::StreamKeyNotFound => Self::StreamKeyNotFound,
             RoomError::StreamKeyNotOwn => Self::StreamKeyNotOwn,
+            RoomError::UnsupportedFormat(format) => Self::UnsupportedStreamFormat(format),
+            RoomError::NoAcceptableFormat => Self::NoAcceptableStreamFormat,
+            RoomError::HlsUnavailable => Self::HlsUnavailable,
+            RoomError::HlsSegmentNotFound(identifier) => Self::NotFound {
+                resource: "segment",
+                identifier,
+            },
+            RoomError::RelayNotFound(identifier) => Self::NotFound {
+                resource: "relay",
+                identifier: identifier.to_string(),
+            },
+            RoomError::RelayNotOwn => Self::RelayNotOwn,
+            RoomError::InvalidRelayTarget(e) => Self::InvalidRelayTarget(e.to_string()),
+            RoomError::TrackNotFound(identifier) => Self::NotFound {
+                resource: "track",
+                identifier: identifier.to_string(),
+            },
+            RoomError::ArtworkNotFound(identifier) => Self::NotFound {
+                resource: "artwork",
+                identifier: identifier.to_string(),
+            },
+            RoomError::PreviewFailed(e) => Self::PreviewFailed(e),
+            RoomError::PreviewNotFound(identifier) => Self::NotFound {
+                resource: "preview",
+                identifier: identifier.to_string(),
+            },
+            RoomError::ClipsUnavailable => Self::ClipsUnavailable,
+            RoomError::ClipEmpty => Self::ClipEmpty,
+            RoomError::ClipTooLarge(max) => Self::ClipTooLarge(max),
+            RoomError::ClipFailed(e) => Self::Unknown(e.to_string()),
+            RoomError::ClipNotFound(identifier) => Self::NotFound {
+                resource: "clip",
+                identifier: identifier.to_string(),
+            },
+            RoomError::RecordingsUnavailable(e) => Self::Unknown(e.to_string()),
+            RoomError::LibraryTrackNotFound(identifier) => Self::NotFound {
+                resource: "library track",
+                identifier: identifier.to_string(),
+            },
+            RoomError::UploadNotFound(identifier) => Self::NotFound {
+                resource: "upload",
+                identifier: identifier.to_string(),
+            },
+            RoomError::UploadNotOwn => Self::UploadNotOwn,
+            RoomError::UploadTooLarge(max) => Self::UploadTooLarge(max),
+            RoomError::UnsupportedUploadType(content_type) => Self::UnsupportedUploadType(content_type),
+            RoomError::TooManyUploadFields(max) => Self::TooManyUploadFields(max),
+            RoomError::UploadFailed(e) => Self::Unknown(e.to_string()),
+            RoomError::Input(e) => e.into(),
             RoomError::Database(e) => e.into(),
         }
     }
 }
 
+impl From<LibraryError> for ServerError {
+    fn from(value: LibraryError) -> Self {
+        match value {
+            LibraryError::TrackNotFound(identifier) => Self::NotFound {
+                resource: "library track",
+                identifier: identifier.to_string(),
+            },
+            LibraryError::ArtworkNotFound(identifier) => Self::NotFound {
+                resource: "artwork",
+                identifier: identifier.to_string(),
+            },
+            LibraryError::Database(e) => e.into(),
+        }
+    }
+}
+
+impl From<MultipartError> for ServerError {
+    fn from(value: MultipartError) -> Self {
+        Self::InvalidUpload(value.body_text())
+    }
+}
+
 impl From<InputError> for ServerError {
     fn from(value: InputError) -> Self {
         match value {
@@ -144,6 +268,9 @@ impl From<InputError> for ServerError {
             InputError::NotFound => Self::InputNotFound,
             InputError::UnsupportedType => Self::UnsupportedInputType,
             InputError::ParseError(e) => Self::InputParseError(e),
+            InputError::PathDenied(path) => Self::InputPathDenied(path),
+            InputError::ExtractorDenied(extractor) => Self::InputExtractorDenied(extractor),
+            InputError::ExtractionFailed(e) => Self::InputExtractionFailed(e),
             e => Self::Unknown(e.to_string()),
         }
     }
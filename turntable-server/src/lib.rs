@@ -1,4 +1,4 @@
-use axum::{routing::get, Router as AxumRouter};
+use axum::{http::HeaderName, routing::get, Router as AxumRouter};
 use context::ServerContext;
 use sse::ServerSentEvents;
 use std::{
@@ -15,6 +15,8 @@ mod auth;
 mod context;
 mod docs;
 mod errors;
+mod library;
+mod metrics;
 mod rooms;
 mod schemas;
 mod serialized;
@@ -42,13 +44,17 @@ pub async fn run_server(collab: &Arc<Collab>) {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        // Lets browsers read the id of a preview, to control it with
+        .expose_headers([HeaderName::from_static("x-preview-id")]);
 
     let version_one_router = Router::new()
         .nest("/auth", auth::router())
         .nest("/rooms", rooms::router())
+        .nest("/library", library::router())
         .nest("/streams", streaming::router())
-        .nest("/events", sse::router());
+        .nest("/events", sse::router())
+        .nest("/metrics", metrics::router());
 
     let root_router = Router::new()
         .nest("/v1", version_one_router)
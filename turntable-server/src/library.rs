@@ -0,0 +1,126 @@
+use axum::{body::Body, extract::Path, http::Response, routing::get, Json};
+use turntable_collab::LibraryFilter;
+
+use crate::{
+    auth::Session,
+    context::ServerContext,
+    errors::ServerResult,
+    schemas::{LibraryAlbumsQuerySchema, LibraryTracksQuerySchema, ValidatedQuery},
+    serialized::{LibraryAlbum, LibraryArtist, LibraryTrack, ToSerialized},
+    Router,
+};
+
+/// How many tracks are returned when no limit is given.
+const DEFAULT_TRACK_LIMIT: i64 = 100;
+
+#[utoipa::path(
+    get,
+    path = "/v1/library/tracks",
+    tag = "library",
+    params(LibraryTracksQuerySchema),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Vec<LibraryTrack>, description = "The tracks that match every given filter, ordered by artist, album and title")
+    )
+)]
+async fn tracks(_session: Session, context: ServerContext, ValidatedQuery(query): ValidatedQuery<LibraryTracksQuerySchema>) -> ServerResult<Json<Vec<LibraryTrack>>> {
+    let filter = LibraryFilter {
+        artist: query.artist,
+        album: query.album,
+        text: query.query,
+        limit: query.limit.unwrap_or(DEFAULT_TRACK_LIMIT),
+        offset: query.offset.unwrap_or_default(),
+    };
+
+    let tracks = context.collab.library.tracks(filter).await?;
+
+    Ok(Json(tracks.to_serialized()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/library/tracks/{id}",
+    tag = "library",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = LibraryTrack)
+    )
+)]
+async fn track(_session: Session, context: ServerContext, Path(track_id): Path<i32>) -> ServerResult<Json<LibraryTrack>> {
+    let track = context.collab.library.track_by_id(track_id).await?;
+
+    Ok(Json(track.to_serialized()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/library/tracks/{id}/artwork",
+    tag = "library",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, content_type = "image/*", description = "The cover art that is embedded in the file of the track"),
+        (status = 404, description = "The track has no cover art")
+    )
+)]
+async fn artwork(_session: Session, context: ServerContext, Path(track_id): Path<i32>) -> ServerResult<Response<Body>> {
+    let artwork = context.collab.library.artwork(track_id).await?;
+
+    let response = Response::builder()
+        .status(200)
+        .header("Content-Type", artwork.media_type)
+        .header("Cache-Control", "private, max-age=3600")
+        .body(Body::from(artwork.data))
+        .unwrap();
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/library/artists",
+    tag = "library",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Vec<LibraryArtist>)
+    )
+)]
+async fn artists(_session: Session, context: ServerContext) -> ServerResult<Json<Vec<LibraryArtist>>> {
+    let artists = context.collab.library.artists().await?;
+
+    Ok(Json(artists.to_serialized()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/library/albums",
+    tag = "library",
+    params(LibraryAlbumsQuerySchema),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Vec<LibraryAlbum>)
+    )
+)]
+async fn albums(_session: Session, context: ServerContext, ValidatedQuery(query): ValidatedQuery<LibraryAlbumsQuerySchema>) -> ServerResult<Json<Vec<LibraryAlbum>>> {
+    let albums = context.collab.library.albums(query.artist.as_deref()).await?;
+
+    Ok(Json(albums.to_serialized()))
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/tracks", get(tracks))
+        .route("/tracks/:id", get(track))
+        .route("/tracks/:id/artwork", get(artwork))
+        .route("/artists", get(artists))
+        .route("/albums", get(albums))
+}
@@ -0,0 +1,29 @@
+use axum::{response::IntoResponse, routing::get, Json};
+use turntable_core::Metrics as CoreMetrics;
+
+use crate::{
+    auth::Session,
+    serialized::{Metrics, ToSerialized},
+    Router,
+};
+
+#[utoipa::path(
+    get, 
+    path = "/v1/metrics",
+    tag = "metrics",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Metrics)
+    )
+)]
+async fn metrics(_session: Session) -> impl IntoResponse {
+    let metrics: Metrics = CoreMetrics::global().snapshot().to_serialized();
+
+    Json(metrics)
+}
+
+pub fn router() -> Router {
+    Router::new().route("/", get(metrics))
+}
@@ -1,15 +1,19 @@
-use axum::{extract::Path, response::IntoResponse, routing::{get, post}, Json};
-use turntable_collab::{Input, NewRoom, Track as CollabTrack};
-use turntable_core::Queue as CoreQueue;
+use std::io;
+
+use axum::{body::Body, extract::{DefaultBodyLimit, Multipart, Path}, http::{header::ACCEPT, HeaderMap, Response}, response::IntoResponse, routing::{delete, get, post}, Json};
+use futures_util::stream;
+use tokio::{fs::File, io::AsyncReadExt};
+use turntable_collab::{IcecastMethod, NewRelay, NewRoom, StreamPreferences};
+use turntable_core::{EncoderOptions, Queue as CoreQueue};
 
 use crate::{
     auth::Session,
     context::ServerContext,
-    errors::ServerResult,
+    errors::{ServerError, ServerResult},
     schemas::{
-        InputSchema, JoinWithInviteSchema, NewRoomSchema, NewStreamKeySchema, RoomActionSchema, ValidatedJson
+        EnqueueLibrarySchema, InputSchema, JoinWithInviteSchema, NewClipSchema, NewRelaySchema, NewRoomSchema, NewStreamKeySchema, PreviewActionSchema, PreviewQuerySchema, RoomActionSchema, ValidatedJson, ValidatedQuery
     },
-    serialized::{Queue, Room, RoomInvite, StreamKey, ToSerialized}, Router
+    serialized::{Clip, Queue, Recordings, Relay, Room, RoomInvite, StreamKey, ToSerialized, Upload}, Router
 };
 
 #[utoipa::path(
@@ -109,7 +113,13 @@ async fn stream_keys(session: Session, context: ServerContext, Path(room_id): Pa
     )
 )]
 async fn create_stream_key(session: Session, context: ServerContext, Path(room_id): Path<i32>, ValidatedJson(body): ValidatedJson<NewStreamKeySchema>) -> ServerResult<Json<StreamKey>> {
-    let new_key = context.collab.rooms.create_stream_key(room_id, session.user.id, body.source).await?;
+    let options = EncoderOptions {
+        bitrate: body.bitrate,
+        complexity: body.complexity,
+        ..Default::default()
+    };
+
+    let new_key = context.collab.rooms.create_stream_key(room_id, session.user.id, body.source, body.format, options).await?;
 
     Ok(Json(new_key.to_serialized()))
 }
@@ -132,6 +142,106 @@ async fn queue(_session: Session, context: ServerContext, Path(room_id): Path<i3
     Ok(Json(queue.tracks().to_serialized()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/rooms/{id}/queue/{track_id}/preview",
+    tag = "rooms",
+    params(PreviewQuerySchema),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (
+            status = 200,
+            content_type = "application/octet-stream",
+            description = "A private stream of a track in the queue, which plays without affecting the room and ends with the track or when the request is closed",
+            headers(
+                ("X-Preview-Id" = u64, description = "The id of the preview, to control it with while it's being streamed")
+            )
+        )
+    )
+)]
+async fn preview_track(
+    session: Session,
+    context: ServerContext,
+    Path((room_id, track_id)): Path<(i32, u64)>,
+    ValidatedQuery(query): ValidatedQuery<PreviewQuerySchema>,
+    headers: HeaderMap,
+) -> ServerResult<Response<Body>> {
+    let preferences = StreamPreferences {
+        format: query.format,
+        accept: headers.get(ACCEPT).and_then(|h| h.to_str().ok()).map(|h| h.to_string()),
+        ..Default::default()
+    };
+
+    let handle = context
+        .collab
+        .rooms
+        .preview(room_id, session.user.id, track_id, query.position.unwrap_or_default(), preferences)
+        .await?;
+
+    let content_type = handle.content_type();
+    let preview_id = handle.id.value();
+
+    let response = Response::builder()
+        .status(200)
+        .header("Transfer-Encoding", "chunked")
+        .header("Content-Type", content_type)
+        .header("Cache-Control", "no-store")
+        .header("X-Preview-Id", preview_id)
+        .body(Body::from_stream(handle))
+        .unwrap();
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/rooms/{id}/queue/{track_id}/artwork",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, content_type = "image/*", description = "The cover art that is embedded in the file of a track in the queue or history"),
+        (status = 403, description = "User is not a member of the room"),
+        (status = 404, description = "The track isn't in the queue or history, or has no embedded cover art")
+    )
+)]
+async fn track_artwork(session: Session, context: ServerContext, Path((room_id, track_id)): Path<(i32, u64)>) -> ServerResult<Response<Body>> {
+    let artwork = context.collab.rooms.track_artwork(room_id, session.user.id, track_id)?;
+
+    let response = Response::builder()
+        .status(200)
+        .header("Content-Type", artwork.media_type)
+        .header("Cache-Control", "private, max-age=3600")
+        .body(Body::from(artwork.data))
+        .unwrap();
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rooms/{id}/previews/{preview_id}/actions",
+    tag = "rooms",
+    request_body = PreviewActionSchema,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "Action was performed on the preview."),
+        (status = 404, description = "The preview ended, or it was started by another user")
+    )
+)]
+async fn perform_preview_action(session: Session, context: ServerContext, Path((room_id, preview_id)): Path<(i32, u64)>, Json(body): Json<PreviewActionSchema>) -> ServerResult<()> {
+    match body {
+        PreviewActionSchema::Seek { to } => context.collab.rooms.seek_preview(room_id, session.user.id, preview_id, to)?,
+    }
+
+    Ok(())
+}
+
 #[utoipa::path(
     post,
     path = "/v1/rooms/{id}/queue",
@@ -145,15 +255,26 @@ async fn queue(_session: Session, context: ServerContext, Path(room_id): Path<i3
     )
 )]
 async fn add_to_queue(session: Session, context: ServerContext, Path(room_id): Path<i32>, ValidatedJson(body): ValidatedJson<InputSchema>) -> ServerResult<()> {
-    let room = context.collab.rooms.room_by_id(room_id)?;
-    let queue = room.queue()?;
+    context.collab.rooms.enqueue(room_id, session.user.id, &body.query).await?;
 
-    let input = Input::query(&body.query).await?;
-    let tracks: Vec<CollabTrack> = input.into_iter().map(Into::into).collect();
+    Ok(())
+}
 
-    for track in tracks {
-        queue.push(track, session.user.id)
-    }
+#[utoipa::path(
+    post,
+    path = "/v1/rooms/{id}/queue/library",
+    tag = "rooms",
+    request_body = EnqueueLibrarySchema,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "The library tracks were added to the queue"),
+        (status = 404, description = "One of the tracks doesn't exist, so none were added")
+    )
+)]
+async fn add_library_tracks_to_queue(session: Session, context: ServerContext, Path(room_id): Path<i32>, ValidatedJson(body): ValidatedJson<EnqueueLibrarySchema>) -> ServerResult<()> {
+    context.collab.rooms.enqueue_library_tracks(room_id, session.user.id, &body.track_ids).await?;
 
     Ok(())
 }
@@ -224,11 +345,283 @@ async fn perform_room_action(_session: Session, context: ServerContext, Path(roo
     match body {
         RoomActionSchema::Play => { room.player()?.play() },
         RoomActionSchema::Pause => { room.player()?.pause() },
-        RoomActionSchema::Next => { room.queue()?.next() },
+        RoomActionSchema::Next => { room.player()?.skip() },
         RoomActionSchema::Previous => { room.queue()?.previous() },
-        RoomActionSchema::Seek { to } => { room.player()?.seek(to) }
+        RoomActionSchema::Seek { to } => { room.player()?.seek(to) },
+        RoomActionSchema::StartRecording => { room.start_recording()? },
+        RoomActionSchema::StopRecording => { room.stop_recording() }
+    };
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get, 
+    path = "/v1/rooms/{id}/relays",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Vec<Relay>),
+        (status = 403, description = "The user is not in the room")
+    )
+)]
+async fn relays(session: Session, context: ServerContext, Path(room_id): Path<i32>) -> ServerResult<Json<Vec<Relay>>> {
+    let relays = context.collab.rooms.relays(room_id, session.user.id)?;
+
+    Ok(Json(relays.to_serialized()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rooms/{id}/relays",
+    tag = "rooms",
+    request_body = NewRelaySchema,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Relay, description = "The room is being pushed to the Icecast server, and will reconnect until the relay is deleted")
+    )
+)]
+async fn create_relay(session: Session, context: ServerContext, Path(room_id): Path<i32>, ValidatedJson(body): ValidatedJson<NewRelaySchema>) -> ServerResult<Json<Relay>> {
+    let method = match body.legacy {
+        Some(true) => IcecastMethod::Source,
+        _ => IcecastMethod::Put,
     };
 
+    let new_relay = NewRelay {
+        url: body.url,
+        method,
+        format: body.format,
+        options: EncoderOptions {
+            bitrate: body.bitrate,
+            complexity: body.complexity,
+            ..Default::default()
+        },
+    };
+
+    let relay = context.collab.rooms.start_relay(room_id, session.user.id, new_relay)?;
+
+    Ok(Json(relay.to_serialized()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/rooms/{id}/relays/{relay_id}",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "The relay was stopped and removed"),
+        (status = 403, description = "The user is not in the room, or neither created the relay nor owns the room")
+    )
+)]
+async fn delete_relay(session: Session, context: ServerContext, Path((room_id, relay_id)): Path<(i32, u64)>) -> ServerResult<()> {
+    context.collab.rooms.stop_relay(room_id, session.user.id, relay_id)?;
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/rooms/{id}/recordings",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Recordings, description = "Whether the room is being recorded, and the recorded files with the tracks that played in them"),
+        (status = 403, description = "User is not a member of the room")
+    )
+)]
+async fn recordings(session: Session, context: ServerContext, Path(room_id): Path<i32>) -> ServerResult<Json<Recordings>> {
+    let files = context.collab.rooms.recordings(room_id, session.user.id)?;
+    let room = context.collab.rooms.room_by_id(room_id)?;
+
+    Ok(Json(Recordings {
+        status: room.recorder_state().to_serialized(),
+        files: files.to_serialized(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/rooms/{id}/clips",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Vec<Clip>, description = "The clips of the room that haven't expired yet"),
+        (status = 403, description = "User is not a member of the room")
+    )
+)]
+async fn clips(session: Session, context: ServerContext, Path(room_id): Path<i32>) -> ServerResult<Json<Vec<Clip>>> {
+    let clips = context.collab.rooms.clips(room_id, session.user.id)?;
+
+    Ok(Json(clips.to_serialized()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rooms/{id}/clips",
+    tag = "rooms",
+    request_body = NewClipSchema,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Clip, description = "The last seconds of the room were captured, and can be downloaded until the clip expires"),
+        (status = 409, description = "Time-shifting is disabled, or it was only just started by this request, so there is nothing to capture from yet"),
+        (status = 413, description = "The clip takes up more than the clips of a room can take up together")
+    )
+)]
+async fn create_clip(session: Session, context: ServerContext, Path(room_id): Path<i32>, ValidatedJson(body): ValidatedJson<NewClipSchema>) -> ServerResult<Json<Clip>> {
+    let clip = context.collab.rooms.create_clip(room_id, session.user.id, body.seconds, body.format).await?;
+
+    Ok(Json(clip.to_serialized()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/rooms/{id}/clips/{clip_id}",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, content_type = "application/octet-stream", description = "The encoded clip, as a file download"),
+        (status = 403, description = "User is not a member of the room")
+    )
+)]
+async fn download_clip(session: Session, context: ServerContext, Path((room_id, clip_id)): Path<(i32, u64)>) -> ServerResult<Response<Body>> {
+    let clip = context.collab.rooms.clip(room_id, session.user.id, clip_id)?;
+    let room = context.collab.rooms.room_by_id(room_id)?;
+
+    let file_name = format!("{}-clip-{}.{}", room.data().slug, clip.id.value(), clip.format);
+
+    // The open file can still be read if the clip expires during the download
+    let file = File::open(&clip.path).await.map_err(|e| ServerError::Unknown(e.to_string()))?;
+
+    let chunks = stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut buf = vec![0; 64 * 1024];
+
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(amount) => {
+                buf.truncate(amount);
+                Some((Ok(buf), Some(file)))
+            },
+            Err(e) => Some((Err::<Vec<u8>, io::Error>(e), None)),
+        }
+    });
+
+    let response = Response::builder()
+        .status(200)
+        .header("Content-Type", &clip.content_type)
+        .header("Content-Length", clip.size_in_bytes)
+        .header("Content-Disposition", format!("attachment; filename=\"{}\"", file_name))
+        .body(Body::from_stream(chunks))
+        .unwrap();
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/rooms/{id}/uploads",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Vec<Upload>, description = "The files that were uploaded to the room, oldest first")
+    )
+)]
+async fn uploads(session: Session, context: ServerContext, Path(room_id): Path<i32>) -> ServerResult<Json<Vec<Upload>>> {
+    let uploads = context.collab.rooms.uploads(room_id, session.user.id).await?;
+
+    Ok(Json(uploads.to_serialized()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rooms/{id}/uploads",
+    tag = "rooms",
+    request_body(content = String, content_type = "multipart/form-data", description = "One or more audio files, each in a field with a file name"),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, body = Vec<Upload>, description = "The files were stored and added to the queue. Files that were already uploaded to the room are reused"),
+        (status = 413, description = "A file is larger than the configured limit, or the request has more fields than allowed"),
+        (status = 415, description = "A file is not audio that can be played")
+    )
+)]
+async fn create_uploads(session: Session, context: ServerContext, Path(room_id): Path<i32>, mut multipart: Multipart) -> ServerResult<Json<Vec<Upload>>> {
+    let rooms = &context.collab.rooms;
+    let mut uploads = vec![];
+    let mut field_count = 0;
+
+    while let Some(mut field) = multipart.next_field().await? {
+        // Every field counts, since the body isn't limited otherwise
+        field_count += 1;
+        rooms.check_upload_fields(field_count)?;
+
+        // Fields without a file name are regular form values
+        let Some(file_name) = field.file_name().map(|f| f.to_string()) else {
+            continue;
+        };
+
+        let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+        let mut pending = rooms.begin_upload(room_id, session.user.id, file_name, content_type).await?;
+
+        while let Some(chunk) = field.chunk().await? {
+            pending.write(&chunk).await?;
+        }
+
+        uploads.push(rooms.finish_upload(pending).await?);
+    }
+
+    Ok(Json(uploads.to_serialized()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rooms/{id}/uploads/{upload_id}/queue",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "The uploaded file was added to the queue")
+    )
+)]
+async fn enqueue_upload(session: Session, context: ServerContext, Path((room_id, upload_id)): Path<(i32, i32)>) -> ServerResult<()> {
+    context.collab.rooms.enqueue_upload(room_id, session.user.id, upload_id).await?;
+
+    Ok(())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/rooms/{id}/uploads/{upload_id}",
+    tag = "rooms",
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "The upload was removed, along with its file if no other room uses it"),
+        (status = 403, description = "Only the user that uploaded the file can delete it")
+    )
+)]
+async fn delete_upload(session: Session, context: ServerContext, Path((room_id, upload_id)): Path<(i32, i32)>) -> ServerResult<()> {
+    context.collab.rooms.delete_upload(room_id, session.user.id, upload_id).await?;
+
     Ok(())
 }
 
@@ -243,6 +636,22 @@ pub fn router() -> Router {
         .route("/:id/keys", post(create_stream_key))
         .route("/:id/queue", get(queue))
         .route("/:id/queue", post(add_to_queue))
+        .route("/:id/queue/library", post(add_library_tracks_to_queue))
+        .route("/:id/queue/:track_id/preview", get(preview_track))
+        .route("/:id/queue/:track_id/artwork", get(track_artwork))
+        .route("/:id/previews/:preview_id/actions", post(perform_preview_action))
         .route("/:id/invites", post(create_invite))
         .route("/:id/actions", post(perform_room_action))
+        .route("/:id/relays", get(relays))
+        .route("/:id/relays", post(create_relay))
+        .route("/:id/relays/:relay_id", delete(delete_relay))
+        .route("/:id/recordings", get(recordings))
+        .route("/:id/clips", get(clips))
+        .route("/:id/clips", post(create_clip))
+        .route("/:id/clips/:clip_id", get(download_clip))
+        .route("/:id/uploads", get(uploads))
+        // Files are streamed to disk, where their size is limited per file
+        .route("/:id/uploads", post(create_uploads).layer(DefaultBodyLimit::disable()))
+        .route("/:id/uploads/:upload_id", delete(delete_upload))
+        .route("/:id/uploads/:upload_id/queue", post(enqueue_upload))
 }
@@ -5,10 +5,12 @@ use std::sync::Arc;
 
 use serde::Serialize;
 use turntable_collab::{
-    LinearQueueItem, Room as CollabRoom, RoomConnection as CollabRoomConnection, RoomInviteData,
-    RoomMemberData, SessionData, StreamKeyData, Track as CollabTrack, UserData,
+    Clip as CollabClip, CueEntry as CollabCueEntry, LinearQueueItem, RecorderState as CollabRecorderState, Recording as CollabRecording, Relay as CollabRelay, RelayState as CollabRelayState, Room as CollabRoom, RoomConnection as CollabRoomConnection, RoomInviteData,
+    RoomMemberData, SessionData, StreamKeyData, Track as CollabTrack, UploadData, UserData, LibraryTrackData, LibraryArtistData, LibraryAlbumData,
+};
+use turntable_core::{
+    HeardPosition as CoreHeardPosition, MetricsSnapshot, PlayerState as CorePlayerState,
 };
-use turntable_core::PlayerState as CorePlayerState;
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -68,6 +70,9 @@ pub struct StreamKey {
     source: String,
     room_id: i32,
     user_id: i32,
+    format: Option<String>,
+    bitrate: Option<i32>,
+    complexity: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -76,12 +81,16 @@ pub struct Track {
     id: i32,
     title: String,
     artist: String,
+    album: Option<String>,
 
     canonical: String,
     source: String,
 
     duration: f32,
+    /// A URL of the cover art, which points to `/v1/rooms/{id}/queue/{track_id}/artwork` if it's embedded in the file
     artwork: Option<String>,
+    /// What a live stream is currently playing, if the track is one and it's known
+    stream_title: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -115,6 +124,151 @@ pub enum PlayerState {
     Buffering,
 }
 
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Metrics {
+    /// The amount of samples played into streams, counted once per stream however many listeners it has
+    pub streamed_samples: u64,
+    /// The amount of streamed samples beyond full scale
+    pub clipped_samples: u64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Relay {
+    id: i32,
+    user_id: i32,
+    /// The Icecast mount, without the password
+    url: String,
+    format: String,
+    status: RelayStatus,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum RelayStatus {
+    Connecting,
+    Connected,
+    Retrying { attempt: u32, error: String },
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Clip {
+    id: i32,
+    /// The user that captured the clip
+    user_id: i32,
+    format: String,
+    content_type: String,
+    /// The length of the clip, in seconds
+    duration: f32,
+    /// What was playing when the clip was captured
+    track: Option<Track>,
+    /// When the clip was captured, as an RFC 3339 timestamp
+    created_at: String,
+    /// When the clip can no longer be downloaded, as an RFC 3339 timestamp
+    expires_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Upload {
+    id: i32,
+    /// The user that uploaded the file, who is the only one that can delete it
+    user_id: i32,
+    /// The name of the file as it was uploaded
+    file_name: String,
+    content_type: String,
+    /// The size of the file, in bytes
+    size: i64,
+    /// The title from the tags of the file, or its name without the extension
+    title: String,
+    artist: Option<String>,
+    /// The length of the file, in seconds
+    duration: Option<f32>,
+    /// When the file was uploaded, as an RFC 3339 timestamp
+    created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryTrack {
+    id: i32,
+    title: String,
+    artist: Option<String>,
+    album: Option<String>,
+    /// The length of the track, in seconds
+    duration: Option<f32>,
+    /// Whether the file has embedded cover art, which can be downloaded separately
+    has_artwork: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryArtist {
+    name: String,
+    track_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryAlbum {
+    title: String,
+    artist: Option<String>,
+    track_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Recordings {
+    pub status: RecorderStatus,
+    pub files: Vec<Recording>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum RecorderStatus {
+    Idle,
+    Recording,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    file_name: String,
+    size_in_bytes: u64,
+    /// When the file was started, as an RFC 3339 timestamp
+    started_at: String,
+    /// The amount of audio in the file, in seconds
+    duration: f32,
+    tracks: Vec<RecordedTrack>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedTrack {
+    /// Where the track starts in the file, in seconds
+    offset: f32,
+    title: String,
+    artist: Option<String>,
+    canonical: String,
+    source: String,
+    user_id: i32,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HeardPosition {
+    /// The stream format, such as `wav`
+    format: String,
+    /// The estimated position listeners of the format hear in the current track, in seconds
+    position: f32,
+    /// The estimated total position listeners of the format hear, in seconds
+    total_position: f32,
+}
+
 /// Helper trait to convert any type into a serialized version
 pub trait ToSerialized<T>
 where
@@ -215,6 +369,9 @@ impl ToSerialized<StreamKey> for StreamKeyData {
             source: self.source.clone(),
             room_id: self.room_id,
             user_id: self.user_id,
+            format: self.format.clone(),
+            bitrate: self.bitrate,
+            complexity: self.complexity,
         }
     }
 }
@@ -234,10 +391,12 @@ impl ToSerialized<Track> for CollabTrack {
         Track {
             id: self.id.value() as i32,
             title: self.metadata.title.clone(),
+            album: self.metadata.album.clone(),
             artwork: self.metadata.artwork.clone(),
             canonical: self.metadata.canonical.clone(),
             source: self.metadata.source.clone(),
             duration: self.metadata.duration,
+            stream_title: self.stream_title(),
             artist: self
                 .metadata
                 .artist
@@ -274,3 +433,139 @@ impl ToSerialized<PlayerState> for CorePlayerState {
         }
     }
 }
+
+impl ToSerialized<Metrics> for MetricsSnapshot {
+    fn to_serialized(&self) -> Metrics {
+        Metrics {
+            streamed_samples: self.streamed_samples,
+            clipped_samples: self.clipped_samples,
+        }
+    }
+}
+
+impl ToSerialized<Relay> for Arc<CollabRelay> {
+    fn to_serialized(&self) -> Relay {
+        let status = match self.state() {
+            CollabRelayState::Connecting => RelayStatus::Connecting,
+            CollabRelayState::Connected => RelayStatus::Connected,
+            CollabRelayState::Retrying { attempt, error } => RelayStatus::Retrying { attempt, error },
+            CollabRelayState::Stopped => RelayStatus::Stopped,
+        };
+
+        Relay {
+            id: self.id.value() as i32,
+            user_id: self.user_id,
+            url: self.target.redacted_url(),
+            format: self.format.clone(),
+            status,
+        }
+    }
+}
+
+impl ToSerialized<Clip> for Arc<CollabClip> {
+    fn to_serialized(&self) -> Clip {
+        Clip {
+            id: self.id.value() as i32,
+            user_id: self.user_id,
+            format: self.format.clone(),
+            content_type: self.content_type.clone(),
+            duration: self.duration,
+            track: self.track.as_ref().map(|t| t.to_serialized()),
+            created_at: self.created_at.to_rfc3339(),
+            expires_at: self.expires_at.to_rfc3339(),
+        }
+    }
+}
+
+impl ToSerialized<RecorderStatus> for Option<CollabRecorderState> {
+    fn to_serialized(&self) -> RecorderStatus {
+        match self {
+            Some(CollabRecorderState::Recording) => RecorderStatus::Recording,
+            Some(CollabRecorderState::Failed(error)) => RecorderStatus::Failed { error: error.clone() },
+            Some(CollabRecorderState::Stopped) | None => RecorderStatus::Idle,
+        }
+    }
+}
+
+impl ToSerialized<Recording> for CollabRecording {
+    fn to_serialized(&self) -> Recording {
+        Recording {
+            file_name: self.file_name.clone(),
+            size_in_bytes: self.size_in_bytes,
+            started_at: self.cue_sheet.started_at.to_rfc3339(),
+            duration: self.cue_sheet.duration,
+            tracks: self.cue_sheet.entries.to_serialized(),
+        }
+    }
+}
+
+impl ToSerialized<RecordedTrack> for CollabCueEntry {
+    fn to_serialized(&self) -> RecordedTrack {
+        RecordedTrack {
+            offset: self.offset,
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            canonical: self.canonical.clone(),
+            source: self.source.clone(),
+            user_id: self.user_id,
+        }
+    }
+}
+
+impl ToSerialized<HeardPosition> for CoreHeardPosition {
+    fn to_serialized(&self) -> HeardPosition {
+        HeardPosition {
+            format: self.format.clone(),
+            position: self.position,
+            total_position: self.total_position,
+        }
+    }
+}
+
+impl ToSerialized<Upload> for UploadData {
+    fn to_serialized(&self) -> Upload {
+        Upload {
+            id: self.id,
+            user_id: self.user_id,
+            file_name: self.file_name.clone(),
+            content_type: self.content_type.clone(),
+            size: self.size,
+            title: self.display_title(),
+            artist: self.artist.clone(),
+            duration: self.duration,
+            created_at: self.created_at.to_rfc3339(),
+        }
+    }
+}
+
+impl ToSerialized<LibraryTrack> for LibraryTrackData {
+    fn to_serialized(&self) -> LibraryTrack {
+        LibraryTrack {
+            id: self.id,
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            album: self.album.clone(),
+            duration: self.duration,
+            has_artwork: self.artwork_type.is_some(),
+        }
+    }
+}
+
+impl ToSerialized<LibraryArtist> for LibraryArtistData {
+    fn to_serialized(&self) -> LibraryArtist {
+        LibraryArtist {
+            name: self.name.clone(),
+            track_count: self.track_count,
+        }
+    }
+}
+
+impl ToSerialized<LibraryAlbum> for LibraryAlbumData {
+    fn to_serialized(&self) -> LibraryAlbum {
+        LibraryAlbum {
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            track_count: self.track_count,
+        }
+    }
+}
@@ -20,7 +20,7 @@ use utoipa::ToSchema;
 
 use crate::{
     context::ServerContext,
-    serialized::{PlayerState, QueueItem, RoomMember, ToSerialized},
+    serialized::{HeardPosition, PlayerState, QueueItem, RoomMember, ToSerialized},
     Router,
 };
 
@@ -41,6 +41,8 @@ pub enum ServerEvent {
         position: f32,
         /// The total position of the player, in seconds.
         total_position: f32,
+        /// The estimated positions listeners hear, for every stream format.
+        heard_positions: Vec<HeardPosition>,
     },
     /// A track as a queue item has been ingested
     TrackActivated {
@@ -58,10 +60,18 @@ pub enum ServerEvent {
         /// The error that happened while activating the queue item.
         error: String,
     },
+    /// The title of what a live track is playing changed
+    TrackStreamTitleUpdate {
+        room_id: i32,
+        track_id: i32,
+        title: String,
+    },
     /// The currently playing track of a room updated
     RoomQueueItemUpdate {
         room_id: i32,
         new_item: Option<QueueItem>,
+        /// The total position the new item starts at, so listeners can switch once they hear it.
+        total_position: f32,
     },
     /// A queue was modified and updated
     RoomQueueUpdate {
@@ -101,14 +111,21 @@ impl From<CollabEvent> for ServerEvent {
                 room_id,
                 position,
                 total_position,
+                heard_positions,
             } => Self::PlayerTimeUpdate {
                 room_id,
                 position,
                 total_position,
+                heard_positions: heard_positions.to_serialized(),
             },
-            CollabEvent::RoomQueueItemUpdate { room_id, new_item } => Self::RoomQueueItemUpdate {
+            CollabEvent::RoomQueueItemUpdate {
+                room_id,
+                new_item,
+                total_position,
+            } => Self::RoomQueueItemUpdate {
                 room_id,
                 new_item: new_item.to_serialized(),
+                total_position,
             },
             CollabEvent::RoomQueueUpdate {
                 room_id,
@@ -132,6 +149,15 @@ impl From<CollabEvent> for ServerEvent {
                 track_id: track_id.value() as i32,
                 error,
             },
+            CollabEvent::TrackStreamTitleUpdate {
+                room_id,
+                track_id,
+                title,
+            } => Self::TrackStreamTitleUpdate {
+                room_id,
+                track_id: track_id.value() as i32,
+                title,
+            },
             CollabEvent::UserConnected {
                 room_id,
                 user_id,
@@ -1,38 +1,484 @@
-use axum::{body::Body, extract::Path, response::Response, routing::get};
+use std::time::{Duration, Instant};
 
-use crate::{context::ServerContext, errors::ServerResult, Router};
+use axum::{
+    body::Body,
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        Path, WebSocketUpgrade,
+    },
+    http::{
+        header::{ACCEPT, HOST},
+        HeaderMap,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Serialize;
+use tokio::{
+    select,
+    sync::mpsc::{self, error::TrySendError},
+    task::spawn_blocking,
+    time::interval,
+};
+use turntable_collab::{PlaylistFormat, RoomConnectionHandle, StreamPacket};
+use turntable_core::{Config, HlsPackager, SampleFormat};
+
+use crate::{
+    context::ServerContext,
+    errors::ServerResult,
+    schemas::{SampleFormatSchema, StreamQuerySchema, ValidatedQuery},
+    Router,
+};
 
 #[utoipa::path(
     get, 
     path = "/v1/streams/{token}",
     tag = "streaming",
+    params(StreamQuerySchema),
     responses(
         (
             status = 200,
             content_type = "application/octet-stream",
-            description = "A live audio stream"
+            description = "A live audio stream, in the format picked by the format parameter, the Accept header, or the stream key. \
+                With an `Icy-MetaData: 1` header, the title of what is playing is interleaved every `icy-metaint` bytes"
+        ),
+        (
+            status = 406,
+            description = "None of the formats in the Accept header are supported"
         )
     )
 )]
 async fn stream_audio(
     context: ServerContext,
     Path(token): Path<String>,
+    ValidatedQuery(query): ValidatedQuery<StreamQuerySchema>,
+    headers: HeaderMap,
 ) -> ServerResult<Response<Body>> {
-    let handle = context.collab.rooms.connect(token).await?;
+    let accept = headers
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.to_string());
+
+    let icy_metadata = headers
+        .get("Icy-MetaData")
+        .is_some_and(|h| h.as_bytes() == b"1");
+
+    let mut preferences = query.into_preferences(accept);
+    preferences.icy_metadata = icy_metadata;
+
+    let handle = context.collab.rooms.connect(token, preferences).await?;
     let content_type = handle.content_type();
+    let icy_metaint = handle.icy_metaint();
     let body = Body::from_stream(handle);
 
-    let response = Response::builder()
+    let mut response = Response::builder()
         .status(200)
         .header("Transfer-Encoding", "chunked")
         .header("Content-Type", content_type)
-        .header("Cache-Control", "no-store")
-        .body(body)
+        .header("Cache-Control", "no-store");
+
+    if let Some(metaint) = icy_metaint {
+        response = response.header("icy-metaint", metaint);
+    }
+
+    Ok(response.body(body).unwrap())
+}
+
+#[utoipa::path(
+    get, 
+    path = "/v1/streams/{token}/hls/index.m3u8",
+    tag = "streaming",
+    params(StreamQuerySchema),
+    responses(
+        (
+            status = 200,
+            content_type = "application/vnd.apple.mpegurl",
+            description = "A rolling HLS media playlist of the stream, in a segmentable format such as `mp3`"
+        ),
+        (
+            status = 400,
+            description = "The requested format can't be segmented"
+        ),
+        (
+            status = 501,
+            description = "No segmentable format is available, because the server was built without the `mp3` feature"
+        )
+    )
+)]
+async fn hls_playlist(
+    context: ServerContext,
+    Path(token): Path<String>,
+    ValidatedQuery(query): ValidatedQuery<StreamQuerySchema>,
+) -> ServerResult<Response<Body>> {
+    let preferences = query.into_preferences(None);
+    let playlist = context
+        .collab
+        .rooms
+        .hls_playlist(token, preferences)
+        .await?;
+
+    let response = Response::builder()
+        .status(200)
+        .header("Content-Type", HlsPackager::PLAYLIST_CONTENT_TYPE)
+        .header("Cache-Control", "no-cache")
+        .body(Body::from(playlist))
+        .unwrap();
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/streams/{token}/playlist.m3u",
+    tag = "streaming",
+    responses(
+        (
+            status = 200,
+            content_type = "audio/x-mpegurl",
+            description = "An M3U playlist with the stream of the room in every container format and HLS, to open in apps such as VLC or mpv"
+        )
+    )
+)]
+async fn m3u_playlist(context: ServerContext, Path(token): Path<String>, headers: HeaderMap) -> ServerResult<Response<Body>> {
+    stream_playlist(context, token, &headers, PlaylistFormat::M3u).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/streams/{token}/playlist.pls",
+    tag = "streaming",
+    responses(
+        (
+            status = 200,
+            content_type = "audio/x-scpls",
+            description = "A PLS playlist with the stream of the room in every container format and HLS"
+        )
+    )
+)]
+async fn pls_playlist(context: ServerContext, Path(token): Path<String>, headers: HeaderMap) -> ServerResult<Response<Body>> {
+    stream_playlist(context, token, &headers, PlaylistFormat::Pls).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/streams/{token}/playlist.xspf",
+    tag = "streaming",
+    responses(
+        (
+            status = 200,
+            content_type = "application/xspf+xml",
+            description = "An XSPF playlist with the stream of the room in every container format and HLS"
+        )
+    )
+)]
+async fn xspf_playlist(context: ServerContext, Path(token): Path<String>, headers: HeaderMap) -> ServerResult<Response<Body>> {
+    stream_playlist(context, token, &headers, PlaylistFormat::Xspf).await
+}
+
+/// Responds with a playlist of the streams of a room, pointing at this server as it was reached.
+async fn stream_playlist(
+    context: ServerContext,
+    token: String,
+    headers: &HeaderMap,
+    format: PlaylistFormat,
+) -> ServerResult<Response<Body>> {
+    let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+
+    // Proxies pass on how the client reached them, which is what the player has to connect to
+    let scheme = header("X-Forwarded-Proto").unwrap_or("http");
+    let host = header("X-Forwarded-Host")
+        .or(header(HOST.as_str()))
+        .unwrap_or("localhost");
+
+    let streams_url = format!("{}://{}/v1/streams", scheme, host);
+    let playlist = context
+        .collab
+        .rooms
+        .stream_playlist(token, &streams_url)
+        .await?;
+
+    let response = Response::builder()
+        .status(200)
+        .header("Content-Type", format.content_type())
+        .header("Cache-Control", "no-cache")
+        .body(Body::from(playlist.render(format)))
+        .unwrap();
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    get, 
+    path = "/v1/streams/{token}/hls/{segment}",
+    tag = "streaming",
+    responses(
+        (
+            status = 200,
+            content_type = "application/octet-stream",
+            description = "A segment listed in the HLS media playlist"
+        ),
+        (
+            status = 404,
+            description = "The segment is no longer available"
+        )
+    )
+)]
+async fn hls_segment(
+    context: ServerContext,
+    Path((token, segment)): Path<(String, String)>,
+) -> ServerResult<Response<Body>> {
+    let (content_type, segment) = context.collab.rooms.hls_segment(token, &segment).await?;
+
+    let response = Response::builder()
+        .status(200)
+        .header("Content-Type", content_type)
+        .header("Cache-Control", "max-age=60")
+        .body(Body::from(segment.bytes.to_vec()))
         .unwrap();
 
     Ok(response)
 }
 
+/// How long a WebSocket listener can fall behind before it's disconnected, so it can reconnect at the live edge.
+const WEBSOCKET_MAX_LAG: Duration = Duration::from_secs(2);
+
+/// How often WebSocket listeners are pinged, and a third of how long they can stay silent.
+const WEBSOCKET_PING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How many packets can wait to be sent, each being roughly a playback tick long.
+const WEBSOCKET_QUEUE_SIZE: usize = 4;
+
+/// The first message of a WebSocket stream, describing the audio in the packets after it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebSocketStreamHeader {
+    content_type: String,
+    sample_rate: usize,
+    channel_count: usize,
+    /// How the samples of raw PCM are stored, which can't be told from the audio itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample_format: Option<SampleFormatSchema>,
+}
+
+impl WebSocketStreamHeader {
+    /// The content type of raw PCM, which is sent as little-endian interleaved samples.
+    const RAW_CONTENT_TYPE: &'static str = "audio/pcm";
+
+    fn new(content_type: String, config: &Config, sample_format: SampleFormat) -> Self {
+        let is_raw = content_type == Self::RAW_CONTENT_TYPE;
+
+        Self {
+            content_type,
+            sample_rate: config.sample_rate,
+            channel_count: config.channel_count,
+            sample_format: is_raw.then(|| sample_format.into()),
+        }
+    }
+}
+
+/// Why packets stopped being read for a WebSocket listener.
+enum WebSocketReadEnd {
+    /// The listener fell too far behind.
+    Lagged,
+    /// No audio was encoded in time, or the listener is gone.
+    Stopped,
+}
+
+#[utoipa::path(
+    get, 
+    path = "/v1/streams/{token}/ws",
+    tag = "streaming",
+    params(StreamQuerySchema),
+    responses(
+        (
+            status = 101,
+            description = "A low latency WebSocket stream, in raw PCM by default. \
+                The first message is a JSON text message with the `contentType`, `sampleRate` and `channelCount` of the audio, \
+                and for raw PCM the `sampleFormat` of its little-endian interleaved samples. \
+                Every binary message after it starts with a big-endian `u32` sequence number and the `f32` total position of the player at the end of the packet in seconds, followed by the audio. \
+                Listeners that fall too far behind are closed with code 1013, and should reconnect"
+        ),
+        (
+            status = 406,
+            description = "The requested format is not supported"
+        )
+    )
+)]
+async fn stream_websocket(
+    context: ServerContext,
+    Path(token): Path<String>,
+    ValidatedQuery(query): ValidatedQuery<StreamQuerySchema>,
+    upgrade: WebSocketUpgrade,
+) -> ServerResult<Response> {
+    let sample_format = query.sample_format.map(SampleFormat::from).unwrap_or_default();
+
+    // Raw PCM doesn't need a decoder, so it's the quickest for a worklet to play
+    let preferences = query.into_preferences(Some(WebSocketStreamHeader::RAW_CONTENT_TYPE.to_string()));
+    let handle = context.collab.rooms.connect(token, preferences).await?;
+
+    Ok(upgrade
+        .on_upgrade(move |socket| send_packets(socket, handle, sample_format))
+        .into_response())
+}
+
+/// Sends the packets of a stream to a WebSocket listener, until either side stops.
+async fn send_packets(mut socket: WebSocket, handle: RoomConnectionHandle, sample_format: SampleFormat) {
+    let config = handle.consumer().config().clone();
+    let header = WebSocketStreamHeader::new(handle.content_type(), &config, sample_format);
+
+    let header = serde_json::to_string(&header).expect("header is serialized");
+
+    if socket.send(Message::Text(header)).await.is_err() {
+        return;
+    }
+
+    let (packet_sender, mut packet_receiver) = mpsc::channel(WEBSOCKET_QUEUE_SIZE);
+    let reader = spawn_blocking(move || read_packets(handle, packet_sender));
+
+    let mut sequence: u32 = 0;
+    let mut ping = interval(WEBSOCKET_PING_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    loop {
+        select! {
+            packet = packet_receiver.recv() => {
+                let Some(packet) = packet else {
+                    break;
+                };
+
+                let frame = frame_packet(sequence, &packet);
+                sequence = sequence.wrapping_add(1);
+
+                if socket.send(Message::Binary(frame)).await.is_err() {
+                    return;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                // Pings are answered automatically, so any message just shows the listener is alive
+                Some(Ok(_)) => last_seen = Instant::now(),
+            },
+            _ = ping.tick() => {
+                if last_seen.elapsed() > WEBSOCKET_PING_INTERVAL * 3 {
+                    return;
+                }
+
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    let close_frame = match reader.await {
+        Ok(WebSocketReadEnd::Lagged) => CloseFrame {
+            code: close_code::AGAIN,
+            reason: "Listener fell too far behind".into(),
+        },
+        _ => CloseFrame {
+            code: close_code::AWAY,
+            reason: "Stream ended".into(),
+        },
+    };
+
+    let _ = socket.send(Message::Close(Some(close_frame))).await;
+}
+
+/// Reads packets into the queue of a WebSocket listener.
+///
+/// While the queue is full, new audio is appended to a pending packet instead of being dropped,
+/// so encoded streams stay intact.
+fn read_packets(
+    handle: RoomConnectionHandle,
+    sender: mpsc::Sender<StreamPacket>,
+) -> WebSocketReadEnd {
+    let mut pending: Option<StreamPacket> = None;
+    let mut lagging_since = None;
+
+    while let Some(packet) = handle.read_packet(WEBSOCKET_MAX_LAG) {
+        let packet = match pending.take() {
+            Some(mut pending) => {
+                pending.bytes.extend_from_slice(&packet.bytes);
+                pending.total_position = packet.total_position;
+                pending
+            }
+            None => packet,
+        };
+
+        match sender.try_send(packet) {
+            Ok(()) => lagging_since = None,
+            Err(TrySendError::Full(packet)) => {
+                let since = *lagging_since.get_or_insert_with(Instant::now);
+
+                if since.elapsed() > WEBSOCKET_MAX_LAG {
+                    return WebSocketReadEnd::Lagged;
+                }
+
+                pending = Some(packet);
+            }
+            Err(TrySendError::Closed(_)) => break,
+        }
+    }
+
+    WebSocketReadEnd::Stopped
+}
+
+/// Frames a packet as its sequence number and total position in big-endian, followed by the audio.
+fn frame_packet(sequence: u32, packet: &StreamPacket) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(8 + packet.bytes.len());
+
+    frame.extend_from_slice(&sequence.to_be_bytes());
+    frame.extend_from_slice(&packet.total_position.to_be_bytes());
+    frame.extend_from_slice(&packet.bytes);
+
+    frame
+}
+
 pub fn router() -> Router {
-    Router::new().route("/:token", get(stream_audio))
+    Router::new()
+        .route("/:token", get(stream_audio))
+        .route("/:token/ws", get(stream_websocket))
+        .route("/:token/hls/index.m3u8", get(hls_playlist))
+        .route("/:token/hls/:segment", get(hls_segment))
+        .route("/:token/playlist.m3u", get(m3u_playlist))
+        .route("/:token/playlist.pls", get(pls_playlist))
+        .route("/:token/playlist.xspf", get(xspf_playlist))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websocket_header_describes_raw_samples() {
+        let config = Config {
+            sample_rate: 44100,
+            channel_count: 2,
+            ..Default::default()
+        };
+
+        let header = WebSocketStreamHeader::new("audio/pcm".to_string(), &config, SampleFormat::Float32);
+        let json: serde_json::Value = serde_json::to_value(&header).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "contentType": "audio/pcm",
+                "sampleRate": 44100,
+                "channelCount": 2,
+                "sampleFormat": "f32",
+            })
+        );
+
+        let header = WebSocketStreamHeader::new("audio/pcm".to_string(), &config, SampleFormat::default());
+        assert_eq!(serde_json::to_value(&header).unwrap()["sampleFormat"], "s16");
+    }
+
+    #[test]
+    fn test_websocket_header_leaves_out_sample_format_of_containers() {
+        let header = WebSocketStreamHeader::new("audio/mpeg".to_string(), &Config::default(), SampleFormat::Int24);
+        let json = serde_json::to_value(&header).unwrap();
+
+        assert!(json.get("sampleFormat").is_none());
+    }
 }